@@ -1,9 +1,29 @@
+use crate::transmission::{DEFAULT_ACK_TIMEOUT, DEFAULT_HEARTBEAT_INTERVAL};
+use common::ack::AckMode;
 use std::env;
 use std::io::{Error, ErrorKind, Result};
+use std::time::Duration;
 
-pub fn parse_server_addr_args() -> Result<String> {
+pub struct ClientArgs {
+    pub server_addr: String,
+    /// Send data records in `DataBatch` datagrams flushed at this interval.
+    pub batch_flush_interval: Option<Duration>,
+    /// Requested from the server at startup when set.
+    pub ack_mode: Option<AckMode>,
+    pub ack_timeout: Duration,
+    pub report_ack_loss: bool,
+    /// `--heartbeat-ms 0` turns heartbeats off.
+    pub heartbeat_interval: Option<Duration>,
+}
+
+pub fn parse_client_args() -> Result<ClientArgs> {
     let mut server = String::from("127.0.0.1");
     let mut port: u16 = 8080;
+    let mut batch_flush_interval = None;
+    let mut ack_mode = None;
+    let mut ack_timeout = DEFAULT_ACK_TIMEOUT;
+    let mut report_ack_loss = false;
+    let mut heartbeat_interval = Some(DEFAULT_HEARTBEAT_INTERVAL);
     let mut args = env::args().skip(1);
 
     while let Some(arg) = args.next() {
@@ -22,8 +42,70 @@ pub fn parse_server_addr_args() -> Result<String> {
                     Error::new(ErrorKind::InvalidInput, format!("invalid port: {value}"))
                 })?;
             }
+            "--batch-ms" => {
+                let value = args.next().ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "missing value for --batch-ms")
+                })?;
+                let ms = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|ms| *ms > 0)
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("invalid batch interval: {value}"),
+                        )
+                    })?;
+                batch_flush_interval = Some(Duration::from_millis(ms));
+            }
+            "--ack-mode" => {
+                let value = args.next().ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "missing value for --ack-mode")
+                })?;
+                ack_mode = Some(
+                    value
+                        .parse::<AckMode>()
+                        .map_err(|err| Error::new(ErrorKind::InvalidInput, err))?,
+                );
+            }
+            "--ack-timeout-ms" => {
+                let value = args.next().ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        "missing value for --ack-timeout-ms",
+                    )
+                })?;
+                let ms = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|ms| *ms > 0)
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("invalid ack timeout: {value}"),
+                        )
+                    })?;
+                ack_timeout = Duration::from_millis(ms);
+            }
+            "--report-ack-loss" => report_ack_loss = true,
+            "--heartbeat-ms" => {
+                let value = args.next().ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidInput, "missing value for --heartbeat-ms")
+                })?;
+                let ms = value.parse::<u64>().map_err(|_| {
+                    Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("invalid heartbeat interval: {value}"),
+                    )
+                })?;
+                heartbeat_interval = (ms > 0).then(|| Duration::from_millis(ms));
+            }
             "-h" | "--help" => {
                 println!("Usage: client [-s|--server <host>] [-p|--port <port>]");
+                println!("              [--batch-ms <ms>]");
+                println!("              [--ack-mode none|every|every:N|delayed:MS|selective:MS]");
+                println!("              [--ack-timeout-ms <ms>] [--report-ack-loss]");
+                println!("              [--heartbeat-ms <ms>]");
                 std::process::exit(0);
             }
             _ => {
@@ -35,5 +117,12 @@ pub fn parse_server_addr_args() -> Result<String> {
         }
     }
 
-    Ok(format!("{server}:{port}"))
+    Ok(ClientArgs {
+        server_addr: format!("{server}:{port}"),
+        batch_flush_interval,
+        ack_mode,
+        ack_timeout,
+        report_ack_loss,
+        heartbeat_interval,
+    })
 }
@@ -1,9 +1,10 @@
 use crate::transmission::{
-    ClientState, ContinuousState, active_peer_node_id, add_peer, clear_profile, next_peer_node_id,
-    register_self, remove_peer, request_topology, run_topology_mixed_classes_test,
-    run_topology_removal_test, run_topology_smoke_test, schedule_burst,
-    select_or_add_peer_for_domain, select_peer, set_profile_burst, set_profile_oscillation,
-    set_profile_ramp, set_profile_steady, unregister_self, update_source_domain,
+    ClientState, ContinuousState, active_peer_node_id, add_peer, clear_profile, export_topology,
+    next_peer_node_id, register_self, remove_peer, request_topology,
+    run_topology_mixed_classes_test, run_topology_removal_test, run_topology_smoke_test,
+    schedule_burst, select_or_add_peer_for_domain, select_peer, set_profile_burst,
+    set_profile_oscillation, set_profile_ramp, set_profile_steady, unregister_self,
+    update_source_domain,
 };
 use common::{EndpointDomain, NodeDomain, NodeId, TrafficClass, WireMessage};
 use crossterm::event::KeyCode;
@@ -24,6 +25,7 @@ pub enum InputCommand {
     UnregisterSelf,
     RequestAnalytics,
     RequestTopology,
+    ExportTopology,
     AddPeer { domain: NodeDomain },
     RemovePeer { node_id: NodeId },
     SelectPeer { node_id: NodeId },
@@ -64,6 +66,7 @@ pub fn handle_input(key: KeyCode, state: &ClientState) -> Option<InputCommand> {
             'x' => Some(InputCommand::UnregisterSelf),
             'r' => Some(InputCommand::RequestAnalytics),
             'p' => Some(InputCommand::RequestTopology),
+            'd' => Some(InputCommand::ExportTopology),
             'f' => Some(InputCommand::SetProfileSteady),
             'z' => Some(InputCommand::SetProfileBurst),
             'w' => Some(InputCommand::SetProfileRamp),
@@ -144,12 +147,12 @@ pub fn execute_command(
         }
         InputCommand::RegisterSelf => {
             register_self(state, socket, server_addr)?;
-            print!("Self node registered");
+            print!("Self node registration sent");
             Ok(())
         }
         InputCommand::UnregisterSelf => {
             unregister_self(state, socket, server_addr)?;
-            print!("Self node unregistered");
+            print!("Self node unregistration sent");
             Ok(())
         }
         InputCommand::RequestAnalytics => {
@@ -164,6 +167,11 @@ pub fn execute_command(
             print!("Requesting topology...");
             Ok(())
         }
+        InputCommand::ExportTopology => {
+            export_topology(state, socket, server_addr)?;
+            print!("Requesting topology for export...");
+            Ok(())
+        }
         InputCommand::AddPeer { domain } => {
             add_peer(state, domain, socket, server_addr)?;
             print!("Added {} peer", format_node_domain(domain));
@@ -1,9 +1,12 @@
-use crate::cli::parse_server_addr_args;
+use crate::cli::{ClientArgs, parse_client_args};
 use crate::input::{execute_command, handle_input};
 use crate::transmission::{
-    ClientState, next_profile_deadline, receive_acks, register_self, send_continuous_packets,
-    send_profile_packets, send_scheduled_packets, unregister_self,
+    ClientState, DataBatcher, flush_data_batch, next_control_deadline, next_profile_deadline,
+    query_ack_mode, receive_acks, register_self, request_ack_mode, retransmit_control_messages,
+    send_ack_loss_report, send_continuous_packets, send_heartbeat, send_profile_packets,
+    send_scheduled_packets, unregister_self,
 };
+use common::ack::AckMode;
 use common::{EndpointDomain, load_or_create_id};
 use crossterm::{
     ExecutableCommand, cursor,
@@ -37,7 +40,8 @@ impl Drop for TerminalGuard {
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
     println!("Program path: {}", args[0]);
-    let server_addr = parse_server_addr_args()?;
+    let args = parse_client_args()?;
+    let server_addr = &args.server_addr;
 
     terminal::enable_raw_mode()?;
     let mut stdout = stdout();
@@ -47,10 +51,10 @@ fn main() -> Result<()> {
 
     print!("Network Traffic simulator");
     stdout.execute(MoveToNextLine(1))?;
-    print!("Send to: {}", &server_addr);
+    print!("Send to: {}", server_addr);
     stdout.execute(MoveToNextLine(1))?;
     print!(
-        "Commands: Space=send | B=burst | 1-9=count | V/X=reg/unreg | N/J=add peer | C=select next | M=remove | F/Z/W/O=profiles | I/E=src | K/L=dst | T/Y/U=topology tests | P=topology | Q=quit"
+        "Commands: Space=send | B=burst | 1-9=count | V/X=reg/unreg | N/J=add peer | C=select next | M=remove | F/Z/W/O=profiles | I/E=src | K/L=dst | T/Y/U=topology tests | P=topology | D=export | Q=quit"
     );
     stdout.execute(MoveToNextLine(1))?;
     print!("Mode: src=external dst=internal");
@@ -62,25 +66,37 @@ fn main() -> Result<()> {
     print!("Peer: active=1/2 id=70656572 domain=internal");
     stdout.execute(MoveToNextLine(1))?;
     print!("Profile: none");
+    stdout.execute(MoveToNextLine(1))?;
+    print!("Server: [no replies yet]");
+    stdout.execute(MoveToNextLine(1))?;
+    print!("Ack loss: [no expired acks]");
+    stdout.execute(MoveToNextLine(1))?;
 
     let socket = open_socket().expect("Couldn't open socket");
     socket.set_nonblocking(true).expect("error on non blocking");
-    let result = run_app(socket, &server_addr);
 
-    result
+    run_app(socket, server_addr, &args)
 }
 
-fn run_app(socket: UdpSocket, server_addr: &str) -> Result<()> {
+fn run_app(socket: UdpSocket, server_addr: &str, args: &ClientArgs) -> Result<()> {
     let node_id = load_or_create_id(Path::new("client_id.txt"))?;
     let mut state = ClientState::new(node_id, DEFAULT_DESC);
-    register_self(&state, &socket, server_addr)?;
+    state.batch = args.batch_flush_interval.map(DataBatcher::new);
+    state.ack_timeout = args.ack_timeout;
+    state.report_ack_loss = args.report_ack_loss;
+    state.heartbeat_interval = args.heartbeat_interval;
+    register_self(&mut state, &socket, server_addr)?;
+    match args.ack_mode {
+        Some(mode) => request_ack_mode(&mut state, &socket, server_addr, mode)?,
+        None => query_ack_mode(&mut state, &socket, server_addr)?,
+    }
 
     loop {
         let timeout = compute_input_timeout(&state, Instant::now());
         if let Ok(Some(key)) = get_input(timeout) {
             match key {
                 KeyCode::Char('q') | KeyCode::Esc => {
-                    let _ = unregister_self(&state, &socket, server_addr);
+                    let _ = unregister_self(&mut state, &socket, server_addr);
                     break;
                 }
                 _ => {
@@ -97,7 +113,11 @@ fn run_app(socket: UdpSocket, server_addr: &str) -> Result<()> {
         send_scheduled_packets(&mut state, &socket, server_addr, Instant::now())?;
         send_continuous_packets(&mut state, &socket, server_addr)?;
         send_profile_packets(&mut state, &socket, server_addr)?;
+        flush_data_batch(&mut state, &socket, server_addr, Instant::now())?;
+        retransmit_control_messages(&mut state, &socket, server_addr, Instant::now())?;
         receive_acks(&mut state, &socket)?;
+        send_ack_loss_report(&mut state, &socket, server_addr, Instant::now())?;
+        send_heartbeat(&mut state, &socket, server_addr, Instant::now())?;
     }
 
     Ok(())
@@ -115,11 +135,15 @@ fn compute_input_timeout(state: &ClientState, now: Instant) -> Duration {
         .as_ref()
         .map(|continuous| continuous.next_send_at);
     let next_profile_deadline = next_profile_deadline(state);
+    let next_control_deadline = next_control_deadline(state);
+    let next_batch_deadline = state.batch.as_ref().and_then(|batch| batch.flush_at);
 
     let next_deadline = [
         next_burst_deadline,
         next_continuous_deadline,
         next_profile_deadline,
+        next_control_deadline,
+        next_batch_deadline,
     ]
     .into_iter()
     .flatten()
@@ -134,12 +158,11 @@ fn compute_input_timeout(state: &ClientState, now: Instant) -> Duration {
 }
 
 fn get_input(timeout: Duration) -> Result<Option<KeyCode>> {
-    if event::poll(timeout)? {
-        if let Event::Key(key) = event::read()? {
-            if key.kind == KeyEventKind::Press {
-                return Ok(Some(key.code));
-            }
-        }
+    if event::poll(timeout)?
+        && let Event::Key(key) = event::read()?
+        && key.kind == KeyEventKind::Press
+    {
+        return Ok(Some(key.code));
     }
     Ok(None)
 }
@@ -154,6 +177,12 @@ fn render_domain_status(state: &ClientState) -> Result<()> {
         format_domain(state.src_domain),
         format_domain(state.dst_domain)
     );
+    if let Some(batch) = &state.batch {
+        print!(" batch={}ms", batch.flush_interval.as_millis());
+    }
+    if state.ack_mode != AckMode::Every {
+        print!(" ack={}", state.ack_mode);
+    }
     out.execute(cursor::RestorePosition)?;
     Ok(())
 }
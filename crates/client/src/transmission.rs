@@ -1,7 +1,10 @@
 use common::{
-    EndpointDomain, NodeDomain, NodeId, TrafficClass, WireMessage,
-    analytics::{AnalyticsSnapshot, TopologySnapshot},
-    make_data_packet, make_register_node_packet, make_unregister_node_packet,
+    DataPacket, EndpointDomain, MAX_DATA_BATCH_RECORDS, NodeDomain, NodeId, RegisterNodePacket,
+    RegisterOutcome, TrafficClass, UnregisterNodePacket, UnregisterOutcome, WireMessage,
+    ack::{AckLossEntry, AckLossReport, AckMode},
+    analytics::{AnalyticsSnapshot, TopologyQuery, TopologySnapshot},
+    make_data_packet, make_heartbeat_packet, make_register_node_packet,
+    make_unregister_node_packet,
 };
 use crossterm::{ExecutableCommand, cursor, terminal};
 use std::{
@@ -11,6 +14,14 @@ use std::{
     time::{Duration, Instant},
 };
 
+/// First retransmission delay for unacknowledged control messages; doubles
+/// on every attempt.
+const CONTROL_RETRY_INITIAL: Duration = Duration::from_millis(250);
+const CONTROL_MAX_ATTEMPTS: u32 = 6;
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(2);
+const ACK_LOSS_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Clone, Copy)]
 pub struct ScheduledSend {
     pub at: Instant,
@@ -24,6 +35,61 @@ pub struct ContinuousState {
     pub interval: Duration,
 }
 
+/// A data send awaiting its ack.
+#[derive(Clone, Copy)]
+pub struct PendingAck {
+    pub sent_at: Instant,
+    pub class: TrafficClass,
+    pub dst_node_id: NodeId,
+}
+
+/// Acks that came back vs. timed out for one class and peer. The
+/// `reported_*` fields mark what the server has already been told.
+#[derive(Default, Clone, Copy)]
+pub struct AckLossCounters {
+    pub acked: u64,
+    pub lost: u64,
+    reported_acked: u64,
+    reported_lost: u64,
+}
+
+impl AckLossCounters {
+    fn loss_rate(&self) -> f64 {
+        match self.acked + self.lost {
+            0 => 0.0,
+            total => self.lost as f64 / total as f64,
+        }
+    }
+}
+
+/// Data records waiting to go out as one `DataBatch`.
+pub struct DataBatcher {
+    pub flush_interval: Duration,
+    pub records: Vec<DataPacket>,
+    /// Set when the first record is queued.
+    pub flush_at: Option<Instant>,
+}
+
+impl DataBatcher {
+    pub fn new(flush_interval: Duration) -> Self {
+        Self {
+            flush_interval,
+            records: Vec::with_capacity(MAX_DATA_BATCH_RECORDS),
+            flush_at: None,
+        }
+    }
+}
+
+/// A register/unregister, ack mode change or server info request awaiting
+/// its reply.
+pub struct PendingControl {
+    pub message: WireMessage,
+    pub node_id: NodeId,
+    pub attempts: u32,
+    pub next_retry_at: Instant,
+    pub backoff: Duration,
+}
+
 #[derive(Clone, Copy)]
 pub struct PeerNode {
     pub node_id: NodeId,
@@ -44,14 +110,46 @@ pub struct ClientState {
     pub next_global_seq: u32,
     pub next_class_seq: HashMap<TrafficClass, u32>,
     pub queue: VecDeque<ScheduledSend>,
-    pub pending_acks: HashMap<u32, Instant>,
+    pub pending_acks: HashMap<u32, PendingAck>,
+    /// Pending acks older than this (plus the ack mode's hold time) count
+    /// as lost on the reverse path.
+    pub ack_timeout: Duration,
+    pub last_ack_expiry_at: Instant,
+    pub ack_loss: HashMap<(TrafficClass, NodeId), AckLossCounters>,
+    /// Send `AckLossReport`s so edge snapshots include reverse-path loss.
+    pub report_ack_loss: bool,
+    pub last_ack_loss_report_at: Instant,
+    /// Keeps this node from going suspect on the server while idle; `None`
+    /// sends no heartbeats.
+    pub heartbeat_interval: Option<Duration>,
+    pub last_heartbeat_at: Instant,
+    /// How the server acknowledges this session's data, as last confirmed
+    /// by an `AckModeSet` or learned from `ServerInfo`.
+    pub ack_mode: AckMode,
+    /// Whether `ack_mode` came from the server; until then expired acks do
+    /// not count as reverse-path loss.
+    pub ack_mode_known: bool,
+    /// `SetAckMode` or `RequestServerInfo` resent until the server answers.
+    pub pending_ack_mode: Option<PendingControl>,
+    /// Sends confirmed by any kind of ack.
+    pub delivered: u64,
+    /// Batch data records instead of one datagram per packet.
+    pub batch: Option<DataBatcher>,
+    /// Control session of this process; see `RegisterNodePacket::session`.
+    pub control_session: u32,
+    pub next_control_id: u32,
+    pub pending_controls: HashMap<u32, PendingControl>,
+    /// RTT samples taken.
     pub total_acks: u64,
+    pub last_rtt: Duration,
     pub min_rtt: Duration,
     pub max_rtt: Duration,
     pub sum_rtt: Duration,
     pub continuous_state: Option<ContinuousState>,
     pub active_profile: Option<ActiveProfile>,
     pub pending_topology_expectation: Option<TopologyExpectation>,
+    /// Write the next topology snapshot to GraphML, GEXF and DOT files.
+    pub pending_topology_export: bool,
 }
 
 impl ClientState {
@@ -86,13 +184,30 @@ impl ClientState {
             next_class_seq: init_class_seq,
             queue: VecDeque::new(),
             pending_acks: HashMap::new(),
+            ack_timeout: DEFAULT_ACK_TIMEOUT,
+            last_ack_expiry_at: Instant::now(),
+            ack_loss: HashMap::new(),
+            report_ack_loss: false,
+            last_ack_loss_report_at: Instant::now(),
+            heartbeat_interval: Some(DEFAULT_HEARTBEAT_INTERVAL),
+            last_heartbeat_at: Instant::now(),
+            ack_mode: AckMode::Every,
+            ack_mode_known: false,
+            pending_ack_mode: None,
+            delivered: 0,
+            batch: None,
+            control_session: common::new_session_id(),
+            next_control_id: 1,
+            pending_controls: HashMap::new(),
             total_acks: 0,
+            last_rtt: Duration::ZERO,
             min_rtt: Duration::MAX,
             max_rtt: Duration::ZERO,
             sum_rtt: Duration::ZERO,
             continuous_state: None,
             active_profile: None,
             pending_topology_expectation: None,
+            pending_topology_export: false,
         }
     }
 }
@@ -193,10 +308,10 @@ fn select_first_peer_for_domain(
 }
 
 fn destination_peer(state: &mut ClientState, requested_domain: EndpointDomain) -> PeerNode {
-    if let Some(peer) = active_peer(state) {
-        if endpoint_domain_from_node_domain(peer.domain) == requested_domain {
-            return peer;
-        }
+    if let Some(peer) = active_peer(state)
+        && endpoint_domain_from_node_domain(peer.domain) == requested_domain
+    {
+        return peer;
     }
 
     if let Some(peer) = select_first_peer_for_domain(state, requested_domain) {
@@ -311,60 +426,293 @@ fn send_data_packet(
         declared_bytes,
         state.desc,
     );
-    let bytes = encode_wire_message(&WireMessage::Data(pkt))?;
-    let send_time = Instant::now();
-    socket.send_to(&bytes, server_addr)?;
-
-    state.pending_acks.insert(state.next_global_seq, send_time);
     state.next_global_seq = state.next_global_seq.wrapping_add(1);
     state
         .next_class_seq
         .insert(class, class_seq.wrapping_add(1));
+
+    let Some(batch) = state.batch.as_mut() else {
+        let bytes = encode_wire_message(&WireMessage::Data(pkt))?;
+        let send_time = Instant::now();
+        socket.send_to(&bytes, server_addr)?;
+        track_pending_ack(state, &pkt, send_time);
+        return Ok(());
+    };
+    let now = Instant::now();
+    batch.records.push(pkt);
+    batch.flush_at.get_or_insert(now + batch.flush_interval);
+    if batch.records.len() >= MAX_DATA_BATCH_RECORDS {
+        send_data_batch(state, socket, server_addr)?;
+    }
+    Ok(())
+}
+
+/// Sends the pending batch once its flush interval has elapsed.
+pub fn flush_data_batch(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+    now: Instant,
+) -> Result<()> {
+    match state.batch.as_ref().and_then(|batch| batch.flush_at) {
+        Some(flush_at) if flush_at <= now => send_data_batch(state, socket, server_addr),
+        _ => Ok(()),
+    }
+}
+
+fn send_data_batch(state: &mut ClientState, socket: &UdpSocket, server_addr: &str) -> Result<()> {
+    let Some(batch) = state.batch.as_mut() else {
+        return Ok(());
+    };
+    batch.flush_at = None;
+    if batch.records.is_empty() {
+        return Ok(());
+    }
+    let mut records = std::mem::replace(
+        &mut batch.records,
+        Vec::with_capacity(MAX_DATA_BATCH_RECORDS),
+    );
+    // Stamp at send time so the server's one-way latency leaves out the
+    // time records spent waiting in the batch.
+    let send_time = Instant::now();
+    let timestamp_us = common::now_timestamp_us();
+    for record in &mut records {
+        record.timestamp_us = timestamp_us;
+        track_pending_ack(state, record, send_time);
+    }
+    let bytes = encode_wire_message(&WireMessage::DataBatch(records))?;
+    socket.send_to(&bytes, server_addr)?;
     Ok(())
 }
 
+fn track_pending_ack(state: &mut ClientState, pkt: &DataPacket, sent_at: Instant) {
+    if state.ack_mode != AckMode::None {
+        state.pending_acks.insert(
+            pkt.global_seq,
+            PendingAck {
+                sent_at,
+                class: pkt.class,
+                dst_node_id: pkt.dst_node_id,
+            },
+        );
+    }
+}
+
 fn send_register_node(
+    state: &mut ClientState,
     socket: &UdpSocket,
     server_addr: &str,
     node_id: NodeId,
     desc: [u8; 16],
     domain: NodeDomain,
 ) -> Result<()> {
-    let pkt = make_register_node_packet(node_id, desc, domain);
-    let bytes = encode_wire_message(&WireMessage::RegisterNode(pkt))?;
-    socket.send_to(&bytes, server_addr)?;
-    Ok(())
+    let control_id = allocate_control_id(state);
+    let pkt = RegisterNodePacket {
+        control_id,
+        session: state.control_session,
+        ..make_register_node_packet(node_id, desc, domain)
+    };
+    send_control(
+        state,
+        socket,
+        server_addr,
+        control_id,
+        node_id,
+        WireMessage::RegisterNode(pkt),
+    )
 }
 
-fn send_register_self(state: &ClientState, socket: &UdpSocket, server_addr: &str) -> Result<()> {
-    send_register_node(
+fn send_register_self(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+) -> Result<()> {
+    let (node_id, desc, domain) = (state.node_id, state.desc, state.node_domain);
+    send_register_node(state, socket, server_addr, node_id, desc, domain)
+}
+
+fn send_unregister_node(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+    node_id: NodeId,
+) -> Result<()> {
+    let control_id = allocate_control_id(state);
+    let pkt = UnregisterNodePacket {
+        control_id,
+        session: state.control_session,
+        ..make_unregister_node_packet(node_id)
+    };
+    send_control(
+        state,
         socket,
         server_addr,
-        state.node_id,
-        state.desc,
-        state.node_domain,
+        control_id,
+        node_id,
+        WireMessage::UnregisterNode(pkt),
     )
 }
 
-fn send_unregister_node(socket: &UdpSocket, server_addr: &str, node_id: NodeId) -> Result<()> {
-    let pkt = make_unregister_node_packet(node_id);
-    let bytes = encode_wire_message(&WireMessage::UnregisterNode(pkt))?;
+/// Control ids skip 0, which tells the server not to deduplicate.
+fn allocate_control_id(state: &mut ClientState) -> u32 {
+    let control_id = state.next_control_id;
+    state.next_control_id = state.next_control_id.wrapping_add(1).max(1);
+    control_id
+}
+
+fn send_control(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+    control_id: u32,
+    node_id: NodeId,
+    message: WireMessage,
+) -> Result<()> {
+    let bytes = encode_wire_message(&message)?;
     socket.send_to(&bytes, server_addr)?;
+    state.pending_controls.insert(
+        control_id,
+        PendingControl {
+            message,
+            node_id,
+            attempts: 1,
+            next_retry_at: Instant::now() + CONTROL_RETRY_INITIAL,
+            backoff: CONTROL_RETRY_INITIAL,
+        },
+    );
     Ok(())
 }
 
+/// Resends control messages whose ack is overdue, doubling the delay each
+/// time, and gives up after `CONTROL_MAX_ATTEMPTS`.
+pub fn retransmit_control_messages(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+    now: Instant,
+) -> Result<()> {
+    let due: Vec<u32> = state
+        .pending_controls
+        .iter()
+        .filter(|(_, pending)| pending.next_retry_at <= now)
+        .map(|(control_id, _)| *control_id)
+        .collect();
+
+    for control_id in due {
+        let Some(pending) = state.pending_controls.get_mut(&control_id) else {
+            continue;
+        };
+        if !retransmit_control(pending, socket, server_addr, now)? {
+            state.pending_controls.remove(&control_id);
+        }
+    }
+    if let Some(pending) = state.pending_ack_mode.as_mut()
+        && pending.next_retry_at <= now
+        && !retransmit_control(pending, socket, server_addr, now)?
+    {
+        state.pending_ack_mode = None;
+    }
+    Ok(())
+}
+
+/// Resends `pending`, or reports it and returns false once it has used up
+/// its attempts.
+fn retransmit_control(
+    pending: &mut PendingControl,
+    socket: &UdpSocket,
+    server_addr: &str,
+    now: Instant,
+) -> Result<bool> {
+    if pending.attempts >= CONTROL_MAX_ATTEMPTS {
+        render_server_status(&format!(
+            "Server: {} {} not acknowledged after {} attempts",
+            short_node_id(&pending.node_id),
+            control_kind(&pending.message),
+            pending.attempts
+        ))?;
+        return Ok(false);
+    }
+    let bytes = encode_wire_message(&pending.message)?;
+    socket.send_to(&bytes, server_addr)?;
+    pending.attempts += 1;
+    pending.backoff *= 2;
+    pending.next_retry_at = now + pending.backoff;
+    Ok(true)
+}
+
+pub fn next_control_deadline(state: &ClientState) -> Option<Instant> {
+    state
+        .pending_controls
+        .values()
+        .chain(&state.pending_ack_mode)
+        .map(|pending| pending.next_retry_at)
+        .min()
+}
+
+fn control_kind(message: &WireMessage) -> &'static str {
+    match message {
+        WireMessage::UnregisterNode(_) => "unregister",
+        WireMessage::SetAckMode(_) => "ack mode change",
+        WireMessage::RequestServerInfo => "server info request",
+        _ => "register",
+    }
+}
+
+fn render_control_ack(
+    state: &mut ClientState,
+    control_id: u32,
+    node_id: NodeId,
+    outcome: &str,
+) -> Result<()> {
+    // Retransmissions can yield duplicate acks; only the first one counts.
+    let Some(pending) = state.pending_controls.remove(&control_id) else {
+        return Ok(());
+    };
+    let attempts = match pending.attempts {
+        1 => String::new(),
+        n => format!(" after {n} attempts"),
+    };
+    render_server_status(&format!(
+        "Server: {} {}{}",
+        short_node_id(&node_id),
+        outcome,
+        attempts
+    ))
+}
+
 pub fn request_topology(socket: &UdpSocket, server_addr: &str) -> Result<()> {
     let pkt = encode_wire_message(&WireMessage::RequestTopology)?;
     socket.send_to(&pkt, server_addr)?;
     Ok(())
 }
 
-pub fn register_self(state: &ClientState, socket: &UdpSocket, server_addr: &str) -> Result<()> {
+/// Asks for an unfiltered snapshot to write to files. A filtered request
+/// leaves the change-log cursor and loss windows of plain `RequestTopology`
+/// to the clients that poll it.
+pub fn export_topology(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+) -> Result<()> {
+    state.pending_topology_export = true;
+    let pkt = encode_wire_message(&WireMessage::RequestFilteredTopology(
+        TopologyQuery::default(),
+    ))?;
+    socket.send_to(&pkt, server_addr)?;
+    Ok(())
+}
+
+pub fn register_self(state: &mut ClientState, socket: &UdpSocket, server_addr: &str) -> Result<()> {
     send_register_self(state, socket, server_addr)
 }
 
-pub fn unregister_self(state: &ClientState, socket: &UdpSocket, server_addr: &str) -> Result<()> {
-    send_unregister_node(socket, server_addr, state.node_id)
+pub fn unregister_self(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+) -> Result<()> {
+    let node_id = state.node_id;
+    send_unregister_node(state, socket, server_addr, node_id)
 }
 
 pub fn update_source_domain(
@@ -386,7 +734,14 @@ pub fn add_peer(
 ) -> Result<()> {
     let endpoint_domain = endpoint_domain_from_node_domain(domain);
     let peer = add_peer_local(state, endpoint_domain);
-    send_register_node(socket, server_addr, peer.node_id, peer.desc, peer.domain)?;
+    send_register_node(
+        state,
+        socket,
+        server_addr,
+        peer.node_id,
+        peer.desc,
+        peer.domain,
+    )?;
     render_peer_status(state)
 }
 
@@ -401,11 +756,12 @@ pub fn remove_active_peer(
     }
 
     let removed = state.peers.remove(state.active_peer_index);
-    send_unregister_node(socket, server_addr, removed.node_id)?;
+    send_unregister_node(state, socket, server_addr, removed.node_id)?;
 
     if state.peers.is_empty() {
         let replacement = add_peer_local(state, EndpointDomain::Internal);
         send_register_node(
+            state,
             socket,
             server_addr,
             replacement.node_id,
@@ -432,7 +788,14 @@ pub fn select_or_add_peer_for_domain(
 ) -> Result<()> {
     if select_first_peer_for_domain(state, domain).is_none() {
         let peer = add_peer_local(state, domain);
-        send_register_node(socket, server_addr, peer.node_id, peer.desc, peer.domain)?;
+        send_register_node(
+            state,
+            socket,
+            server_addr,
+            peer.node_id,
+            peer.desc,
+            peer.domain,
+        )?;
     }
     state.dst_domain = domain;
     render_peer_status(state)
@@ -652,6 +1015,16 @@ pub fn send_profile_packets(
     Ok(())
 }
 
+fn render_server_status(message: &str) -> Result<()> {
+    let mut out = stdout();
+    out.execute(cursor::SavePosition)?;
+    out.execute(cursor::MoveTo(0, 8))?;
+    out.execute(terminal::Clear(terminal::ClearType::CurrentLine))?;
+    print!("{message}");
+    out.execute(cursor::RestorePosition)?;
+    Ok(())
+}
+
 fn render_profile_status(message: &str) -> Result<()> {
     let mut out = stdout();
     out.execute(cursor::SavePosition)?;
@@ -680,6 +1053,8 @@ pub fn run_topology_smoke_test(
         1200,
         EndpointDomain::External,
     )?;
+    // Batched records must reach the server before the snapshot request.
+    send_data_batch(state, socket, server_addr)?;
     request_topology(socket, server_addr)?;
     state.pending_topology_expectation = Some(TopologyExpectation::Smoke {
         node_id: state.node_id,
@@ -697,7 +1072,8 @@ pub fn run_topology_removal_test(
     state.continuous_state = None;
     state.node_domain = NodeDomain::Internal;
     send_register_self(state, socket, server_addr)?;
-    send_unregister_node(socket, server_addr, state.node_id)?;
+    let node_id = state.node_id;
+    send_unregister_node(state, socket, server_addr, node_id)?;
     request_topology(socket, server_addr)?;
     state.pending_topology_expectation = Some(TopologyExpectation::Removal {
         node_id: state.node_id,
@@ -731,6 +1107,8 @@ pub fn run_topology_mixed_classes_test(
             EndpointDomain::External,
         )?;
     }
+    // Batched records must reach the server before the snapshot request.
+    send_data_batch(state, socket, server_addr)?;
     request_topology(socket, server_addr)?;
     state.pending_topology_expectation = Some(TopologyExpectation::MixedClasses {
         node_id: state.node_id,
@@ -784,6 +1162,232 @@ pub fn schedule_burst(
     }
 }
 
+/// Marks `seq` delivered; `false` if it was not outstanding. RTT is only
+/// sampled under immediate ack modes, since delayed and selective acks
+/// would fold the server's hold time into it.
+fn record_ack(state: &mut ClientState, seq: u32) -> bool {
+    let Some(pending) = state.pending_acks.remove(&seq) else {
+        return false;
+    };
+    state.delivered += 1;
+    if state.ack_mode_known {
+        state
+            .ack_loss
+            .entry((pending.class, pending.dst_node_id))
+            .or_default()
+            .acked += 1;
+    }
+    if state.ack_mode.is_immediate() {
+        let rtt = Instant::now() - pending.sent_at;
+        state.total_acks += 1;
+        state.last_rtt = rtt;
+        state.min_rtt = state.min_rtt.min(rtt);
+        state.max_rtt = state.max_rtt.max(rtt);
+        state.sum_rtt += rtt;
+    }
+    true
+}
+
+fn render_ack_stats(state: &ClientState, seq: u32) -> Result<()> {
+    let mut out = stdout();
+    out.execute(cursor::SavePosition)?;
+    out.execute(cursor::MoveTo(0, 4))?;
+    out.execute(terminal::Clear(terminal::ClearType::CurrentLine))?;
+    if state.ack_mode.is_immediate() && state.total_acks > 0 {
+        print!(
+            "Stats: ACK seq={:5} | RTT={:4}µs | min={:4} max={:4} avg={:4}",
+            seq,
+            state.last_rtt.as_micros(),
+            state.min_rtt.as_micros(),
+            state.max_rtt.as_micros(),
+            (state.sum_rtt.as_micros() / state.total_acks as u128)
+        );
+    } else {
+        print!(
+            "Stats: ACK seq={:5} | delivered={} | ack mode {} (no RTT samples)",
+            seq, state.delivered, state.ack_mode
+        );
+    }
+    out.execute(cursor::RestorePosition)?;
+    Ok(())
+}
+
+/// Expires pending acks past the timeout, at most ten times per timeout.
+/// They count as reverse-path loss only when the ack mode acknowledges
+/// every record; under every-N most sends are never meant to be acked.
+fn expire_pending_acks(state: &mut ClientState, now: Instant) -> Result<()> {
+    if now.duration_since(state.last_ack_expiry_at) < state.ack_timeout / 10 {
+        return Ok(());
+    }
+    state.last_ack_expiry_at = now;
+    let deadline = state.ack_timeout + state.ack_mode.hold_time();
+    let expired: Vec<u32> = state
+        .pending_acks
+        .iter()
+        .filter(|(_, pending)| now.duration_since(pending.sent_at) >= deadline)
+        .map(|(seq, _)| *seq)
+        .collect();
+    if expired.is_empty() {
+        return Ok(());
+    }
+
+    let count_loss = state.ack_mode_known && state.ack_mode.acks_every_record();
+    for seq in expired {
+        if let Some(pending) = state.pending_acks.remove(&seq)
+            && count_loss
+        {
+            state
+                .ack_loss
+                .entry((pending.class, pending.dst_node_id))
+                .or_default()
+                .lost += 1;
+        }
+    }
+    if count_loss {
+        render_ack_loss_status(state)?;
+    }
+    Ok(())
+}
+
+fn render_ack_loss_status(state: &ClientState) -> Result<()> {
+    let total = state
+        .ack_loss
+        .values()
+        .fold(AckLossCounters::default(), |sum, counters| {
+            AckLossCounters {
+                acked: sum.acked + counters.acked,
+                lost: sum.lost + counters.lost,
+                ..sum
+            }
+        });
+    let worst = state
+        .ack_loss
+        .iter()
+        .filter(|(_, counters)| counters.lost > 0)
+        .max_by(|(_, a), (_, b)| a.loss_rate().total_cmp(&b.loss_rate()));
+
+    let mut out = stdout();
+    out.execute(cursor::SavePosition)?;
+    out.execute(cursor::MoveTo(0, 9))?;
+    out.execute(terminal::Clear(terminal::ClearType::CurrentLine))?;
+    print!(
+        "Ack loss: {:.2}% ({}/{})",
+        total.loss_rate() * 100.0,
+        total.lost,
+        total.acked + total.lost
+    );
+    if let Some(((class, dst_node_id), counters)) = worst {
+        print!(
+            " | worst {}→{} {:.2}%",
+            class,
+            short_node_id(dst_node_id),
+            counters.loss_rate() * 100.0
+        );
+    }
+    out.execute(cursor::RestorePosition)?;
+    Ok(())
+}
+
+/// Sends per-class, per-peer ack counts accumulated since the last report.
+pub fn send_ack_loss_report(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+    now: Instant,
+) -> Result<()> {
+    if !state.report_ack_loss
+        || now.duration_since(state.last_ack_loss_report_at) < ACK_LOSS_REPORT_INTERVAL
+    {
+        return Ok(());
+    }
+    state.last_ack_loss_report_at = now;
+
+    let mut entries = Vec::new();
+    for ((class, dst_node_id), counters) in state.ack_loss.iter_mut() {
+        let acked = counters.acked - counters.reported_acked;
+        let lost = counters.lost - counters.reported_lost;
+        if acked + lost == 0 {
+            continue;
+        }
+        counters.reported_acked = counters.acked;
+        counters.reported_lost = counters.lost;
+        entries.push(AckLossEntry {
+            dst_node_id: *dst_node_id,
+            class: *class,
+            acked: acked.min(u32::MAX as u64) as u32,
+            lost: lost.min(u32::MAX as u64) as u32,
+        });
+    }
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let report = AckLossReport {
+        src_node_id: state.node_id,
+        entries,
+    };
+    let bytes = encode_wire_message(&WireMessage::AckLossReport(report))?;
+    socket.send_to(&bytes, server_addr)?;
+    Ok(())
+}
+
+pub fn send_heartbeat(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+    now: Instant,
+) -> Result<()> {
+    match state.heartbeat_interval {
+        Some(interval) if now.duration_since(state.last_heartbeat_at) >= interval => {}
+        _ => return Ok(()),
+    }
+    state.last_heartbeat_at = now;
+    let bytes = encode_wire_message(&WireMessage::Heartbeat(make_heartbeat_packet(
+        state.node_id,
+    )))?;
+    socket.send_to(&bytes, server_addr)?;
+    Ok(())
+}
+
+/// Asks the server to acknowledge this session's data with `mode`. The
+/// request is resent like a control message and `ack_mode` only changes
+/// once the server answers with `AckModeSet`.
+pub fn request_ack_mode(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+    mode: AckMode,
+) -> Result<()> {
+    send_ack_mode_control(state, socket, server_addr, WireMessage::SetAckMode(mode))
+}
+
+/// Learns the server's default ack mode from `ServerInfo`, for sessions
+/// that do not request their own.
+pub fn query_ack_mode(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+) -> Result<()> {
+    send_ack_mode_control(state, socket, server_addr, WireMessage::RequestServerInfo)
+}
+
+fn send_ack_mode_control(
+    state: &mut ClientState,
+    socket: &UdpSocket,
+    server_addr: &str,
+    message: WireMessage,
+) -> Result<()> {
+    let bytes = encode_wire_message(&message)?;
+    socket.send_to(&bytes, server_addr)?;
+    state.pending_ack_mode = Some(PendingControl {
+        message,
+        node_id: state.node_id,
+        attempts: 1,
+        next_retry_at: Instant::now() + CONTROL_RETRY_INITIAL,
+        backoff: CONTROL_RETRY_INITIAL,
+    });
+    Ok(())
+}
+
 pub fn receive_acks(state: &mut ClientState, socket: &UdpSocket) -> Result<()> {
     let mut buf = [0u8; 8192];
 
@@ -793,38 +1397,111 @@ pub fn receive_acks(state: &mut ClientState, socket: &UdpSocket) -> Result<()> {
                 if let Ok(message) = common::decode_message(&buf[..amt]) {
                     match message {
                         WireMessage::Ack(ack) => {
-                            if let Some(send_time) = state.pending_acks.remove(&ack.original_seq) {
-                                let rtt = Instant::now() - send_time;
-
-                                state.total_acks += 1;
-                                state.min_rtt = state.min_rtt.min(rtt);
-                                state.max_rtt = state.max_rtt.max(rtt);
-                                state.sum_rtt += rtt;
-
-                                let mut out = stdout();
-                                out.execute(cursor::SavePosition)?;
-                                out.execute(cursor::MoveTo(0, 4))?;
-                                out.execute(terminal::Clear(terminal::ClearType::CurrentLine))?;
-                                print!(
-                                    "Stats: ACK seq={:5} | RTT={:4}µs | min={:4} max={:4} avg={:4}",
-                                    ack.original_seq,
-                                    rtt.as_micros(),
-                                    state.min_rtt.as_micros(),
-                                    state.max_rtt.as_micros(),
-                                    (state.sum_rtt.as_micros() / state.total_acks as u128)
-                                );
-                                out.execute(cursor::RestorePosition)?;
+                            if record_ack(state, ack.original_seq) {
+                                render_ack_stats(state, ack.original_seq)?;
+                            }
+                        }
+                        WireMessage::AckBatch(ack) => {
+                            let last = ack
+                                .original_seqs
+                                .iter()
+                                .filter(|seq| record_ack(state, **seq))
+                                .last();
+                            if let Some(seq) = last {
+                                render_ack_stats(state, *seq)?;
+                            }
+                        }
+                        WireMessage::SelectiveAck(sack) => {
+                            let last = sack
+                                .received_seqs()
+                                .filter(|seq| record_ack(state, *seq))
+                                .last();
+                            if let Some(seq) = last {
+                                render_ack_stats(state, seq)?;
+                            }
+                        }
+                        WireMessage::AckModeSet(mode) => {
+                            state.ack_mode = mode;
+                            state.ack_mode_known = true;
+                            let attempts = match state.pending_ack_mode.take_if(|pending| {
+                                matches!(pending.message, WireMessage::SetAckMode(requested) if requested == mode)
+                            }) {
+                                Some(pending) if pending.attempts > 1 => {
+                                    format!(" after {} attempts", pending.attempts)
+                                }
+                                _ => String::new(),
+                            };
+                            render_server_status(&format!("Server: ack mode {mode}{attempts}"))?;
+                        }
+                        WireMessage::ServerInfo(info) => {
+                            // A pending `SetAckMode` overrides the default.
+                            if state
+                                .pending_ack_mode
+                                .take_if(|pending| {
+                                    matches!(pending.message, WireMessage::RequestServerInfo)
+                                })
+                                .is_some()
+                            {
+                                state.ack_mode = info.config.server.ack_mode;
+                                state.ack_mode_known = true;
                             }
                         }
                         WireMessage::Analytics(snapshot) => display_analytics(&snapshot),
                         WireMessage::Topology(snapshot) => {
                             display_topology_snapshot(state, &snapshot)?;
                         }
+                        WireMessage::RegisterAck(ack) => {
+                            let outcome = match ack.outcome {
+                                RegisterOutcome::Registered => "registered",
+                                RegisterOutcome::Refreshed => "registration refreshed",
+                                RegisterOutcome::NodeLimitReached => {
+                                    "registration rejected (node limit reached)"
+                                }
+                                RegisterOutcome::Superseded => "registration superseded",
+                            };
+                            render_control_ack(state, ack.control_id, ack.node_id, outcome)?;
+                        }
+                        WireMessage::UnregisterAck(ack) => {
+                            let outcome = match ack.outcome {
+                                UnregisterOutcome::Unregistered => "unregistered",
+                                UnregisterOutcome::UnknownNode => "was not registered",
+                                UnregisterOutcome::Superseded => "unregistration superseded",
+                            };
+                            render_control_ack(state, ack.control_id, ack.node_id, outcome)?;
+                        }
+                        WireMessage::Error {
+                            code,
+                            detail,
+                            related_seq,
+                        } => {
+                            let seq = related_seq
+                                .map(|seq| format!(" (seq={seq})"))
+                                .unwrap_or_default();
+                            render_server_status(&format!("Server: error {code}{seq}: {detail}"))?;
+                        }
                         WireMessage::Data(_)
                         | WireMessage::RequestAnalytics
                         | WireMessage::RegisterNode(_)
                         | WireMessage::UnregisterNode(_)
-                        | WireMessage::RequestTopology => {}
+                        | WireMessage::RequestTopology
+                        | WireMessage::RequestGroupedTopology { .. }
+                        | WireMessage::RequestFilteredTopology(_)
+                        | WireMessage::RequestPath { .. }
+                        | WireMessage::Path(_)
+                        | WireMessage::Subscribe(_)
+                        | WireMessage::Unsubscribe
+                        | WireMessage::RequestServerInfo
+                        | WireMessage::Admin(_)
+                        | WireMessage::AdminReply(_)
+                        | WireMessage::DataBatch(_)
+                        | WireMessage::SetAckMode(_)
+                        | WireMessage::AckLossReport(_)
+                        | WireMessage::Heartbeat(_)
+                        | WireMessage::RequestEvents { .. }
+                        | WireMessage::Events(_)
+                        | WireMessage::SubscribeEvents { .. }
+                        | WireMessage::EventsSubscribed { .. }
+                        | WireMessage::UnsubscribeEvents => {}
                     }
                 }
             }
@@ -836,7 +1513,7 @@ pub fn receive_acks(state: &mut ClientState, socket: &UdpSocket) -> Result<()> {
         }
     }
 
-    Ok(())
+    expire_pending_acks(state, Instant::now())
 }
 
 pub fn send_continuous_packets(
@@ -955,7 +1632,11 @@ fn display_topology_snapshot(state: &mut ClientState, snapshot: &TopologySnapsho
         snapshot.global_stats.total_packets
     );
 
-    let status = if let Some(expectation) = state.pending_topology_expectation.take() {
+    let status = if std::mem::take(&mut state.pending_topology_export) {
+        // A full disk should not take the client down with it.
+        export_topology_files(snapshot)
+            .unwrap_or_else(|err| format!("Topology export failed: {err}"))
+    } else if let Some(expectation) = state.pending_topology_expectation.take() {
         validate_topology_expectation(expectation, snapshot)
     } else {
         format!("{base} (no active test)")
@@ -964,6 +1645,19 @@ fn display_topology_snapshot(state: &mut ClientState, snapshot: &TopologySnapsho
     render_topology_status(&status)
 }
 
+fn export_topology_files(snapshot: &TopologySnapshot) -> Result<String> {
+    // Peeked snapshots can share a sequence number; the timestamp cannot.
+    let stem = format!("topology-{}", snapshot.snapshot_timestamp_epoch_us / 1_000);
+    std::fs::write(format!("{stem}.graphml"), snapshot.to_graphml())?;
+    std::fs::write(format!("{stem}.gexf"), snapshot.to_gexf())?;
+    std::fs::write(format!("{stem}.dot"), snapshot.to_dot())?;
+    Ok(format!(
+        "Topology exported: {stem}.graphml/.gexf/.dot nodes={} edges={}",
+        snapshot.nodes.len(),
+        snapshot.edges.len()
+    ))
+}
+
 fn validate_topology_expectation(
     expectation: TopologyExpectation,
     snapshot: &TopologySnapshot,
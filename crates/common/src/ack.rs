@@ -0,0 +1,157 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{NodeId, TrafficClass};
+use std::time::Duration;
+
+/// Longest `Delayed` or `Selective` hold a session may ask for.
+pub const MAX_ACK_HOLD_MS: u32 = 60_000;
+
+/// How the server acknowledges data records from a UDP session. The
+/// server default comes from `server.ack_mode`; a client can switch its own
+/// session with `SetAckMode`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AckMode {
+    /// Never acknowledge data, for throughput tests.
+    None,
+    /// Acknowledge every record immediately, for RTT studies.
+    #[default]
+    Every,
+    /// Acknowledge every Nth record immediately.
+    EveryN(u32),
+    /// Hold acks and send them as one `AckBatch` after `max_delay_ms`.
+    Delayed { max_delay_ms: u32 },
+    /// Report received sequence numbers as `SelectiveAck` bitmaps every
+    /// `interval_ms`, for loss studies.
+    Selective { interval_ms: u32 },
+}
+
+impl AckMode {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            AckMode::EveryN(0) => Err("EveryN must be at least 1".to_string()),
+            AckMode::Delayed { max_delay_ms: 0 } => {
+                Err("Delayed max_delay_ms must be greater than 0".to_string())
+            }
+            AckMode::Selective { interval_ms: 0 } => {
+                Err("Selective interval_ms must be greater than 0".to_string())
+            }
+            AckMode::Delayed { max_delay_ms } if *max_delay_ms > MAX_ACK_HOLD_MS => Err(format!(
+                "Delayed max_delay_ms must be at most {MAX_ACK_HOLD_MS}"
+            )),
+            AckMode::Selective { interval_ms } if *interval_ms > MAX_ACK_HOLD_MS => Err(format!(
+                "Selective interval_ms must be at most {MAX_ACK_HOLD_MS}"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether acks leave the server as soon as the record is processed,
+    /// so that send-to-ack time is a true RTT.
+    pub fn is_immediate(&self) -> bool {
+        matches!(self, AckMode::Every | AckMode::EveryN(_))
+    }
+
+    /// Whether every received record is eventually acknowledged, so a
+    /// missing ack means loss rather than policy.
+    pub fn acks_every_record(&self) -> bool {
+        !matches!(self, AckMode::None | AckMode::EveryN(2..))
+    }
+
+    /// Longest time the server may hold an ack before sending it.
+    pub fn hold_time(&self) -> Duration {
+        match self {
+            AckMode::Delayed { max_delay_ms } => Duration::from_millis(*max_delay_ms as u64),
+            AckMode::Selective { interval_ms } => Duration::from_millis(*interval_ms as u64),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Short form used on the command line: `none`, `every`, `every:N`,
+/// `delayed:MS` or `selective:MS`.
+impl Display for AckMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AckMode::None => write!(f, "none"),
+            AckMode::Every => write!(f, "every"),
+            AckMode::EveryN(n) => write!(f, "every:{n}"),
+            AckMode::Delayed { max_delay_ms } => write!(f, "delayed:{max_delay_ms}"),
+            AckMode::Selective { interval_ms } => write!(f, "selective:{interval_ms}"),
+        }
+    }
+}
+
+impl FromStr for AckMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let number = |default: u32| match arg {
+            Some(arg) => arg
+                .parse::<u32>()
+                .map_err(|_| format!("invalid number in ack mode: {arg}")),
+            None => Ok(default),
+        };
+        let mode = match name {
+            "none" if arg.is_none() => AckMode::None,
+            "every" if arg.is_none() => AckMode::Every,
+            "every" => AckMode::EveryN(number(1)?),
+            "delayed" => AckMode::Delayed {
+                max_delay_ms: number(20)?,
+            },
+            "selective" => AckMode::Selective {
+                interval_ms: number(100)?,
+            },
+            _ => return Err(format!("unknown ack mode: {s}")),
+        };
+        mode.validate()?;
+        Ok(mode)
+    }
+}
+
+/// SACK-style report of the data records received since the previous one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SelectiveAckPacket {
+    pub base_seq: u32,
+    /// Bit `i` (least significant bit first within each byte) is set when
+    /// `base_seq + i` was received.
+    pub bitmap: Vec<u8>,
+    pub server_timestamp_us: u64,
+}
+
+impl SelectiveAckPacket {
+    pub fn received_seqs(&self) -> impl Iterator<Item = u32> + '_ {
+        self.bitmap
+            .iter()
+            .enumerate()
+            .flat_map(move |(byte, bits)| {
+                (0..8)
+                    .filter(move |bit| bits & (1 << bit) != 0)
+                    .map(move |bit| self.base_seq.wrapping_add((byte * 8 + bit) as u32))
+            })
+    }
+}
+
+/// Sender-side count of acks that came back or timed out since the
+/// previous report, so the server can show reverse-path loss per edge.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AckLossReport {
+    #[serde(with = "crate::codec::id")]
+    pub src_node_id: NodeId,
+    pub entries: Vec<AckLossEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AckLossEntry {
+    #[serde(with = "crate::codec::id")]
+    pub dst_node_id: NodeId,
+    pub class: TrafficClass,
+    pub acked: u32,
+    pub lost: u32,
+}
@@ -0,0 +1,77 @@
+use std::fmt::{self, Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::ClassTtls;
+use crate::{EdgeId, NodeId, analytics};
+
+/// Privileged command for a running server. Rejected unless `token`
+/// matches the server's `admin.token`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminRequest {
+    pub token: String,
+    /// Echoed in the reply so callers can match them up.
+    pub request_id: u32,
+    pub command: AdminCommand,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AdminCommand {
+    /// Zero the global packet/byte counters; topology is kept.
+    ResetCounters,
+    EvictNode(#[serde(with = "crate::codec::id")] NodeId),
+    EvictEdge(#[serde(with = "crate::codec::id")] EdgeId),
+    /// Drop all nodes, edges and counters and bump the epoch.
+    NewEpoch,
+    /// `None` fields keep their current value.
+    SetTtls {
+        node_ttl_secs: Option<u64>,
+        edge_ttl_secs: Option<u64>,
+        edge_ttl_secs_by_class: ClassTtls,
+    },
+    SetWindow {
+        window_secs: u32,
+    },
+    /// Write the current state to the server's checkpoint directory.
+    Checkpoint,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminReply {
+    pub request_id: u32,
+    /// Human-readable outcome on success.
+    pub result: Result<String, AdminError>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AdminError {
+    /// The server has no admin token configured.
+    Disabled,
+    Unauthorized,
+    NotFound,
+    Invalid(String),
+    Failed(String),
+}
+
+impl Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminError::Disabled => write!(f, "admin commands are disabled"),
+            AdminError::Unauthorized => write!(f, "invalid admin token"),
+            AdminError::NotFound => write!(f, "not found"),
+            AdminError::Invalid(reason) => write!(f, "invalid request: {reason}"),
+            AdminError::Failed(reason) => write!(f, "failed: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+/// State written by `AdminCommand::Checkpoint`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub epoch: u64,
+    pub taken_at_epoch_us: u64,
+    pub topology: analytics::TopologySnapshot,
+    pub analytics: analytics::AnalyticsSnapshot,
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::{EdgeId, NodeDomain, NodeId, TrafficClass};
+
+/// A declarative threshold rule from `[[analytics.alert_rules]]`, e.g.
+/// `LossRateWindow > 0.02 for 10s on class=Api`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    /// Unique name, reported with every alert.
+    pub name: String,
+    pub metric: AlertMetric,
+    #[serde(default)]
+    pub op: AlertOp,
+    pub threshold: f64,
+    /// Hysteresis: a firing alert resolves only once the value is back past
+    /// this; defaults to `threshold`.
+    #[serde(default)]
+    pub clear_threshold: Option<f64>,
+    /// How long the condition must hold before a pending alert fires.
+    #[serde(default)]
+    pub for_secs: u64,
+    #[serde(default)]
+    pub scope: AlertScope,
+}
+
+impl AlertRule {
+    pub fn clear_threshold(&self) -> f64 {
+        self.clear_threshold.unwrap_or(self.threshold)
+    }
+}
+
+impl Display for AlertRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} {} {}", self.metric, self.op, self.threshold)?;
+        if self.for_secs > 0 {
+            write!(f, " for {}s", self.for_secs)?;
+        }
+        if let Some(class) = self.scope.class {
+            write!(f, " on class={class:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Snapshot value a rule watches. `Node*` metrics are evaluated per node,
+/// the rest per edge.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertMetric {
+    LossRateWindow,
+    ReverseLossRate,
+    LatencyEwmaUs,
+    JitterEwmaUs,
+    PacketsPerSecond,
+    BytesPerSecond,
+    NodePacketsPerSecond,
+    NodeMeanRttUs,
+    NodeMissingSequences,
+}
+
+impl AlertMetric {
+    pub fn is_node_metric(self) -> bool {
+        matches!(
+            self,
+            AlertMetric::NodePacketsPerSecond
+                | AlertMetric::NodeMeanRttUs
+                | AlertMetric::NodeMissingSequences
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlertOp {
+    #[default]
+    Above,
+    Below,
+}
+
+impl AlertOp {
+    pub fn breached(self, value: f64, threshold: f64) -> bool {
+        match self {
+            AlertOp::Above => value > threshold,
+            AlertOp::Below => value < threshold,
+        }
+    }
+}
+
+impl Display for AlertOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AlertOp::Above => ">",
+            AlertOp::Below => "<",
+        })
+    }
+}
+
+/// Which nodes or edges a rule applies to; unset fields match everything.
+/// A group matches a node and everything registered beneath it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertScope {
+    /// Edge rules only.
+    pub class: Option<TrafficClass>,
+    /// For node rules, the node itself; for edge rules, the source.
+    pub src_domain: Option<NodeDomain>,
+    #[serde(with = "crate::codec::id::option")]
+    pub src_group: Option<NodeId>,
+    /// Edge rules only.
+    pub dst_domain: Option<NodeDomain>,
+    #[serde(with = "crate::codec::id::option")]
+    pub dst_group: Option<NodeId>,
+    /// Edge rules also match with source and destination swapped.
+    pub either_direction: bool,
+}
+
+/// What an alert is about.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertTarget {
+    Edge(#[serde(with = "crate::codec::id")] EdgeId),
+    Node(#[serde(with = "crate::codec::id")] NodeId),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertState {
+    /// Condition met, waiting out `for_secs`.
+    Pending,
+    Firing,
+    /// Was pending or firing; the value is back past the threshold (the
+    /// clear threshold once firing) or the target is gone.
+    Resolved,
+}
+
+impl Display for AlertState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AlertState::Pending => "pending",
+            AlertState::Firing => "firing",
+            AlertState::Resolved => "resolved",
+        })
+    }
+}
+
+/// One rule's verdict on one target.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub rule: String,
+    pub target: AlertTarget,
+    pub state: AlertState,
+    pub metric: AlertMetric,
+    /// Value at the latest evaluation.
+    pub value: f64,
+    pub threshold: f64,
+    /// Wall-clock time the alert entered `state` (microseconds since UNIX
+    /// epoch).
+    pub since_epoch_us: u64,
+}
@@ -1,5 +1,9 @@
+use crate::alerts::Alert;
 use crate::{EdgeId, NodeDomain, NodeId, TrafficClass};
 use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+mod export;
 
 /// Graph-first snapshot for force-directed topology visualizers.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -19,38 +23,242 @@ pub struct TopologySnapshot {
     /// Active edges at snapshot time.
     pub edges: Vec<EdgeSnapshot>,
 
-    /// Nodes removed since the previous topology snapshot.
+    /// Nodes removed since the consumer's cursor: its previous snapshot's
+    /// `change_seq`, or the previous export for plain requests.
+    #[serde(with = "crate::codec::id::vec")]
     pub removed_nodes: Vec<NodeId>,
 
-    /// Edges removed since the previous topology snapshot.
+    /// Edges removed since the consumer's cursor.
+    #[serde(with = "crate::codec::id::vec")]
     pub removed_edges: Vec<EdgeId>,
 
+    /// Liveness changes since the consumer's cursor, oldest first.
+    pub liveness_events: Vec<LivenessEvent>,
+
+    /// Sequence number of the newest removal or liveness change included;
+    /// pass it back as `TopologyQuery::after_change_seq`.
+    pub change_seq: u64,
+
+    /// Nodes removed within `analytics.tombstone_retention_secs`, oldest
+    /// first. Unlike `removed_nodes` these repeat in every snapshot until
+    /// they age out, so late joiners still see recent departures.
+    pub node_tombstones: Vec<NodeTombstone>,
+
+    /// Edge counterpart of `node_tombstones`.
+    pub edge_tombstones: Vec<EdgeTombstone>,
+
     /// Global aggregate statistics (kept for dashboard/summary views).
     pub global_stats: GlobalStats,
+
+    /// Modularity of the latest community detection run, if any.
+    pub modularity: Option<f64>,
+
+    /// Alerts currently pending or firing, from `analytics.alert_rules`.
+    pub alerts: Vec<Alert>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NodeSnapshot {
+    #[serde(with = "crate::codec::id")]
     pub node_id: NodeId,
     pub desc: [u8; 16],
     pub domain: NodeDomain,
     pub first_seen_us: u64,
     pub last_seen_us: u64,
     pub active: bool,
+    pub liveness: NodeLiveness,
     pub total_packets: u64,
     pub total_bytes: u64,
     pub total_pps: f64,
     pub total_bps: f64,
     pub latency: LatencyMetrics,
     pub loss: LossMetrics,
+    /// Enclosing group in the node hierarchy, if any.
+    #[serde(with = "crate::codec::id::option")]
+    pub parent_node_id: Option<NodeId>,
+    /// Registered nodes represented by this entry (>1 when collapsed into a group).
+    pub member_count: u32,
+    /// Structural metrics; `None` when graph analytics are disabled or the
+    /// entry is a collapsed group.
+    pub graph: Option<GraphMetrics>,
+    /// Server reference layout; `None` when the layout engine is disabled.
+    pub layout: Option<NodeLayout>,
+    /// Community from the latest detection run; `None` for nodes that joined
+    /// after it or groups spanning several communities.
+    pub community_id: Option<u32>,
+}
+
+/// Lifecycle of a node as seen from its heartbeats and traffic.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeLiveness {
+    /// Known and heard from, but no traffic yet.
+    Registered,
+    /// Sent or received traffic within the activity window.
+    Active,
+    /// Still heard from (heartbeats, health checks) but no recent traffic.
+    Idle,
+    /// Silent for `analytics.suspect_after_ms`.
+    Suspect,
+    /// Silent for `analytics.down_after_ms`; removed once `node_ttl_secs`
+    /// passes.
+    Down,
+    /// Unregistered, expired or evicted. Only appears in events.
+    Departed,
+}
+
+impl NodeLiveness {
+    pub const ALL: [NodeLiveness; 6] = [
+        NodeLiveness::Registered,
+        NodeLiveness::Active,
+        NodeLiveness::Idle,
+        NodeLiveness::Suspect,
+        NodeLiveness::Down,
+        NodeLiveness::Departed,
+    ];
+}
+
+impl Display for NodeLiveness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NodeLiveness::Registered => "registered",
+            NodeLiveness::Active => "active",
+            NodeLiveness::Idle => "idle",
+            NodeLiveness::Suspect => "suspect",
+            NodeLiveness::Down => "down",
+            NodeLiveness::Departed => "departed",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A node moving from one liveness state to another.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LivenessEvent {
+    #[serde(with = "crate::codec::id")]
+    pub node_id: NodeId,
+    /// `None` when the node was first seen.
+    pub from: Option<NodeLiveness>,
+    pub to: NodeLiveness,
+    /// Wall-clock time of the change (microseconds since UNIX epoch).
+    pub at_epoch_us: u64,
+}
+
+/// Why a node or edge left the topology.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The node sent `UnregisterNode`.
+    Unregistered,
+    /// Silent past its node or edge TTL.
+    Expired,
+    /// Removed by an `EvictNode`/`EvictEdge` admin command.
+    Evicted,
+    /// Cleared by a `NewEpoch` admin command.
+    EpochReset,
+    /// Edge dropped because one of its endpoints was removed.
+    EndpointRemoved,
+}
+
+impl Display for RemovalReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RemovalReason::Unregistered => "unregistered",
+            RemovalReason::Expired => "expired",
+            RemovalReason::Evicted => "evicted",
+            RemovalReason::EpochReset => "epoch reset",
+            RemovalReason::EndpointRemoved => "endpoint removed",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Last known state of a removed node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeTombstone {
+    #[serde(with = "crate::codec::id")]
+    pub node_id: NodeId,
+    pub desc: [u8; 16],
+    pub domain: NodeDomain,
+    pub reason: RemovalReason,
+    /// Wall-clock removal time (microseconds since UNIX epoch).
+    pub removed_at_epoch_us: u64,
+    /// Server-relative, like `NodeSnapshot`.
+    pub first_seen_us: u64,
+    pub last_seen_us: u64,
+    pub liveness: NodeLiveness,
+    pub total_packets: u64,
+    pub total_bytes: u64,
+}
+
+/// Last known state of a removed edge.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EdgeTombstone {
+    #[serde(with = "crate::codec::id")]
+    pub edge_id: EdgeId,
+    #[serde(with = "crate::codec::id")]
+    pub src_node_id: NodeId,
+    #[serde(with = "crate::codec::id")]
+    pub dst_node_id: NodeId,
+    pub class: TrafficClass,
+    pub reason: RemovalReason,
+    pub removed_at_epoch_us: u64,
+    pub last_seen_us: u64,
+    pub packets: u64,
+    pub bytes: u64,
+    pub latency_ewma_us: f64,
+    pub reverse_loss_rate: f64,
+}
+
+/// Position of a node in the server's force-directed reference layout.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct NodeLayout {
+    pub position_2d: [f64; 2],
+    pub position_3d: [f64; 3],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct GraphMetrics {
+    /// Weakly connected component; stable for a given node set.
+    pub component_id: u32,
+    /// Distinct peers sending to this node.
+    pub in_degree: u32,
+    /// Distinct peers this node sends to.
+    pub out_degree: u32,
+    /// Distinct neighbors in either direction over (nodes - 1).
+    pub degree_centrality: f64,
+    /// Normalized share of latency-weighted shortest paths through this node.
+    pub betweenness_centrality: f64,
+}
+
+/// Answer to `RequestPath`: the lowest-latency directed route between two nodes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PathSnapshot {
+    #[serde(with = "crate::codec::id")]
+    pub src_node_id: NodeId,
+    #[serde(with = "crate::codec::id")]
+    pub dst_node_id: NodeId,
+    pub found: bool,
+    /// Nodes along the path, `src` first and `dst` last.
+    #[serde(with = "crate::codec::id::vec")]
+    pub node_ids: Vec<NodeId>,
+    /// Edges traversed, one per hop.
+    #[serde(with = "crate::codec::id::vec")]
+    pub edge_ids: Vec<EdgeId>,
+    /// Sum of per-edge latency EWMAs.
+    pub total_latency_us: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EdgeSnapshot {
+    #[serde(with = "crate::codec::id")]
     pub edge_id: EdgeId,
+    #[serde(with = "crate::codec::id")]
     pub src_node_id: NodeId,
+    #[serde(with = "crate::codec::id")]
     pub dst_node_id: NodeId,
     pub class: TrafficClass,
+    /// Server-clock span of the edge's traffic, like the node fields.
+    pub first_seen_us: u64,
+    pub last_seen_us: u64,
     pub packets: u64,
     pub bytes: u64,
     pub packets_per_second: f64,
@@ -61,7 +269,89 @@ pub struct EdgeSnapshot {
     pub latency_delta_us: f64,
     pub jitter_ewma_us: f64,
     pub loss_rate_window: f64,
+    /// Share of acks lost on the way back to the sender, smoothed over its
+    /// `AckLossReport`s; 0 if it does not report.
+    pub reverse_loss_rate: f64,
     pub active: bool,
+    /// Underlying edges rolled into this one (>1 for group super-edges).
+    pub member_edges: u32,
+    /// Deviation from the edge's learned baseline; `None` while it warms up
+    /// or when anomaly detection is off.
+    pub anomaly: Option<AnomalyScores>,
+}
+
+/// How far each metric of an edge sits from its rolling baseline, in
+/// standard deviations.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct AnomalyScores {
+    pub latency_ewma_us: f64,
+    pub jitter_ewma_us: f64,
+    pub packets_per_second: f64,
+    pub loss_rate: f64,
+    /// Some score reached `analytics.anomaly.score_threshold`.
+    pub anomalous: bool,
+}
+
+impl AnomalyScores {
+    pub fn max(&self) -> f64 {
+        self.latency_ewma_us
+            .max(self.jitter_ewma_us)
+            .max(self.packets_per_second)
+            .max(self.loss_rate)
+    }
+
+    /// Per-metric maximum, for rolling edges into a group super-edge.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            latency_ewma_us: self.latency_ewma_us.max(other.latency_ewma_us),
+            jitter_ewma_us: self.jitter_ewma_us.max(other.jitter_ewma_us),
+            packets_per_second: self.packets_per_second.max(other.packets_per_second),
+            loss_rate: self.loss_rate.max(other.loss_rate),
+            anomalous: self.anomalous || other.anomalous,
+        }
+    }
+}
+
+/// Server-side filters for a topology request. Empty lists and `None`
+/// thresholds match everything.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TopologyQuery {
+    /// Keep only edges of these traffic classes.
+    pub classes: Vec<TrafficClass>,
+
+    /// Keep only nodes in these domains (and edges between them).
+    pub domains: Vec<NodeDomain>,
+
+    /// Keep only these nodes plus everything within `hops` edges of them.
+    #[serde(with = "crate::codec::id::vec")]
+    pub node_ids: Vec<NodeId>,
+
+    /// Neighborhood radius around `node_ids`.
+    pub hops: u8,
+
+    /// Drop nodes and edges not seen within the activity window.
+    pub active_only: bool,
+
+    /// Keep only edges at or above this packet rate.
+    pub min_packets_per_second: Option<f64>,
+
+    /// Keep only edges at or above this window loss rate (0.0..=1.0).
+    pub min_loss_rate: Option<f64>,
+
+    /// Collapse the filtered graph to this grouping depth.
+    pub group_depth: Option<u8>,
+
+    /// Report removals and liveness changes after this `change_seq` instead
+    /// of those since the previous export.
+    pub after_change_seq: Option<u64>,
+}
+
+/// Standing request for periodic topology pushes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct TopologySubscription {
+    /// Send only nodes and edges that changed since the previous push, plus
+    /// removed ids. Otherwise every push is a full snapshot.
+    pub delta: bool,
 }
 
 /// Top-level analytics snapshot sent to visualizer
@@ -94,6 +384,9 @@ pub struct GlobalStats {
     /// Bytes broken down by traffic class
     pub bytes_by_class: [u64; 4],
 
+    /// Bumped by the `NewEpoch` admin command; counters restart with it
+    pub epoch: u64,
+
     /// Packets/bytes grouped by route:
     /// [internal->internal, internal->external, external->internal, external->external]
     pub route_stats: [RouteStats; 4],
@@ -105,6 +398,7 @@ pub struct GlobalStats {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ClientStats {
     /// id
+    #[serde(with = "crate::codec::id")]
     pub node_id: [u8; 16],
 
     /// description
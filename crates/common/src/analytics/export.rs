@@ -0,0 +1,297 @@
+//! Conversions from `TopologySnapshot` to graph interchange formats, so
+//! captured topologies can be opened in Gephi, yEd or Graphviz.
+//!
+//! Nodes are keyed by their UUID string and labelled with their `desc`.
+//! Edges carry traffic class, rates, latency and loss as attributes.
+
+use super::{EdgeSnapshot, NodeSnapshot, TopologySnapshot};
+use crate::NodeId;
+use std::fmt::Write;
+
+/// Attribute columns shared by the GraphML and GEXF exporters.
+const NODE_ATTRIBUTES: [(&str, &str); 7] = [
+    ("domain", "string"),
+    ("active", "boolean"),
+    ("liveness", "string"),
+    ("total_packets", "long"),
+    ("total_pps", "double"),
+    ("member_count", "int"),
+    ("community_id", "int"),
+];
+
+const EDGE_ATTRIBUTES: [(&str, &str); 8] = [
+    ("class", "string"),
+    ("packets", "long"),
+    ("bytes", "long"),
+    ("packets_per_second", "double"),
+    ("bytes_per_second", "double"),
+    ("latency_ewma_us", "double"),
+    ("jitter_ewma_us", "double"),
+    ("loss_rate", "double"),
+];
+
+impl TopologySnapshot {
+    /// GraphML document readable by yEd, Gephi and NetworkX.
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        let _ = writeln!(
+            out,
+            "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>"
+        );
+        for (name, kind) in NODE_ATTRIBUTES {
+            let _ = writeln!(
+                out,
+                "  <key id=\"{name}\" for=\"node\" attr.name=\"{name}\" attr.type=\"{kind}\"/>"
+            );
+        }
+        for (name, kind) in EDGE_ATTRIBUTES {
+            let _ = writeln!(
+                out,
+                "  <key id=\"{name}\" for=\"edge\" attr.name=\"{name}\" attr.type=\"{kind}\"/>"
+            );
+        }
+        out.push_str("  <graph id=\"topology\" edgedefault=\"directed\">\n");
+
+        for node in &self.nodes {
+            let _ = writeln!(out, "    <node id=\"{}\">", format_uuid(&node.node_id));
+            let _ = writeln!(
+                out,
+                "      <data key=\"label\">{}</data>",
+                escape_xml(&node_label(node))
+            );
+            for (key, value) in node_attribute_values(node) {
+                let _ = writeln!(
+                    out,
+                    "      <data key=\"{key}\">{}</data>",
+                    escape_xml(&value)
+                );
+            }
+            out.push_str("    </node>\n");
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "    <edge id=\"{}\" source=\"{}\" target=\"{}\">",
+                format_uuid(&edge.edge_id),
+                format_uuid(&edge.src_node_id),
+                format_uuid(&edge.dst_node_id)
+            );
+            for (key, value) in edge_attribute_values(edge) {
+                let _ = writeln!(
+                    out,
+                    "      <data key=\"{key}\">{}</data>",
+                    escape_xml(&value)
+                );
+            }
+            out.push_str("    </edge>\n");
+        }
+
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    /// GEXF 1.3 document for Gephi. The graph is dynamic: each node and
+    /// edge spans `[first_seen_us, last_seen_us]` on the server clock, so
+    /// Gephi's timeline can replay when they appeared.
+    pub fn to_gexf(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n");
+        let _ = writeln!(
+            out,
+            "  <graph mode=\"dynamic\" defaultedgetype=\"directed\" timeformat=\"integer\" timerepresentation=\"interval\">"
+        );
+        out.push_str("    <attributes class=\"node\">\n");
+        for (index, (name, kind)) in NODE_ATTRIBUTES.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "      <attribute id=\"{index}\" title=\"{name}\" type=\"{}\"/>",
+                gexf_type(kind)
+            );
+        }
+        out.push_str("    </attributes>\n");
+        out.push_str("    <attributes class=\"edge\">\n");
+        for (index, (name, kind)) in EDGE_ATTRIBUTES.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "      <attribute id=\"{index}\" title=\"{name}\" type=\"{}\"/>",
+                gexf_type(kind)
+            );
+        }
+        out.push_str("    </attributes>\n");
+
+        out.push_str("    <nodes>\n");
+        for node in &self.nodes {
+            let _ = writeln!(
+                out,
+                "      <node id=\"{}\" label=\"{}\" start=\"{}\" end=\"{}\">",
+                format_uuid(&node.node_id),
+                escape_xml(&node_label(node)),
+                node.first_seen_us,
+                node.last_seen_us.max(node.first_seen_us)
+            );
+            write_gexf_values(&mut out, &NODE_ATTRIBUTES, node_attribute_values(node));
+            out.push_str("      </node>\n");
+        }
+        out.push_str("    </nodes>\n");
+
+        out.push_str("    <edges>\n");
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\" start=\"{}\" end=\"{}\">",
+                format_uuid(&edge.edge_id),
+                format_uuid(&edge.src_node_id),
+                format_uuid(&edge.dst_node_id),
+                edge.packets_per_second,
+                edge.first_seen_us,
+                edge.last_seen_us.max(edge.first_seen_us)
+            );
+            write_gexf_values(&mut out, &EDGE_ATTRIBUTES, edge_attribute_values(edge));
+            out.push_str("      </edge>\n");
+        }
+        out.push_str("    </edges>\n");
+
+        out.push_str("  </graph>\n</gexf>\n");
+        out
+    }
+
+    /// Graphviz DOT digraph. Edges are labelled with class and rate; the
+    /// remaining metrics are kept as custom attributes for tooling.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph topology {\n");
+        for node in &self.nodes {
+            let attributes: Vec<String> = node_attribute_values(node)
+                .into_iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_dot(&value)))
+                .collect();
+            let _ = writeln!(
+                out,
+                "  \"{}\" [label=\"{}\", {}];",
+                format_uuid(&node.node_id),
+                escape_dot(&node_label(node)),
+                attributes.join(", ")
+            );
+        }
+        for edge in &self.edges {
+            let attributes: Vec<String> = edge_attribute_values(edge)
+                .into_iter()
+                .map(|(key, value)| format!("{key}=\"{}\"", escape_dot(&value)))
+                .collect();
+            let _ = writeln!(
+                out,
+                "  \"{}\" -> \"{}\" [label=\"{} {:.1} pps\", {}];",
+                format_uuid(&edge.src_node_id),
+                format_uuid(&edge.dst_node_id),
+                edge.class,
+                edge.packets_per_second,
+                attributes.join(", ")
+            );
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn node_attribute_values(node: &NodeSnapshot) -> Vec<(&'static str, String)> {
+    let mut values = vec![
+        ("domain", node.domain.to_string()),
+        ("active", node.active.to_string()),
+        ("liveness", node.liveness.to_string()),
+        ("total_packets", node.total_packets.to_string()),
+        ("total_pps", node.total_pps.to_string()),
+        ("member_count", node.member_count.to_string()),
+    ];
+    if let Some(community_id) = node.community_id {
+        values.push(("community_id", community_id.to_string()));
+    }
+    values
+}
+
+fn edge_attribute_values(edge: &EdgeSnapshot) -> Vec<(&'static str, String)> {
+    vec![
+        ("class", edge.class.to_string()),
+        ("packets", edge.packets.to_string()),
+        ("bytes", edge.bytes.to_string()),
+        ("packets_per_second", edge.packets_per_second.to_string()),
+        ("bytes_per_second", edge.bytes_per_second.to_string()),
+        ("latency_ewma_us", edge.latency_ewma_us.to_string()),
+        ("jitter_ewma_us", edge.jitter_ewma_us.to_string()),
+        ("loss_rate", edge.loss_rate_window.to_string()),
+    ]
+}
+
+/// GEXF refers to attributes by their column index within `columns`.
+fn write_gexf_values(
+    out: &mut String,
+    columns: &[(&str, &str)],
+    values: Vec<(&'static str, String)>,
+) {
+    out.push_str("        <attvalues>\n");
+    for (key, value) in values {
+        let index = columns
+            .iter()
+            .position(|(name, _)| *name == key)
+            .expect("attribute is declared");
+        let _ = writeln!(
+            out,
+            "          <attvalue for=\"{index}\" value=\"{}\"/>",
+            escape_xml(&value)
+        );
+    }
+    out.push_str("        </attvalues>\n");
+}
+
+fn gexf_type(kind: &str) -> &str {
+    match kind {
+        "int" => "integer",
+        other => other,
+    }
+}
+
+fn node_label(node: &NodeSnapshot) -> String {
+    let label = String::from_utf8_lossy(&node.desc);
+    let label = label.trim_end_matches('\0');
+    if label.is_empty() {
+        format_uuid(&node.node_id)
+    } else {
+        label.to_string()
+    }
+}
+
+fn format_uuid(id: &NodeId) -> String {
+    uuid::Uuid::from_bytes(*id).to_string()
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_dot(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
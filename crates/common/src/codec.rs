@@ -0,0 +1,138 @@
+//! Wire encodings for `WireMessage`.
+//!
+//! Postcard is the default and what the Rust client speaks. JSON exists for
+//! visualizers written in other languages; the server answers each datagram
+//! in the codec it arrived in.
+
+use crate::WireMessage;
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Codec {
+    #[default]
+    Postcard,
+    Json,
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    Postcard(postcard::Error),
+    Json(serde_json::Error),
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Postcard(err) => write!(f, "postcard: {err}"),
+            CodecError::Json(err) => write!(f, "json: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// Postcard's first byte is the variant index (a single-byte varint below
+/// 128). Once variants reach `"` the codecs become ambiguous and inbound
+/// datagrams need an explicit framing byte instead.
+const _: () = assert!(
+    WireMessage::VARIANT_COUNT <= b'"',
+    "postcard variant tags would collide with JSON detection"
+);
+
+impl Codec {
+    /// Guesses the codec of an inbound datagram. JSON messages are either an
+    /// object (`{"Data":{...}}`) or a bare string for unit variants
+    /// (`"RequestTopology"`); neither first byte is a valid postcard variant
+    /// tag, which is asserted at compile time above.
+    pub fn detect(bytes: &[u8]) -> Codec {
+        match bytes.first() {
+            Some(b'{') | Some(b'"') => Codec::Json,
+            _ => Codec::Postcard,
+        }
+    }
+
+    pub fn encode_message(self, message: &WireMessage) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Codec::Postcard => postcard::to_stdvec(message).map_err(CodecError::Postcard),
+            Codec::Json => serde_json::to_vec(message).map_err(CodecError::Json),
+        }
+    }
+
+    pub fn decode_message(self, bytes: &[u8]) -> Result<WireMessage, CodecError> {
+        match self {
+            Codec::Postcard => postcard::from_bytes(bytes).map_err(CodecError::Postcard),
+            Codec::Json => serde_json::from_slice(bytes).map_err(CodecError::Json),
+        }
+    }
+}
+
+/// Serde adapter for `NodeId`/`EdgeId` fields: UUID strings in
+/// human-readable formats (JSON), raw 16 bytes otherwise, so the postcard
+/// encoding is unchanged.
+pub(crate) mod id {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use uuid::Uuid;
+
+    pub fn serialize<S: Serializer>(id: &[u8; 16], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(&Uuid::from_bytes(*id).hyphenated())
+        } else {
+            id.serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 16], D::Error> {
+        if deserializer.is_human_readable() {
+            let text = String::deserialize(deserializer)?;
+            Uuid::parse_str(&text)
+                .map(Uuid::into_bytes)
+                .map_err(D::Error::custom)
+        } else {
+            <[u8; 16]>::deserialize(deserializer)
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(transparent)]
+    struct Id(#[serde(with = "super::id")] [u8; 16]);
+
+    pub mod option {
+        use super::Id;
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            id: &Option<[u8; 16]>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            id.map(Id).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<[u8; 16]>, D::Error> {
+            Ok(Option::<Id>::deserialize(deserializer)?.map(|Id(id)| id))
+        }
+    }
+
+    pub mod vec {
+        use super::Id;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            ids: &[[u8; 16]],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(ids.iter().map(|id| Id(*id)))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<[u8; 16]>, D::Error> {
+            Ok(Vec::<Id>::deserialize(deserializer)?
+                .into_iter()
+                .map(|Id(id)| id)
+                .collect())
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+
+use crate::TrafficClass;
+use crate::ack::AckMode;
+use crate::alerts::AlertRule;
+
+/// Server settings as loaded from the TOML config file and CLI overrides.
+/// Every field has a default, so an empty file is a valid config.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub server: NetworkConfig,
+    pub analytics: AnalyticsConfig,
+    pub logging: LoggingConfig,
+    pub admin: AdminConfig,
+    pub observers: ObserversConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// UDP listen address.
+    pub bind_addr: String,
+    /// WebSocket listener, disabled when unset.
+    pub ws_addr: Option<String>,
+    /// HTTP listener (viewer, JSON API, metrics), disabled when unset.
+    pub http_addr: Option<String>,
+    /// Maintain reference layout positions.
+    pub layout: bool,
+    /// Acknowledgement policy for UDP sessions that have not chosen one.
+    pub ack_mode: AckMode,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: "127.0.0.1:8080".to_string(),
+            ws_addr: None,
+            http_addr: None,
+            layout: false,
+            ack_mode: AckMode::Every,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyticsConfig {
+    /// Sliding window for packet/byte rates.
+    pub window_secs: u32,
+    /// Registrations beyond this many nodes are rejected.
+    pub max_nodes: usize,
+    /// Nodes and edges count as active if seen within this long.
+    /// Defaults to three rate windows when unset.
+    pub activity_ttl_secs: Option<u64>,
+    /// How often stale nodes and edges are swept.
+    pub cleanup_interval_ms: u64,
+    /// Nodes silent for this long are removed.
+    pub node_ttl_secs: u64,
+    /// Edges idle for this long are removed unless their class overrides it.
+    pub edge_ttl_secs: u64,
+    pub edge_ttl_secs_by_class: ClassTtls,
+    /// How long removed nodes and edges stay in snapshot tombstones; 0
+    /// keeps none.
+    pub tombstone_retention_secs: u64,
+    /// Nodes not heard from (register, heartbeat or own traffic) for this
+    /// long are suspect, and down after `down_after_ms`.
+    pub suspect_after_ms: u64,
+    pub down_after_ms: u64,
+    pub event_thresholds: EventThresholds,
+    pub anomaly: AnomalyConfig,
+    /// EWMA smoothing factors in (0, 1]; higher reacts faster.
+    pub latency_ewma_alpha: f64,
+    pub jitter_ewma_alpha: f64,
+    /// Per-node component, degree and betweenness metrics in snapshots.
+    /// Betweenness is O(nodes × edges), so large graphs may want it off.
+    pub graph_analytics: bool,
+    /// Threshold rules evaluated against snapshot values every second.
+    pub alert_rules: Vec<AlertRule>,
+}
+
+impl Default for AnalyticsConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 5,
+            max_nodes: 1000,
+            activity_ttl_secs: None,
+            cleanup_interval_ms: 1000,
+            node_ttl_secs: 60,
+            edge_ttl_secs: 30,
+            edge_ttl_secs_by_class: ClassTtls::default(),
+            tombstone_retention_secs: 30,
+            suspect_after_ms: 5000,
+            down_after_ms: 15000,
+            event_thresholds: EventThresholds::default(),
+            anomaly: AnomalyConfig::default(),
+            latency_ewma_alpha: 0.2,
+            jitter_ewma_alpha: 0.2,
+            graph_analytics: true,
+            alert_rules: Vec::new(),
+        }
+    }
+}
+
+impl AnalyticsConfig {
+    pub fn activity_ttl_secs(&self) -> u64 {
+        self.activity_ttl_secs
+            .unwrap_or((self.window_secs as u64).saturating_mul(3))
+    }
+
+    pub fn edge_ttl_secs_for(&self, class: TrafficClass) -> u64 {
+        self.edge_ttl_secs_by_class
+            .get(class)
+            .unwrap_or(self.edge_ttl_secs)
+    }
+}
+
+/// Optional per-traffic-class overrides.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassTtls {
+    pub api: Option<u64>,
+    pub heavy_compute: Option<u64>,
+    pub background: Option<u64>,
+    pub health_check: Option<u64>,
+}
+
+impl ClassTtls {
+    pub fn get(&self, class: TrafficClass) -> Option<u64> {
+        match class {
+            TrafficClass::Api => self.api,
+            TrafficClass::HeavyCompute => self.heavy_compute,
+            TrafficClass::Background => self.background,
+            TrafficClass::HealthCheck => self.health_check,
+        }
+    }
+}
+
+/// Edge values that raise topology change events; 0 turns one off.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventThresholds {
+    pub latency_ewma_us: f64,
+    /// Loss rate over one evaluation second, in [0, 1].
+    pub loss_rate: f64,
+    /// Shortest sequence gap reported as a loss burst.
+    pub loss_burst_packets: u64,
+}
+
+impl Default for EventThresholds {
+    fn default() -> Self {
+        Self {
+            latency_ewma_us: 100_000.0,
+            loss_rate: 0.05,
+            loss_burst_packets: 10,
+        }
+    }
+}
+
+/// Per-edge baselines that flag statistical outliers in latency, jitter,
+/// packet rate and loss.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnomalyConfig {
+    /// Standard deviations from the baseline that count as anomalous; 0
+    /// turns detection off.
+    pub score_threshold: f64,
+    /// Weight of each new sample in the rolling mean and variance, in
+    /// (0, 1].
+    pub baseline_alpha: f64,
+    /// Samples (one per second) a baseline needs before it scores.
+    pub warmup_samples: u32,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            score_threshold: 3.0,
+            baseline_alpha: 0.05,
+            warmup_samples: 30,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// `env_logger`-style filter, e.g. `info,server::analytics=debug`.
+    pub filter: String,
+    /// One JSON object per line instead of plain text.
+    pub json: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            filter: "info".to_string(),
+            json: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Shared secret for `Admin` requests; admin commands are refused
+    /// while unset.
+    pub token: Option<String>,
+    /// Where `Checkpoint` writes its JSON files.
+    pub checkpoint_dir: String,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            checkpoint_dir: ".".to_string(),
+        }
+    }
+}
+
+/// Built-in analytics observers; all are off by default.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ObserversConfig {
+    /// CSV file that receives every edge's metrics after each cleanup
+    /// sweep.
+    pub edge_csv: Option<String>,
+    /// Log packets whose one-way latency exceeds this many microseconds;
+    /// 0 disables.
+    pub slow_packet_us: u64,
+    /// File that receives one JSON line per alert that goes pending, fires
+    /// or resolves.
+    pub alert_log: Option<String>,
+}
+
+/// Reply to `RequestServerInfo`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServerInfo {
+    pub version: String,
+    pub uptime_us: u64,
+    /// Effective settings after the config file, CLI overrides and admin
+    /// changes. The admin token is redacted.
+    pub config: ServerConfig,
+}
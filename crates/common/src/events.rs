@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::Alert;
+use crate::analytics::RemovalReason;
+use crate::{EdgeId, NodeDomain, NodeId, TrafficClass};
+
+/// One entry of the server's topology change stream. `seq` starts at 1 and
+/// grows by one per event, so a jump means events were missed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TopologyEvent {
+    pub seq: u64,
+    /// Wall-clock time (microseconds since UNIX epoch).
+    pub at_epoch_us: u64,
+    pub kind: TopologyEventKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum TopologyEventKind {
+    NodeAdded {
+        #[serde(with = "crate::codec::id")]
+        node_id: NodeId,
+        desc: [u8; 16],
+        domain: NodeDomain,
+    },
+    /// The node's label or domain changed.
+    NodeUpdated {
+        #[serde(with = "crate::codec::id")]
+        node_id: NodeId,
+        desc: [u8; 16],
+        domain: NodeDomain,
+    },
+    NodeRemoved {
+        #[serde(with = "crate::codec::id")]
+        node_id: NodeId,
+        reason: RemovalReason,
+    },
+    EdgeAdded {
+        #[serde(with = "crate::codec::id")]
+        edge_id: EdgeId,
+        #[serde(with = "crate::codec::id")]
+        src_node_id: NodeId,
+        #[serde(with = "crate::codec::id")]
+        dst_node_id: NodeId,
+        class: TrafficClass,
+    },
+    EdgeRemoved {
+        #[serde(with = "crate::codec::id")]
+        edge_id: EdgeId,
+        reason: RemovalReason,
+    },
+    /// `metric` moved across its `analytics.event_thresholds` value; `above`
+    /// tells the direction. Latency is checked on every sample, loss over
+    /// each one-second evaluation.
+    EdgeMetricThresholdCrossed {
+        #[serde(with = "crate::codec::id")]
+        edge_id: EdgeId,
+        metric: EdgeMetric,
+        value: f64,
+        threshold: f64,
+        above: bool,
+    },
+    /// A single sequence gap of at least `loss_burst_packets` packets.
+    LossBurstDetected {
+        #[serde(with = "crate::codec::id")]
+        edge_id: EdgeId,
+        missing: u64,
+    },
+    /// An alert rule started firing or resolved.
+    AlertChanged { alert: Alert },
+    /// `metric` started or stopped deviating from the edge's learned
+    /// baseline by `analytics.anomaly.score_threshold` standard deviations.
+    EdgeAnomalyChanged {
+        #[serde(with = "crate::codec::id")]
+        edge_id: EdgeId,
+        metric: EdgeMetric,
+        value: f64,
+        baseline_mean: f64,
+        score: f64,
+        anomalous: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMetric {
+    LatencyEwmaUs,
+    /// Window loss rate.
+    LossRate,
+    JitterEwmaUs,
+    PacketsPerSecond,
+}
+
+/// Reply to `RequestEvents` and payload of event pushes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EventBatch {
+    /// Oldest first.
+    pub events: Vec<TopologyEvent>,
+    /// Events after the requested `after_seq` that had already left the
+    /// server's buffer.
+    pub missed: u64,
+}
@@ -3,7 +3,15 @@ use std::{fmt::Display, path::Path, time::SystemTime};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+pub mod ack;
+pub mod admin;
+pub mod alerts;
 pub mod analytics;
+pub mod codec;
+pub mod config;
+pub mod events;
+
+pub use codec::{Codec, CodecError};
 
 pub type NodeId = [u8; 16];
 pub type EdgeId = [u8; 16];
@@ -43,6 +51,15 @@ pub enum NodeDomain {
     External = 1,
 }
 
+impl Display for NodeDomain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeDomain::Internal => write!(f, "internal"),
+            NodeDomain::External => write!(f, "external"),
+        }
+    }
+}
+
 impl From<EndpointDomain> for NodeDomain {
     fn from(value: EndpointDomain) -> Self {
         match value {
@@ -63,21 +80,46 @@ impl From<NodeDomain> for EndpointDomain {
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct RegisterNodePacket {
+    #[serde(with = "crate::codec::id")]
     pub node_id: NodeId,
     pub desc: [u8; 16],
     pub domain: NodeDomain,
+    /// Enclosing group (host, service, cluster...), itself a node id.
+    #[serde(with = "crate::codec::id::option")]
+    pub parent_node_id: Option<NodeId>,
     pub timestamp_us: u64,
+    /// Sender-chosen id echoed in the ack. Retransmissions reuse it so the
+    /// server applies the request once; 0 opts out of deduplication. The
+    /// serde default only lets JSON senders omit it: postcard is not
+    /// self-describing, so postcard messages must always carry the field.
+    #[serde(default)]
+    pub control_id: u32,
+    /// Random per sender process (see [`new_session_id`]). Control ids
+    /// restart with every session, so the server forgets the previous
+    /// session's ids instead of treating the new ones as stale. Optional in
+    /// JSON only, like `control_id`.
+    #[serde(default)]
+    pub session: u32,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct UnregisterNodePacket {
+    #[serde(with = "crate::codec::id")]
     pub node_id: NodeId,
     pub timestamp_us: u64,
+    /// See [`RegisterNodePacket::control_id`].
+    #[serde(default)]
+    pub control_id: u32,
+    /// See [`RegisterNodePacket::session`].
+    #[serde(default)]
+    pub session: u32,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct DataPacket {
+    #[serde(with = "crate::codec::id")]
     pub src_node_id: NodeId,
+    #[serde(with = "crate::codec::id")]
     pub dst_node_id: NodeId,
     pub global_seq: u32,
     pub class_seq: u32,
@@ -94,6 +136,93 @@ pub struct AckPacket {
     pub server_processing_us: u32,
 }
 
+/// Liveness signal for a registered node, so it is not suspected while it
+/// has no traffic to send. Unknown nodes are ignored.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct HeartbeatPacket {
+    #[serde(with = "crate::codec::id")]
+    pub node_id: NodeId,
+    pub timestamp_us: u64,
+}
+
+/// What went wrong, carried by `WireMessage::Error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// The datagram or frame was not a valid message in any codec.
+    DecodeFailed,
+    /// A server-to-client message was sent to the server.
+    UnexpectedMessage,
+    /// The message decoded but its contents were rejected.
+    InvalidRequest,
+    /// A data record was dropped because tracking one of its endpoints
+    /// would exceed the server's node limit.
+    NodeLimitReached,
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use ErrorCode::*;
+        match self {
+            DecodeFailed => write!(f, "decode failed"),
+            UnexpectedMessage => write!(f, "unexpected message"),
+            InvalidRequest => write!(f, "invalid request"),
+            NodeLimitReached => write!(f, "node limit reached"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RegisterOutcome {
+    /// The node was new.
+    Registered,
+    /// The node was already known; its description, domain and parent
+    /// were updated.
+    Refreshed,
+    NodeLimitReached,
+    /// A newer control request for this node was already applied; this
+    /// late retransmission was ignored.
+    Superseded,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnregisterOutcome {
+    Unregistered,
+    UnknownNode,
+    /// See [`RegisterOutcome::Superseded`].
+    Superseded,
+}
+
+/// Records per `DataBatch` that keep a postcard datagram under a typical
+/// 1500-byte Ethernet MTU. The server rejects larger batches.
+pub const MAX_DATA_BATCH_RECORDS: usize = 16;
+
+/// Acknowledges every record of a `DataBatch` at once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AckBatchPacket {
+    /// `global_seq` of each record, in batch order.
+    pub original_seqs: Vec<u32>,
+    pub server_timestamp_us: u64,
+    pub server_processing_us: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RegisterAckPacket {
+    #[serde(with = "crate::codec::id")]
+    pub node_id: NodeId,
+    pub control_id: u32,
+    pub outcome: RegisterOutcome,
+    pub server_timestamp_us: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct UnregisterAckPacket {
+    #[serde(with = "crate::codec::id")]
+    pub node_id: NodeId,
+    pub control_id: u32,
+    pub outcome: UnregisterOutcome,
+    pub server_timestamp_us: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WireMessage {
     RegisterNode(RegisterNodePacket),
@@ -104,6 +233,70 @@ pub enum WireMessage {
     Topology(analytics::TopologySnapshot),
     RequestAnalytics,
     Analytics(analytics::AnalyticsSnapshot),
+    /// Topology rolled up so that no node is deeper than `max_depth` in the
+    /// grouping hierarchy (0 = root groups only).
+    RequestGroupedTopology {
+        max_depth: u8,
+    },
+    RequestFilteredTopology(analytics::TopologyQuery),
+    RequestPath {
+        #[serde(with = "crate::codec::id")]
+        src_node_id: NodeId,
+        #[serde(with = "crate::codec::id")]
+        dst_node_id: NodeId,
+    },
+    Path(analytics::PathSnapshot),
+    /// Ask the server to push `Topology` snapshots to this client until it
+    /// unsubscribes or disconnects.
+    Subscribe(analytics::TopologySubscription),
+    Unsubscribe,
+    RequestServerInfo,
+    ServerInfo(config::ServerInfo),
+    Admin(admin::AdminRequest),
+    AdminReply(admin::AdminReply),
+    /// Server-side failure the sender should know about. `related_seq` is
+    /// the `global_seq` of the data packet concerned, if any.
+    Error {
+        code: ErrorCode,
+        detail: String,
+        related_seq: Option<u32>,
+    },
+    RegisterAck(RegisterAckPacket),
+    UnregisterAck(UnregisterAckPacket),
+    /// Many data records in one datagram; records may differ in class and
+    /// endpoints. Answered with a single `AckBatch`.
+    DataBatch(Vec<DataPacket>),
+    AckBatch(AckBatchPacket),
+    /// Switches how the server acknowledges data from this UDP session;
+    /// confirmed with `AckModeSet`.
+    SetAckMode(ack::AckMode),
+    AckModeSet(ack::AckMode),
+    SelectiveAck(ack::SelectiveAckPacket),
+    AckLossReport(ack::AckLossReport),
+    Heartbeat(HeartbeatPacket),
+    /// Topology change events after `after_seq`, answered with `Events`.
+    RequestEvents {
+        after_seq: u64,
+    },
+    Events(events::EventBatch),
+    /// Push `Events` to this client as they happen. `after_seq` replays
+    /// buffered events after it; `None` starts with the next event.
+    /// Confirmed with `EventsSubscribed`. Over UDP this is a lease that
+    /// expires unless renewed by subscribing again.
+    SubscribeEvents {
+        after_seq: Option<u64>,
+    },
+    EventsSubscribed {
+        after_seq: u64,
+    },
+    UnsubscribeEvents,
+}
+
+impl WireMessage {
+    /// Number of variants. Postcard leads with the variant index, which
+    /// `Codec::detect` relies on never reaching a JSON first byte; bump this
+    /// with every new variant.
+    pub const VARIANT_COUNT: u8 = 33;
 }
 
 pub fn now_timestamp_us() -> u64 {
@@ -143,7 +336,22 @@ pub fn make_register_node_packet(
         node_id,
         desc,
         domain,
+        parent_node_id: None,
         timestamp_us: now_timestamp_us(),
+        control_id: 0,
+        session: 0,
+    }
+}
+
+pub fn make_register_child_node_packet(
+    node_id: NodeId,
+    desc: [u8; 16],
+    domain: NodeDomain,
+    parent_node_id: NodeId,
+) -> RegisterNodePacket {
+    RegisterNodePacket {
+        parent_node_id: Some(parent_node_id),
+        ..make_register_node_packet(node_id, desc, domain)
     }
 }
 
@@ -151,6 +359,21 @@ pub fn make_unregister_node_packet(node_id: NodeId) -> UnregisterNodePacket {
     UnregisterNodePacket {
         node_id,
         timestamp_us: now_timestamp_us(),
+        control_id: 0,
+        session: 0,
+    }
+}
+
+/// Fresh control session id for a sender process.
+pub fn new_session_id() -> u32 {
+    let bytes = Uuid::new_v4().into_bytes();
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+pub fn make_heartbeat_packet(node_id: NodeId) -> HeartbeatPacket {
+    HeartbeatPacket {
+        node_id,
+        timestamp_us: now_timestamp_us(),
     }
 }
 
@@ -161,6 +384,7 @@ pub fn synthetic_domain_node_id(domain: EndpointDomain) -> NodeId {
     }
 }
 
+/// Postcard encoding, the default codec. See [`Codec`] for JSON.
 pub fn encode_message(message: &WireMessage) -> postcard::Result<Vec<u8>> {
     postcard::to_stdvec(message)
 }
@@ -177,10 +401,10 @@ pub fn load_or_create_id(path: &Path) -> std::io::Result<NodeId> {
         }
     }
 
-    if let Some(parent) = path.parent() {
-        if !parent.as_os_str().is_empty() {
-            std::fs::create_dir_all(parent)?;
-        }
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)?;
     }
 
     let id = Uuid::new_v4();
@@ -222,6 +446,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn full_data_batch_fits_in_one_datagram() {
+        let records = (0..MAX_DATA_BATCH_RECORDS as u32)
+            .map(|seq| {
+                make_data_packet(
+                    *b"ABCDEFGHIJLMNOPQ",
+                    *b"QRSTUVWXYZABCDEF",
+                    u32::MAX - seq,
+                    u32::MAX - seq,
+                    TrafficClass::HeavyCompute,
+                    u32::MAX,
+                    *b"test-node-------",
+                )
+            })
+            .collect();
+        let bytes = encode_message(&WireMessage::DataBatch(records)).expect("should encode");
+        assert!(bytes.len() <= 1472, "batch is {} bytes", bytes.len());
+        match decode_message(&bytes).expect("should decode") {
+            WireMessage::DataBatch(records) => {
+                assert_eq!(records.len(), MAX_DATA_BATCH_RECORDS);
+                assert_eq!(records[1].global_seq, u32::MAX - 1);
+            }
+            _ => panic!("expected data batch"),
+        }
+    }
+
     #[test]
     fn round_trip_register_node_message() {
         let node_id: NodeId = *b"ABCDEFGHIJLMNOPQ";
@@ -244,10 +494,29 @@ mod tests {
     }
 
     #[test]
-    fn round_trip_topology_message() {
+    fn round_trip_register_child_node_message() {
+        let node_id: NodeId = *b"ABCDEFGHIJLMNOPQ";
+        let parent_node_id: NodeId = *b"HOST-GROUP-00001";
+        let msg = WireMessage::RegisterNode(make_register_child_node_packet(
+            node_id,
+            *b"test-node-------",
+            NodeDomain::Internal,
+            parent_node_id,
+        ));
+        let bytes = encode_message(&msg).expect("should encode");
+        match decode_message(&bytes).expect("should decode") {
+            WireMessage::RegisterNode(packet) => {
+                assert_eq!(packet.node_id, node_id);
+                assert_eq!(packet.parent_node_id, Some(parent_node_id));
+            }
+            _ => panic!("expected register-node message"),
+        }
+    }
+
+    fn sample_topology_snapshot() -> analytics::TopologySnapshot {
         let node_id: NodeId = *b"ABCDEFGHIJLMNOPQ";
         let edge_id: EdgeId = *b"QRSTUVWXYZABCDEF";
-        let snapshot = analytics::TopologySnapshot {
+        analytics::TopologySnapshot {
             snapshot_seq: 1,
             snapshot_timestamp_epoch_us: 123_456,
             snapshot_interval_us: 10_000,
@@ -258,6 +527,7 @@ mod tests {
                 first_seen_us: 10,
                 last_seen_us: 20,
                 active: true,
+                liveness: analytics::NodeLiveness::Active,
                 total_packets: 1,
                 total_bytes: 1200,
                 total_pps: 0.2,
@@ -275,12 +545,19 @@ mod tests {
                     duplicates: 0,
                     total_gaps: 0,
                 },
+                parent_node_id: None,
+                member_count: 1,
+                graph: None,
+                layout: None,
+                community_id: Some(0),
             }],
             edges: vec![analytics::EdgeSnapshot {
                 edge_id,
                 src_node_id: node_id,
                 dst_node_id: synthetic_domain_node_id(EndpointDomain::Internal),
                 class: TrafficClass::Api,
+                first_seen_us: 10,
+                last_seen_us: 20,
                 packets: 1,
                 bytes: 1200,
                 packets_per_second: 0.2,
@@ -291,15 +568,26 @@ mod tests {
                 latency_delta_us: 0.0,
                 jitter_ewma_us: 0.0,
                 loss_rate_window: 0.0,
+                reverse_loss_rate: 0.0,
                 active: true,
+                member_edges: 1,
+                anomaly: Some(analytics::AnomalyScores {
+                    latency_ewma_us: 0.5,
+                    ..Default::default()
+                }),
             }],
             removed_nodes: Vec::new(),
             removed_edges: Vec::new(),
+            liveness_events: Vec::new(),
+            change_seq: 0,
+            node_tombstones: Vec::new(),
+            edge_tombstones: Vec::new(),
             global_stats: analytics::GlobalStats {
                 total_packets: 1,
                 total_bytes: 1200,
                 packets_by_class: [1, 0, 0, 0],
                 bytes_by_class: [1200, 0, 0, 0],
+                epoch: 0,
                 route_stats: [
                     analytics::RouteStats {
                         packets: 0,
@@ -320,7 +608,23 @@ mod tests {
                 ],
                 unique_clients: 1,
             },
-        };
+            modularity: Some(0.0),
+            alerts: vec![alerts::Alert {
+                rule: "slow-api".to_string(),
+                target: alerts::AlertTarget::Edge(edge_id),
+                state: alerts::AlertState::Firing,
+                metric: alerts::AlertMetric::LatencyEwmaUs,
+                value: 120_000.0,
+                threshold: 100_000.0,
+                since_epoch_us: 123_000,
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trip_topology_message() {
+        let edge_id: EdgeId = *b"QRSTUVWXYZABCDEF";
+        let snapshot = sample_topology_snapshot();
 
         let msg = WireMessage::Topology(snapshot);
         let bytes = encode_message(&msg).expect("should encode");
@@ -335,4 +639,90 @@ mod tests {
             _ => panic!("expected topology message"),
         }
     }
+
+    #[test]
+    fn export_topology_as_graphml_gexf_and_dot() {
+        let snapshot = sample_topology_snapshot();
+        let node_uuid = uuid::Uuid::from_bytes(*b"ABCDEFGHIJLMNOPQ").to_string();
+
+        let graphml = snapshot.to_graphml();
+        assert!(graphml.contains(&format!("<node id=\"{node_uuid}\">")));
+        assert!(graphml.contains("<data key=\"label\">test-node-------</data>"));
+        assert!(graphml.contains("<data key=\"class\">api</data>"));
+        assert!(graphml.contains("<data key=\"latency_ewma_us\">100</data>"));
+
+        let gexf = snapshot.to_gexf();
+        assert!(gexf.contains("mode=\"dynamic\""));
+        assert!(gexf.contains(&format!(
+            "<node id=\"{node_uuid}\" label=\"test-node-------\" start=\"10\" end=\"20\">"
+        )));
+        assert_eq!(gexf.matches("<edge ").count(), 1);
+        assert!(gexf.contains("weight=\"0.2\" start=\"10\" end=\"20\">"));
+
+        let dot = snapshot.to_dot();
+        assert!(dot.starts_with("digraph topology {"));
+        assert!(dot.contains(&format!("\"{node_uuid}\" -> ")));
+        assert!(dot.contains("label=\"api 0.2 pps\""));
+    }
+
+    #[test]
+    fn variant_count_matches_wire_message() {
+        let last = WireMessage::VARIANT_COUNT - 1;
+        assert!(!matches!(
+            postcard::from_bytes::<WireMessage>(&[last]),
+            Err(postcard::Error::SerdeDeCustom)
+        ));
+        assert!(matches!(
+            postcard::from_bytes::<WireMessage>(&[WireMessage::VARIANT_COUNT]),
+            Err(postcard::Error::SerdeDeCustom)
+        ));
+    }
+
+    #[test]
+    fn json_codec_uses_uuid_strings_and_keeps_postcard_bytes() {
+        let node_id: NodeId = *b"ABCDEFGHIJLMNOPQ";
+        let parent_node_id: NodeId = *b"HOST-GROUP-00001";
+        let msg = WireMessage::RegisterNode(make_register_child_node_packet(
+            node_id,
+            *b"test-node-------",
+            NodeDomain::Internal,
+            parent_node_id,
+        ));
+
+        let json = Codec::Json.encode_message(&msg).expect("should encode");
+        let text = String::from_utf8(json.clone()).expect("json is utf-8");
+        assert!(text.starts_with("{\"RegisterNode\":"));
+        assert!(text.contains(&Uuid::from_bytes(node_id).to_string()));
+        assert!(text.contains(&Uuid::from_bytes(parent_node_id).to_string()));
+        assert_eq!(Codec::detect(&json), Codec::Json);
+        match Codec::Json.decode_message(&json).expect("should decode") {
+            WireMessage::RegisterNode(packet) => {
+                assert_eq!(packet.node_id, node_id);
+                assert_eq!(packet.parent_node_id, Some(parent_node_id));
+            }
+            _ => panic!("expected register-node message"),
+        }
+
+        let postcard = Codec::Postcard.encode_message(&msg).expect("should encode");
+        assert_eq!(postcard, encode_message(&msg).expect("should encode"));
+        assert_eq!(Codec::detect(&postcard), Codec::Postcard);
+        assert_eq!(&postcard[1..17], &node_id);
+
+        let request = Codec::Json
+            .encode_message(&WireMessage::RequestTopology)
+            .expect("should encode");
+        assert_eq!(request, b"\"RequestTopology\"");
+        assert_eq!(Codec::detect(&request), Codec::Json);
+
+        let topology = WireMessage::Topology(sample_topology_snapshot());
+        let bytes = Codec::Json
+            .encode_message(&topology)
+            .expect("should encode");
+        match Codec::Json.decode_message(&bytes).expect("should decode") {
+            WireMessage::Topology(snapshot) => {
+                assert_eq!(snapshot.edges[0].edge_id, *b"QRSTUVWXYZABCDEF");
+            }
+            _ => panic!("expected topology message"),
+        }
+    }
 }
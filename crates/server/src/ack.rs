@@ -0,0 +1,410 @@
+use common::ack::{AckMode, SelectiveAckPacket};
+use common::{AckBatchPacket, Codec, WireMessage};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Delayed acks are flushed early once this many are held.
+const MAX_HELD_ACKS: usize = 256;
+/// Sequence span covered by one `SelectiveAck`; wider reports are split.
+const MAX_SACK_BITS: u32 = 4096;
+/// Selective reports are due right away once this many seqs are held.
+const MAX_RECEIVED_SEQS: usize = MAX_SACK_BITS as usize;
+/// Sessions without data for this long are forgotten, falling back to the
+/// default mode. Longer than any hold, so nothing pending is lost.
+const SESSION_IDLE_TTL: Duration = Duration::from_secs(300);
+/// Most sessions tracked at once. Further `SetAckMode`s are refused and
+/// further sources get their acks immediately.
+pub const MAX_ACK_SESSIONS: usize = 4096;
+
+/// Per-UDP-session acknowledgement policy, applied to the `Ack`/`AckBatch`
+/// replies produced by the request handler.
+pub struct AckSessions {
+    default_mode: AckMode,
+    sessions: HashMap<SocketAddr, AckSession>,
+}
+
+struct AckSession {
+    codec: Codec,
+    mode: AckMode,
+    records_seen: u64,
+    /// `Delayed`: seqs waiting for the next `AckBatch`.
+    held: Vec<u32>,
+    held_since: Option<Instant>,
+    /// `Selective`: seqs received since the last report.
+    received: Vec<u32>,
+    last_report_at: Instant,
+    last_active: Instant,
+}
+
+impl AckSession {
+    fn new(codec: Codec, mode: AckMode, now: Instant) -> Self {
+        Self {
+            codec,
+            mode,
+            records_seen: 0,
+            held: Vec::new(),
+            held_since: None,
+            received: Vec::new(),
+            last_report_at: now,
+            last_active: now,
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        match self.mode {
+            AckMode::Delayed { max_delay_ms } => self
+                .held_since
+                .map(|since| since + Duration::from_millis(max_delay_ms as u64)),
+            AckMode::Selective { .. } if self.received.len() >= MAX_RECEIVED_SEQS => {
+                Some(self.last_report_at)
+            }
+            AckMode::Selective { interval_ms } if !self.received.is_empty() => {
+                Some(self.last_report_at + Duration::from_millis(interval_ms as u64))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl AckSessions {
+    pub fn new(default_mode: AckMode) -> Self {
+        Self {
+            default_mode,
+            sessions: HashMap::new(),
+        }
+    }
+
+    /// Switches `src` to `mode`. Acks held under the previous mode are
+    /// returned so they are not lost. `None`, changing nothing, when a new
+    /// session would exceed `MAX_ACK_SESSIONS`.
+    pub fn set_mode(
+        &mut self,
+        src: SocketAddr,
+        codec: Codec,
+        mode: AckMode,
+        now: Instant,
+    ) -> Option<Vec<WireMessage>> {
+        if !self.sessions.contains_key(&src) && self.sessions.len() >= MAX_ACK_SESSIONS {
+            return None;
+        }
+        let flushed = self
+            .sessions
+            .remove(&src)
+            .map(|mut session| flush(&mut session, now))
+            .unwrap_or_default();
+        self.sessions.insert(src, AckSession::new(codec, mode, now));
+        Some(flushed)
+    }
+
+    /// Applies the session's mode to a handler reply. Anything other than
+    /// `Ack`/`AckBatch` passes through untouched.
+    pub fn filter(
+        &mut self,
+        src: SocketAddr,
+        codec: Codec,
+        reply: WireMessage,
+        now: Instant,
+    ) -> Option<WireMessage> {
+        let seqs = match &reply {
+            WireMessage::Ack(ack) => vec![ack.original_seq],
+            WireMessage::AckBatch(ack) => ack.original_seqs.clone(),
+            _ => return Some(reply),
+        };
+        if !self.sessions.contains_key(&src)
+            && (self.default_mode == AckMode::Every || self.sessions.len() >= MAX_ACK_SESSIONS)
+        {
+            return Some(reply);
+        }
+
+        let default_mode = self.default_mode;
+        let session = self
+            .sessions
+            .entry(src)
+            .or_insert_with(|| AckSession::new(codec, default_mode, now));
+        session.codec = codec;
+        session.last_active = now;
+
+        match session.mode {
+            AckMode::None => None,
+            AckMode::Every => Some(reply),
+            AckMode::EveryN(n) => {
+                let mut kept = Vec::new();
+                for seq in seqs {
+                    session.records_seen += 1;
+                    if session.records_seen.is_multiple_of(n as u64) {
+                        kept.push(seq);
+                    }
+                }
+                match reply {
+                    _ if kept.is_empty() => None,
+                    WireMessage::AckBatch(ack) => Some(WireMessage::AckBatch(AckBatchPacket {
+                        original_seqs: kept,
+                        ..ack
+                    })),
+                    reply => Some(reply),
+                }
+            }
+            AckMode::Delayed { .. } => {
+                session.held.extend(seqs);
+                session.held_since.get_or_insert(now);
+                if session.held.len() >= MAX_HELD_ACKS {
+                    flush(session, now).pop()
+                } else {
+                    None
+                }
+            }
+            AckMode::Selective { .. } => {
+                session.received.extend(seqs);
+                None
+            }
+        }
+    }
+
+    /// Delayed batches and selective reports that are due, with the codec
+    /// to send each in. Also forgets sessions idle for `SESSION_IDLE_TTL`.
+    pub fn due(&mut self, now: Instant) -> Vec<(SocketAddr, Codec, WireMessage)> {
+        let mut out = Vec::new();
+        for (addr, session) in self.sessions.iter_mut() {
+            if session.deadline().is_some_and(|deadline| deadline <= now) {
+                out.extend(
+                    flush(session, now)
+                        .into_iter()
+                        .map(|message| (*addr, session.codec, message)),
+                );
+            }
+        }
+        self.sessions
+            .retain(|_, session| now.duration_since(session.last_active) < SESSION_IDLE_TTL);
+        out
+    }
+
+    /// Earliest time [`Self::due`] has something to send.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.sessions
+            .values()
+            .filter_map(AckSession::deadline)
+            .min()
+    }
+}
+
+fn flush(session: &mut AckSession, now: Instant) -> Vec<WireMessage> {
+    let server_timestamp_us = common::now_timestamp_us();
+    let mut out = Vec::new();
+    session.held_since = None;
+    if !session.held.is_empty() {
+        out.push(WireMessage::AckBatch(AckBatchPacket {
+            original_seqs: std::mem::take(&mut session.held),
+            server_timestamp_us,
+            server_processing_us: 0,
+        }));
+    }
+    if !session.received.is_empty() {
+        session.last_report_at = now;
+        out.extend(
+            selective_acks(std::mem::take(&mut session.received), server_timestamp_us)
+                .into_iter()
+                .map(WireMessage::SelectiveAck),
+        );
+    }
+    out
+}
+
+/// Packs `seqs` into bitmaps of at most `MAX_SACK_BITS` each. Sorting by
+/// value means a wrap past `u32::MAX` just starts another report.
+fn selective_acks(mut seqs: Vec<u32>, server_timestamp_us: u64) -> Vec<SelectiveAckPacket> {
+    seqs.sort_unstable();
+    seqs.dedup();
+    let mut reports: Vec<SelectiveAckPacket> = Vec::new();
+    for seq in seqs {
+        let report = match reports.last_mut() {
+            Some(report) if seq - report.base_seq < MAX_SACK_BITS => report,
+            _ => {
+                reports.push(SelectiveAckPacket {
+                    base_seq: seq,
+                    bitmap: Vec::new(),
+                    server_timestamp_us,
+                });
+                reports.last_mut().expect("just pushed")
+            }
+        };
+        let offset = (seq - report.base_seq) as usize;
+        if report.bitmap.len() <= offset / 8 {
+            report.bitmap.resize(offset / 8 + 1, 0);
+        }
+        report.bitmap[offset / 8] |= 1 << (offset % 8);
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::AckPacket;
+    use common::ack::MAX_ACK_HOLD_MS;
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:40000".parse().expect("valid addr")
+    }
+
+    fn ack(seq: u32) -> WireMessage {
+        WireMessage::Ack(AckPacket {
+            original_seq: seq,
+            server_timestamp_us: 0,
+            server_processing_us: 0,
+        })
+    }
+
+    #[test]
+    fn every_n_keeps_every_nth_record_across_batches() {
+        let mut sessions = AckSessions::new(AckMode::EveryN(3));
+        let now = Instant::now();
+        let kept: Vec<bool> = (0..4)
+            .map(|seq| {
+                sessions
+                    .filter(addr(), Codec::Postcard, ack(seq), now)
+                    .is_some()
+            })
+            .collect();
+        assert_eq!(kept, [false, false, true, false]);
+
+        let batch = WireMessage::AckBatch(AckBatchPacket {
+            original_seqs: (4..10).collect(),
+            server_timestamp_us: 0,
+            server_processing_us: 0,
+        });
+        match sessions.filter(addr(), Codec::Postcard, batch, now) {
+            Some(WireMessage::AckBatch(ack)) => assert_eq!(ack.original_seqs, [5, 8]),
+            other => panic!("expected filtered batch, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn delayed_and_selective_acks_flush_when_due() {
+        let mut sessions = AckSessions::new(AckMode::Every);
+        let start = Instant::now();
+        let delayed: SocketAddr = "127.0.0.1:40001".parse().expect("valid addr");
+        sessions.set_mode(
+            delayed,
+            Codec::Json,
+            AckMode::Delayed { max_delay_ms: 10 },
+            start,
+        );
+        sessions.set_mode(
+            addr(),
+            Codec::Postcard,
+            AckMode::Selective { interval_ms: 50 },
+            start,
+        );
+        for seq in [1, 2] {
+            assert!(
+                sessions
+                    .filter(delayed, Codec::Json, ack(seq), start)
+                    .is_none()
+            );
+        }
+        for seq in [7, 9, 10, 7] {
+            assert!(
+                sessions
+                    .filter(addr(), Codec::Postcard, ack(seq), start)
+                    .is_none()
+            );
+        }
+        assert_eq!(
+            sessions.next_deadline(),
+            Some(start + Duration::from_millis(10))
+        );
+
+        let due = sessions.due(start + Duration::from_millis(10));
+        assert_eq!(due.len(), 1);
+        match &due[0] {
+            (to, Codec::Json, WireMessage::AckBatch(ack)) if *to == delayed => {
+                assert_eq!(ack.original_seqs, [1, 2])
+            }
+            other => panic!("expected delayed batch, got {other:?}"),
+        }
+
+        let due = sessions.due(start + Duration::from_millis(50));
+        match &due[..] {
+            [(_, Codec::Postcard, WireMessage::SelectiveAck(sack))] => {
+                assert_eq!(sack.received_seqs().collect::<Vec<_>>(), [7, 9, 10]);
+            }
+            other => panic!("expected one selective ack, got {other:?}"),
+        }
+        assert_eq!(sessions.next_deadline(), None);
+    }
+
+    #[test]
+    fn flooded_selective_session_stays_bounded() {
+        assert!(
+            AckMode::Selective {
+                interval_ms: u32::MAX
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            AckMode::Delayed {
+                max_delay_ms: MAX_ACK_HOLD_MS
+            }
+            .validate()
+            .is_ok()
+        );
+
+        let mut sessions = AckSessions::new(AckMode::Every);
+        let start = Instant::now();
+        sessions.set_mode(
+            addr(),
+            Codec::Postcard,
+            AckMode::Selective {
+                interval_ms: u32::MAX,
+            },
+            start,
+        );
+        let mut reported = Vec::new();
+        for seq in 0..10 * MAX_RECEIVED_SEQS as u32 {
+            assert!(
+                sessions
+                    .filter(addr(), Codec::Postcard, ack(seq), start)
+                    .is_none()
+            );
+            assert!(sessions.sessions[&addr()].received.len() <= MAX_RECEIVED_SEQS);
+            for (_, _, message) in sessions.due(start) {
+                match message {
+                    WireMessage::SelectiveAck(sack) => reported.extend(sack.received_seqs()),
+                    other => panic!("expected selective ack, got {other:?}"),
+                }
+            }
+        }
+        assert_eq!(reported.len(), 10 * MAX_RECEIVED_SEQS);
+        assert_eq!(sessions.next_deadline(), None);
+
+        let idle = start + SESSION_IDLE_TTL;
+        sessions.filter(addr(), Codec::Postcard, ack(0), start);
+        assert!(sessions.next_deadline().is_some());
+        sessions.due(idle);
+        assert!(sessions.sessions.is_empty());
+    }
+
+    #[test]
+    fn session_count_is_capped() {
+        let mut sessions = AckSessions::new(AckMode::Delayed { max_delay_ms: 10 });
+        let now = Instant::now();
+        for port in 0..MAX_ACK_SESSIONS as u16 {
+            let src = SocketAddr::from(([127, 0, 0, 1], port));
+            assert!(sessions.filter(src, Codec::Postcard, ack(1), now).is_none());
+        }
+        let late: SocketAddr = "127.0.0.2:1".parse().expect("valid addr");
+        assert!(
+            sessions
+                .filter(late, Codec::Postcard, ack(1), now)
+                .is_some()
+        );
+        assert!(
+            sessions
+                .set_mode(late, Codec::Postcard, AckMode::Every, now)
+                .is_none()
+        );
+        assert_eq!(sessions.sessions.len(), MAX_ACK_SESSIONS);
+    }
+}
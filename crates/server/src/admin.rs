@@ -0,0 +1,115 @@
+use crate::analytics::AnalyticsManager;
+use common::admin::{AdminCommand, AdminError, AdminReply, AdminRequest};
+use common::config::{AdminConfig, ClassTtls};
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Authenticates `request` against `config` and runs it on `analytics`.
+/// Every request gets a reply, including rejected ones.
+pub fn execute(
+    analytics: &mut AnalyticsManager,
+    config: &AdminConfig,
+    request: AdminRequest,
+    now: Instant,
+) -> AdminReply {
+    let result = match &config.token {
+        None => Err(AdminError::Disabled),
+        Some(token) if !constant_time_eq(token.as_bytes(), request.token.as_bytes()) => {
+            Err(AdminError::Unauthorized)
+        }
+        Some(_) => run(analytics, config, request.command, now),
+    };
+    AdminReply {
+        request_id: request.request_id,
+        result,
+    }
+}
+
+fn run(
+    analytics: &mut AnalyticsManager,
+    config: &AdminConfig,
+    command: AdminCommand,
+    now: Instant,
+) -> Result<String, AdminError> {
+    match command {
+        AdminCommand::ResetCounters => {
+            analytics.reset_counters();
+            Ok("global counters reset".to_string())
+        }
+        AdminCommand::EvictNode(node_id) => {
+            if analytics.evict_node(node_id, now) {
+                Ok("node evicted".to_string())
+            } else {
+                Err(AdminError::NotFound)
+            }
+        }
+        AdminCommand::EvictEdge(edge_id) => {
+            if analytics.evict_edge(edge_id, now) {
+                Ok("edge evicted".to_string())
+            } else {
+                Err(AdminError::NotFound)
+            }
+        }
+        AdminCommand::NewEpoch => Ok(format!("epoch {} started", analytics.start_epoch(now))),
+        AdminCommand::SetTtls {
+            node_ttl_secs,
+            edge_ttl_secs,
+            edge_ttl_secs_by_class,
+        } => {
+            check_ttls(node_ttl_secs, edge_ttl_secs, &edge_ttl_secs_by_class)?;
+            analytics.set_ttls(node_ttl_secs, edge_ttl_secs, edge_ttl_secs_by_class);
+            Ok("ttls updated".to_string())
+        }
+        AdminCommand::SetWindow { window_secs } => {
+            if window_secs == 0 {
+                return Err(AdminError::Invalid(
+                    "window_secs must be greater than 0".to_string(),
+                ));
+            }
+            analytics.set_window(window_secs);
+            Ok(format!("rate window set to {window_secs} s"))
+        }
+        AdminCommand::Checkpoint => {
+            let checkpoint = analytics.checkpoint(now);
+            let path = PathBuf::from(&config.checkpoint_dir).join(format!(
+                "checkpoint-e{}-{}.json",
+                checkpoint.epoch, checkpoint.taken_at_epoch_us
+            ));
+            let json = serde_json::to_vec_pretty(&checkpoint)
+                .map_err(|err| AdminError::Failed(err.to_string()))?;
+            std::fs::write(&path, json)
+                .map_err(|err| AdminError::Failed(format!("{}: {}", path.display(), err)))?;
+            Ok(path.display().to_string())
+        }
+    }
+}
+
+fn check_ttls(
+    node_ttl_secs: Option<u64>,
+    edge_ttl_secs: Option<u64>,
+    by_class: &ClassTtls,
+) -> Result<(), AdminError> {
+    let ttls = [
+        ("node_ttl_secs", node_ttl_secs),
+        ("edge_ttl_secs", edge_ttl_secs),
+        ("edge_ttl_secs_by_class.api", by_class.api),
+        (
+            "edge_ttl_secs_by_class.heavy_compute",
+            by_class.heavy_compute,
+        ),
+        ("edge_ttl_secs_by_class.background", by_class.background),
+        ("edge_ttl_secs_by_class.health_check", by_class.health_check),
+    ];
+    match ttls.iter().find(|(_, ttl)| *ttl == Some(0)) {
+        Some((key, _)) => Err(AdminError::Invalid(format!("{key} must be greater than 0"))),
+        None => Ok(()),
+    }
+}
+
+/// Compares tokens without exiting early on the first differing byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,289 @@
+use crate::observer::EdgeMetrics;
+use common::alerts::{Alert, AlertMetric, AlertRule, AlertScope, AlertState, AlertTarget};
+use common::{NodeDomain, NodeId};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// A node's current values, as node rules see them.
+#[derive(Debug, Clone, Copy)]
+pub struct NodeSample {
+    pub node_id: NodeId,
+    pub domain: NodeDomain,
+    pub packets_per_second: f64,
+    pub mean_rtt_us: f64,
+    pub missing_sequences: u64,
+}
+
+/// An edge's current values, as edge rules see them.
+#[derive(Debug, Clone, Copy)]
+pub struct EdgeSample {
+    pub metrics: EdgeMetrics,
+    /// Loss since the previous evaluation, for `LossRateWindow`.
+    pub loss_rate: f64,
+    pub packets_per_second: f64,
+    pub bytes_per_second: f64,
+}
+
+/// Evaluates `analytics.alert_rules` against successive samples, tracking
+/// every (rule, target) pair through pending, firing and resolved.
+#[derive(Default)]
+pub struct AlertEngine {
+    rules: Vec<AlertRule>,
+    active: HashMap<(usize, AlertTarget), ActiveAlert>,
+}
+
+/// A pending or firing alert; resolved ones are dropped.
+struct ActiveAlert {
+    firing: bool,
+    since: Instant,
+    since_epoch_us: u64,
+    value: f64,
+}
+
+impl AlertEngine {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            rules,
+            active: HashMap::new(),
+        }
+    }
+
+    pub fn has_rules(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    /// Whether any rule watches a node or an edge metric, so callers only
+    /// gather the samples that will be looked at.
+    pub fn has_node_rules(&self) -> bool {
+        self.rules.iter().any(|rule| rule.metric.is_node_metric())
+    }
+
+    pub fn has_edge_rules(&self) -> bool {
+        self.rules.iter().any(|rule| !rule.metric.is_node_metric())
+    }
+
+    /// Updates every alert from `nodes` and `edges`; `domain_of(node)` and
+    /// `in_group(node, group)` decide scopes. Returns the alerts that went
+    /// pending, fired or resolved. Alerts whose condition no longer holds,
+    /// or whose target is gone or out of the rule's scope, resolve.
+    pub fn evaluate(
+        &mut self,
+        nodes: &[NodeSample],
+        edges: &[EdgeSample],
+        domain_of: impl Fn(NodeId) -> Option<NodeDomain>,
+        in_group: impl Fn(NodeId, NodeId) -> bool,
+        now: Instant,
+        epoch_us: u64,
+    ) -> Vec<Alert> {
+        let mut changes = Vec::new();
+        let mut evaluated = HashSet::new();
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            let samples: Vec<(AlertTarget, f64)> = if rule.metric.is_node_metric() {
+                nodes
+                    .iter()
+                    .filter(|node| node_in_scope(node, &rule.scope, &in_group))
+                    .map(|node| {
+                        (
+                            AlertTarget::Node(node.node_id),
+                            node_value(node, rule.metric),
+                        )
+                    })
+                    .collect()
+            } else {
+                edges
+                    .iter()
+                    .filter(|edge| edge_in_scope(&edge.metrics, &rule.scope, &domain_of, &in_group))
+                    .map(|edge| {
+                        (
+                            AlertTarget::Edge(edge.metrics.edge_id),
+                            edge_value(edge, rule.metric),
+                        )
+                    })
+                    .collect()
+            };
+
+            for (target, value) in samples {
+                let key = (index, target);
+                evaluated.insert(key);
+                let breached = match self.active.get(&key) {
+                    Some(active) if active.firing => {
+                        rule.op.breached(value, rule.clear_threshold())
+                    }
+                    _ => rule.op.breached(value, rule.threshold),
+                };
+                if !breached {
+                    if self.active.remove(&key).is_some() {
+                        changes.push(alert(rule, target, AlertState::Resolved, value, epoch_us));
+                    }
+                    continue;
+                }
+                let active = self.active.entry(key).or_insert_with(|| {
+                    // With no `for_secs` the alert fires right away.
+                    if rule.for_secs > 0 {
+                        changes.push(alert(rule, target, AlertState::Pending, value, epoch_us));
+                    }
+                    ActiveAlert {
+                        firing: false,
+                        since: now,
+                        since_epoch_us: epoch_us,
+                        value,
+                    }
+                });
+                active.value = value;
+                if !active.firing
+                    && now.saturating_duration_since(active.since)
+                        >= Duration::from_secs(rule.for_secs)
+                {
+                    active.firing = true;
+                    active.since = now;
+                    active.since_epoch_us = epoch_us;
+                    changes.push(alert(rule, target, AlertState::Firing, value, epoch_us));
+                }
+            }
+        }
+
+        let rules = &self.rules;
+        self.active.retain(|key, active| {
+            if evaluated.contains(key) {
+                return true;
+            }
+            changes.push(alert(
+                &rules[key.0],
+                key.1,
+                AlertState::Resolved,
+                active.value,
+                epoch_us,
+            ));
+            false
+        });
+        changes
+    }
+
+    /// Alerts currently pending or firing, in rule order.
+    pub fn active(&self) -> Vec<Alert> {
+        let mut active: Vec<(usize, Alert)> = self
+            .active
+            .iter()
+            .map(|((index, target), active)| {
+                let state = if active.firing {
+                    AlertState::Firing
+                } else {
+                    AlertState::Pending
+                };
+                let alert = alert(
+                    &self.rules[*index],
+                    *target,
+                    state,
+                    active.value,
+                    active.since_epoch_us,
+                );
+                (*index, alert)
+            })
+            .collect();
+        active.sort_by(|(a, alert_a), (b, alert_b)| {
+            a.cmp(b)
+                .then_with(|| target_id(alert_a.target).cmp(&target_id(alert_b.target)))
+        });
+        active.into_iter().map(|(_, alert)| alert).collect()
+    }
+}
+
+fn alert(
+    rule: &AlertRule,
+    target: AlertTarget,
+    state: AlertState,
+    value: f64,
+    since_epoch_us: u64,
+) -> Alert {
+    Alert {
+        rule: rule.name.clone(),
+        target,
+        state,
+        metric: rule.metric,
+        value,
+        threshold: match state {
+            AlertState::Resolved => rule.clear_threshold(),
+            _ => rule.threshold,
+        },
+        since_epoch_us,
+    }
+}
+
+fn target_id(target: AlertTarget) -> [u8; 16] {
+    match target {
+        AlertTarget::Edge(id) | AlertTarget::Node(id) => id,
+    }
+}
+
+fn node_value(node: &NodeSample, metric: AlertMetric) -> f64 {
+    match metric {
+        AlertMetric::NodePacketsPerSecond => node.packets_per_second,
+        AlertMetric::NodeMeanRttUs => node.mean_rtt_us,
+        AlertMetric::NodeMissingSequences => node.missing_sequences as f64,
+        _ => 0.0,
+    }
+}
+
+fn edge_value(edge: &EdgeSample, metric: AlertMetric) -> f64 {
+    match metric {
+        AlertMetric::LossRateWindow => edge.loss_rate,
+        AlertMetric::ReverseLossRate => edge.metrics.reverse_loss_rate,
+        AlertMetric::LatencyEwmaUs => edge.metrics.latency_ewma_us,
+        AlertMetric::JitterEwmaUs => edge.metrics.jitter_ewma_us,
+        AlertMetric::PacketsPerSecond => edge.packets_per_second,
+        AlertMetric::BytesPerSecond => edge.bytes_per_second,
+        _ => 0.0,
+    }
+}
+
+fn endpoint_matches(
+    node_id: NodeId,
+    domain: Option<NodeDomain>,
+    want_domain: Option<NodeDomain>,
+    want_group: Option<NodeId>,
+    in_group: &impl Fn(NodeId, NodeId) -> bool,
+) -> bool {
+    want_domain.is_none_or(|want| domain == Some(want))
+        && want_group.is_none_or(|group| in_group(node_id, group))
+}
+
+fn node_in_scope(
+    node: &NodeSample,
+    scope: &AlertScope,
+    in_group: &impl Fn(NodeId, NodeId) -> bool,
+) -> bool {
+    endpoint_matches(
+        node.node_id,
+        Some(node.domain),
+        scope.src_domain,
+        scope.src_group,
+        in_group,
+    )
+}
+
+fn edge_in_scope(
+    edge: &EdgeMetrics,
+    scope: &AlertScope,
+    domain_of: &impl Fn(NodeId) -> Option<NodeDomain>,
+    in_group: &impl Fn(NodeId, NodeId) -> bool,
+) -> bool {
+    let directed = |src: NodeId, dst: NodeId| {
+        endpoint_matches(
+            src,
+            domain_of(src),
+            scope.src_domain,
+            scope.src_group,
+            in_group,
+        ) && endpoint_matches(
+            dst,
+            domain_of(dst),
+            scope.dst_domain,
+            scope.dst_group,
+            in_group,
+        )
+    };
+    scope.class.is_none_or(|class| edge.class == class)
+        && (directed(edge.src_node_id, edge.dst_node_id)
+            || (scope.either_direction && directed(edge.dst_node_id, edge.src_node_id)))
+}
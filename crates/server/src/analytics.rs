@@ -1,13 +1,33 @@
+use crate::alerts::{AlertEngine, EdgeSample, NodeSample};
+use crate::anomaly::EdgeBaselines;
+use crate::changes::{Change, ChangeLog};
 use crate::client::{LatencyStats, LossEvent, RateCalculator, SequenceTracker};
+use crate::community;
+use crate::events::EventLog;
+use crate::graph::Graph;
+use crate::layout::{LayoutEngine, Spring};
+use crate::observer::{CleanupSweep, EdgeMetrics, Observers, PacketObserver, TopologyObserver};
+use crate::{grouping, query};
+use common::ack::AckLossReport;
+use common::analytics::{
+    EdgeTombstone, GraphMetrics, LivenessEvent, NodeLiveness, NodeTombstone, RemovalReason,
+};
+use common::config::{AnalyticsConfig, ClassTtls};
+use common::events::{EdgeMetric, EventBatch, TopologyEvent, TopologyEventKind};
 use common::{
-    AckPacket, DataPacket, EdgeId, NodeDomain, NodeId, RegisterNodePacket, TrafficClass,
-    UnregisterNodePacket,
+    AckBatchPacket, AckPacket, DataPacket, EdgeId, HeartbeatPacket, NodeDomain, NodeId,
+    RegisterNodePacket, RegisterOutcome, TrafficClass, UnregisterNodePacket, UnregisterOutcome,
 };
-use std::collections::{HashMap, HashSet};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::hash::{Hash, Hasher};
 use std::net::SocketAddr;
 use std::time::{Duration, Instant, SystemTime};
 
+/// Weight each `AckLossReport` keeps of the earlier ack counts, so one
+/// small report cannot swing `reverse_loss_rate` on its own.
+const REVERSE_LOSS_DECAY: f64 = 0.8;
+
 /// Main analytics engine tracking graph topology and aggregate stats.
 pub struct AnalyticsManager {
     start_time: Instant,
@@ -19,28 +39,77 @@ pub struct AnalyticsManager {
     bytes_by_class: [u64; 4],
     route_packets: [u64; 4],
     route_bytes: [u64; 4],
-    rate_window_secs: u32,
-    max_nodes: usize,
+    config: AnalyticsConfig,
+    epoch: u64,
     snapshot_seq: u64,
     last_topology_epoch_us: u64,
-    removed_nodes_since_last_snapshot: Vec<NodeId>,
-    removed_edges_since_last_snapshot: Vec<EdgeId>,
+    /// Removals and liveness changes; exports report those after
+    /// `exported_change_seq`, other consumers follow their own cursor.
+    changes: ChangeLog,
+    exported_change_seq: u64,
+    /// Removed nodes and edges with their removal instant, oldest first,
+    /// kept for `tombstone_retention_secs`.
+    node_tombstones: VecDeque<(Instant, NodeTombstone)>,
+    edge_tombstones: VecDeque<(Instant, EdgeTombstone)>,
+    events: EventLog,
+    observers: Observers,
+    alerts: AlertEngine,
+    /// Bumped whenever nodes or edges come or go, and on every export so
+    /// latency weights are picked up once per snapshot interval.
+    graph_generation: u64,
+    /// Graph metrics for `graph_generation`; betweenness is too costly to
+    /// recompute for every peek, HTTP request and alert evaluation.
+    graph_metrics: RefCell<Option<(u64, HashMap<NodeId, GraphMetrics>)>>,
+    layout: Option<LayoutEngine>,
+    communities: HashMap<NodeId, u32>,
+    modularity: Option<f64>,
+    last_snapshot_build_time: Duration,
+    /// Last control request applied per node, so retransmissions replay the
+    /// original outcome instead of being applied again.
+    control_replies: HashMap<NodeId, ControlReply>,
+}
+
+#[derive(Clone, Copy)]
+struct ControlReply {
+    session: u32,
+    control_id: u32,
+    outcome: ControlOutcome,
+    at: Instant,
+}
+
+enum Replay {
+    Same(ControlOutcome),
+    Stale,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ControlOutcome {
+    Register(RegisterOutcome),
+    Unregister(UnregisterOutcome),
 }
 
 #[derive(Hash, Eq, PartialEq, Clone, Copy)]
-struct EdgeKey {
-    src_node_id: NodeId,
-    dst_node_id: NodeId,
-    class: TrafficClass,
+pub(crate) struct EdgeKey {
+    pub(crate) src_node_id: NodeId,
+    pub(crate) dst_node_id: NodeId,
+    pub(crate) class: TrafficClass,
 }
 
 struct NodeState {
     node_id: NodeId,
     desc: [u8; 16],
     domain: NodeDomain,
+    parent_node_id: Option<NodeId>,
     addr: SocketAddr,
     first_seen: Instant,
     last_seen: Instant,
+    /// Last register, heartbeat or packet sent by the node itself. `None`
+    /// for nodes only ever seen as a destination.
+    last_heard: Option<Instant>,
+    /// Last non-health-check packet sent or received.
+    last_traffic: Option<Instant>,
+    /// `None` until the first liveness evaluation.
+    liveness: Option<NodeLiveness>,
     seq_trackers: [SequenceTracker; 4],
     packets_by_class: [u64; 4],
     bytes_by_class: [u64; 4],
@@ -63,9 +132,13 @@ impl NodeState {
             node_id,
             desc,
             domain,
+            parent_node_id: None,
             addr,
             first_seen: now,
             last_seen: now,
+            last_heard: None,
+            last_traffic: None,
+            liveness: None,
             seq_trackers: Default::default(),
             packets_by_class: [0; 4],
             bytes_by_class: [0; 4],
@@ -87,6 +160,7 @@ struct EdgeState {
     src_node_id: NodeId,
     dst_node_id: NodeId,
     class: TrafficClass,
+    first_seen: Instant,
     last_seen: Instant,
     packets: u64,
     bytes: u64,
@@ -100,6 +174,39 @@ struct EdgeState {
     last_latency_sample_us: Option<f64>,
     window_packets: u64,
     window_missing: u64,
+    /// Loss since the previous anomaly evaluation; kept apart from the
+    /// export window so the loss baseline sees the same span whatever the
+    /// snapshot rate.
+    anomaly_window: LossWindow,
+    /// Loss since the previous threshold check, independent of exports.
+    threshold_window: LossWindow,
+    /// Loss since the previous alert evaluation.
+    alert_window: LossWindow,
+    /// Decayed ack counts from `AckLossReport`s; see `REVERSE_LOSS_DECAY`.
+    reverse_acked: f64,
+    reverse_lost: f64,
+    reverse_loss_rate: f64,
+    /// Which side of its event threshold each metric was last seen on.
+    latency_above_threshold: bool,
+    loss_above_threshold: bool,
+    baselines: EdgeBaselines,
+}
+
+/// Packets received and missing on an edge since its owner last took the
+/// rate.
+#[derive(Debug, Default, Clone, Copy)]
+struct LossWindow {
+    packets: u64,
+    missing: u64,
+}
+
+impl LossWindow {
+    /// Loss rate over the window, `None` if no packet arrived in it; the
+    /// next window starts empty.
+    fn take(&mut self) -> Option<f64> {
+        let window = std::mem::take(self);
+        (window.packets > 0).then(|| window.missing as f64 / window.packets as f64)
+    }
 }
 
 impl EdgeState {
@@ -109,6 +216,7 @@ impl EdgeState {
             src_node_id: key.src_node_id,
             dst_node_id: key.dst_node_id,
             class: key.class,
+            first_seen: now,
             last_seen: now,
             packets: 0,
             bytes: 0,
@@ -122,13 +230,32 @@ impl EdgeState {
             last_latency_sample_us: None,
             window_packets: 0,
             window_missing: 0,
+            anomaly_window: LossWindow::default(),
+            threshold_window: LossWindow::default(),
+            alert_window: LossWindow::default(),
+            reverse_acked: 0.0,
+            reverse_lost: 0.0,
+            reverse_loss_rate: 0.0,
+            latency_above_threshold: false,
+            loss_above_threshold: false,
+            baselines: EdgeBaselines::default(),
         }
     }
 }
 
 impl AnalyticsManager {
     pub fn new(window_secs: u32, max_nodes: usize) -> Self {
+        Self::with_config(AnalyticsConfig {
+            window_secs,
+            max_nodes,
+            ..AnalyticsConfig::default()
+        })
+    }
+
+    /// Expects a config already checked by the server's config validation.
+    pub fn with_config(config: AnalyticsConfig) -> Self {
         let start_epoch_us = epoch_timestamp_us();
+        let alerts = AlertEngine::new(config.alert_rules.clone());
         Self {
             start_time: Instant::now(),
             nodes: HashMap::new(),
@@ -139,65 +266,348 @@ impl AnalyticsManager {
             bytes_by_class: [0; 4],
             route_packets: [0; 4],
             route_bytes: [0; 4],
-            rate_window_secs: window_secs,
-            max_nodes,
+            config,
+            epoch: 0,
             snapshot_seq: 0,
             last_topology_epoch_us: start_epoch_us,
-            removed_nodes_since_last_snapshot: Vec::new(),
-            removed_edges_since_last_snapshot: Vec::new(),
+            changes: ChangeLog::new(),
+            exported_change_seq: 0,
+            node_tombstones: VecDeque::new(),
+            edge_tombstones: VecDeque::new(),
+            events: EventLog::new(),
+            observers: Observers::default(),
+            alerts,
+            graph_generation: 0,
+            graph_metrics: RefCell::new(None),
+            layout: None,
+            communities: HashMap::new(),
+            modularity: None,
+            last_snapshot_build_time: Duration::ZERO,
+            control_replies: HashMap::new(),
         }
     }
 
+    /// Recomputes node communities with Louvain over the undirected graph,
+    /// weighting each node pair by its combined packet rate. Idle edges keep
+    /// a small weight so quiet but known links still hold communities together.
+    pub fn detect_communities(&mut self, now: Instant) {
+        const IDLE_EDGE_WEIGHT: f64 = 0.1;
+
+        let mut node_ids: Vec<NodeId> = self.nodes.keys().copied().collect();
+        node_ids.sort_unstable();
+        let index: HashMap<NodeId, usize> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, node_id)| (*node_id, i))
+            .collect();
+
+        let mut pair_weights: HashMap<(usize, usize), f64> = HashMap::new();
+        for edge in self.edges.values() {
+            let (Some(&a), Some(&b)) = (index.get(&edge.src_node_id), index.get(&edge.dst_node_id))
+            else {
+                continue;
+            };
+            let (pps, _) = edge.rate_calculator.calculate_rate(now);
+            *pair_weights.entry((a.min(b), a.max(b))).or_default() += pps.max(IDLE_EDGE_WEIGHT);
+        }
+        let weighted_edges: Vec<(usize, usize, f64)> = pair_weights
+            .into_iter()
+            .map(|((a, b), weight)| (a, b, weight))
+            .collect();
+
+        let partition = community::louvain(node_ids.len(), &weighted_edges);
+        self.communities = node_ids.into_iter().zip(partition.community).collect();
+        self.modularity = Some(partition.modularity);
+    }
+
+    /// Starts maintaining reference layout positions for snapshots.
+    pub fn enable_layout(&mut self) {
+        self.layout.get_or_insert_with(LayoutEngine::new);
+    }
+
+    /// Advances the reference layout. Springs are weighted by the combined
+    /// packet rate of all classes between a pair of nodes.
+    pub fn relax_layout(&mut self, now: Instant, iterations: usize) {
+        let Some(layout) = self.layout.as_mut() else {
+            return;
+        };
+        let node_ids: Vec<NodeId> = self.nodes.keys().copied().collect();
+        let mut pair_rates: HashMap<(NodeId, NodeId), f64> = HashMap::new();
+        for edge in self.edges.values() {
+            let pair = if edge.src_node_id <= edge.dst_node_id {
+                (edge.src_node_id, edge.dst_node_id)
+            } else {
+                (edge.dst_node_id, edge.src_node_id)
+            };
+            let (pps, _) = edge.rate_calculator.calculate_rate(now);
+            *pair_rates.entry(pair).or_default() += pps;
+        }
+        let springs: Vec<Spring> = pair_rates
+            .into_iter()
+            .map(|((a, b), pps)| Spring {
+                a,
+                b,
+                weight: 1.0 + pps.ln_1p(),
+            })
+            .collect();
+        layout.relax(&node_ids, &springs, iterations);
+    }
+
+    /// Registrations are idempotent: a retransmitted packet with the same
+    /// nonzero `control_id` gets the original outcome back, and one older
+    /// than the node's last applied control request is ignored.
     pub fn on_node_registered(
         &mut self,
         packet: &RegisterNodePacket,
         src: SocketAddr,
         now: Instant,
-    ) {
-        if !self.nodes.contains_key(&packet.node_id) && self.nodes.len() >= self.max_nodes {
-            return;
+    ) -> RegisterOutcome {
+        let outcome = self.register_node(packet, src, now);
+        for observer in &mut self.observers.topology {
+            observer.on_node_registered(packet, outcome, now);
         }
+        outcome
+    }
 
-        let node = self.nodes.entry(packet.node_id).or_insert_with(|| {
-            NodeState::new(
-                packet.node_id,
-                packet.desc,
-                packet.domain,
-                src,
-                now,
-                self.rate_window_secs,
-            )
-        });
+    fn register_node(
+        &mut self,
+        packet: &RegisterNodePacket,
+        src: SocketAddr,
+        now: Instant,
+    ) -> RegisterOutcome {
+        match self.replayed_control(packet.node_id, packet.session, packet.control_id) {
+            Some(Replay::Same(ControlOutcome::Register(outcome))) => return outcome,
+            Some(_) => return RegisterOutcome::Superseded,
+            None => {}
+        }
 
+        let known = self.nodes.contains_key(&packet.node_id);
+        let outcome = if !known && self.nodes.len() >= self.config.max_nodes {
+            RegisterOutcome::NodeLimitReached
+        } else {
+            let node = self.nodes.entry(packet.node_id).or_insert_with(|| {
+                NodeState::new(
+                    packet.node_id,
+                    packet.desc,
+                    packet.domain,
+                    src,
+                    now,
+                    self.config.window_secs,
+                )
+            });
+
+            let relabeled = node.desc != packet.desc || node.domain != packet.domain;
+            node.addr = src;
+            node.desc = packet.desc;
+            node.domain = packet.domain;
+            node.parent_node_id = packet
+                .parent_node_id
+                .filter(|parent| *parent != packet.node_id);
+            node.last_seen = now;
+            node.last_heard = Some(now);
+            if known {
+                if relabeled {
+                    self.events.emit(TopologyEventKind::NodeUpdated {
+                        node_id: packet.node_id,
+                        desc: packet.desc,
+                        domain: packet.domain,
+                    });
+                }
+                RegisterOutcome::Refreshed
+            } else {
+                self.graph_generation += 1;
+                self.events.emit(TopologyEventKind::NodeAdded {
+                    node_id: packet.node_id,
+                    desc: packet.desc,
+                    domain: packet.domain,
+                });
+                RegisterOutcome::Registered
+            }
+        };
+        self.update_liveness(packet.node_id, now);
+        self.record_control(
+            packet.node_id,
+            packet.session,
+            packet.control_id,
+            ControlOutcome::Register(outcome),
+            now,
+        );
+        outcome
+    }
+
+    /// Idempotent like [`Self::on_node_registered`].
+    pub fn on_node_unregistered(
+        &mut self,
+        packet: &UnregisterNodePacket,
+        now: Instant,
+    ) -> UnregisterOutcome {
+        let outcome = self.unregister_node(packet, now);
+        for observer in &mut self.observers.topology {
+            observer.on_node_unregistered(packet, outcome, now);
+        }
+        outcome
+    }
+
+    fn unregister_node(
+        &mut self,
+        packet: &UnregisterNodePacket,
+        now: Instant,
+    ) -> UnregisterOutcome {
+        match self.replayed_control(packet.node_id, packet.session, packet.control_id) {
+            Some(Replay::Same(ControlOutcome::Unregister(outcome))) => return outcome,
+            Some(_) => return UnregisterOutcome::Superseded,
+            None => {}
+        }
+
+        let outcome =
+            if self.remove_node_and_edges(packet.node_id, RemovalReason::Unregistered, now) {
+                UnregisterOutcome::Unregistered
+            } else {
+                UnregisterOutcome::UnknownNode
+            };
+        self.record_control(
+            packet.node_id,
+            packet.session,
+            packet.control_id,
+            ControlOutcome::Unregister(outcome),
+            now,
+        );
+        outcome
+    }
+
+    /// Control ids grow per sender session, so an id behind the last applied
+    /// one (in wrapping order) is a stale retransmission. A new session
+    /// starts over.
+    fn replayed_control(&self, node_id: NodeId, session: u32, control_id: u32) -> Option<Replay> {
+        let reply = self.control_replies.get(&node_id)?;
+        if control_id == 0 || session != reply.session {
+            None
+        } else if control_id == reply.control_id {
+            Some(Replay::Same(reply.outcome))
+        } else if (reply.control_id.wrapping_sub(control_id) as i32) > 0 {
+            Some(Replay::Stale)
+        } else {
+            None
+        }
+    }
+
+    fn record_control(
+        &mut self,
+        node_id: NodeId,
+        session: u32,
+        control_id: u32,
+        outcome: ControlOutcome,
+        now: Instant,
+    ) {
+        if control_id != 0 {
+            self.control_replies.insert(
+                node_id,
+                ControlReply {
+                    session,
+                    control_id,
+                    outcome,
+                    at: now,
+                },
+            );
+        }
+    }
+
+    /// Marks a registered node as alive. Returns `false` for unknown nodes,
+    /// which must register first.
+    pub fn on_heartbeat(
+        &mut self,
+        packet: &HeartbeatPacket,
+        src: SocketAddr,
+        now: Instant,
+    ) -> bool {
+        let Some(node) = self.nodes.get_mut(&packet.node_id) else {
+            return false;
+        };
         node.addr = src;
-        node.desc = packet.desc;
-        node.domain = packet.domain;
         node.last_seen = now;
+        node.last_heard = Some(now);
+        self.update_liveness(packet.node_id, now);
+        true
     }
 
-    pub fn on_node_unregistered(&mut self, packet: &UnregisterNodePacket, _now: Instant) {
-        self.remove_node_and_edges(packet.node_id);
+    /// Folds the sender's view of acks lost on the way back into a decayed,
+    /// count-weighted rate. Entries for unknown edges are ignored, as are
+    /// empty ones.
+    pub fn on_ack_loss_report(&mut self, report: &AckLossReport) {
+        for entry in &report.entries {
+            let total = entry.acked as u64 + entry.lost as u64;
+            let key = EdgeKey {
+                src_node_id: report.src_node_id,
+                dst_node_id: entry.dst_node_id,
+                class: entry.class,
+            };
+            if total > 0
+                && let Some(edge) = self.edges.get_mut(&key)
+            {
+                edge.reverse_acked = edge.reverse_acked * REVERSE_LOSS_DECAY + entry.acked as f64;
+                edge.reverse_lost = edge.reverse_lost * REVERSE_LOSS_DECAY + entry.lost as f64;
+                edge.reverse_loss_rate =
+                    edge.reverse_lost / (edge.reverse_acked + edge.reverse_lost);
+            }
+        }
+    }
+
+    /// Applies every record of a `DataBatch` as if received on its own.
+    /// Also returns the `global_seq` of each record dropped at the node
+    /// limit; those are left out of the ack.
+    pub fn on_batch_received(
+        &mut self,
+        src: SocketAddr,
+        packets: &[DataPacket],
+        now: Instant,
+    ) -> (AckBatchPacket, Vec<u32>) {
+        let started = Instant::now();
+        let mut original_seqs = Vec::with_capacity(packets.len());
+        let mut dropped_seqs = Vec::new();
+        for packet in packets {
+            match self.on_packet_received(src, packet, now) {
+                Some(ack) => original_seqs.push(ack.original_seq),
+                None => dropped_seqs.push(packet.global_seq),
+            }
+        }
+        let ack = AckBatchPacket {
+            original_seqs,
+            server_timestamp_us: epoch_timestamp_us(),
+            server_processing_us: started.elapsed().as_micros().min(u32::MAX as u128) as u32,
+        };
+        (ack, dropped_seqs)
     }
 
+    /// Records one data packet. Returns `None`, recording nothing, when an
+    /// endpoint is unknown and the node limit leaves no room to track it.
     pub fn on_packet_received(
         &mut self,
         src: SocketAddr,
         packet: &DataPacket,
         now: Instant,
-    ) -> AckPacket {
+    ) -> Option<AckPacket> {
         let src_node_id = packet.src_node_id;
         let dst_node_id = packet.dst_node_id;
         let class_idx = packet.class as usize;
 
-        self.ensure_node(
+        // Checked up front so a source is not created for a packet whose
+        // destination is then refused.
+        let mut new_nodes: Vec<NodeId> = [src_node_id, dst_node_id]
+            .into_iter()
+            .filter(|node_id| !self.nodes.contains_key(node_id))
+            .collect();
+        new_nodes.dedup();
+        if self.nodes.len() + new_nodes.len() > self.config.max_nodes {
+            return None;
+        }
+        let tracked = self.ensure_node(
             src_node_id,
             packet.desc,
             infer_domain(src_node_id),
             src,
             now,
             true,
-        );
-        self.ensure_node(
+        ) && self.ensure_node(
             dst_node_id,
             domain_desc(infer_domain(dst_node_id)),
             infer_domain(dst_node_id),
@@ -205,6 +615,9 @@ impl AnalyticsManager {
             now,
             false,
         );
+        if !tracked {
+            return None;
+        }
         let src_domain = self
             .nodes
             .get(&src_node_id)
@@ -224,8 +637,13 @@ impl AnalyticsManager {
         self.route_packets[route_idx] += 1;
         self.route_bytes[route_idx] += packet.declared_bytes as u64;
 
+        let is_traffic = packet.class != TrafficClass::HealthCheck;
         if let Some(node) = self.nodes.get_mut(&src_node_id) {
             node.last_seen = now;
+            node.last_heard = Some(now);
+            if is_traffic {
+                node.last_traffic = Some(now);
+            }
             node.addr = src;
             node.desc = packet.desc;
             node.packets_by_class[class_idx] += 1;
@@ -235,9 +653,10 @@ impl AnalyticsManager {
 
             let loss_event = node.seq_trackers[class_idx].process_sequence(packet.class_seq, now);
             if let LossEvent::Loss { count } = loss_event {
-                println!(
+                log::debug!(
                     "Loss detected on node {:?}: {} packets missing",
-                    src_node_id, count
+                    src_node_id,
+                    count
                 );
             }
 
@@ -246,46 +665,120 @@ impl AnalyticsManager {
 
         if let Some(node) = self.nodes.get_mut(&dst_node_id) {
             node.last_seen = now;
+            if is_traffic {
+                node.last_traffic = Some(now);
+            }
         }
+        self.update_liveness(src_node_id, now);
+        self.update_liveness(dst_node_id, now);
 
         let key = EdgeKey {
             src_node_id,
             dst_node_id,
             class: packet.class,
         };
+        if !self.edges.contains_key(&key) {
+            self.graph_generation += 1;
+            self.events.emit(TopologyEventKind::EdgeAdded {
+                edge_id: edge_id_from_key(key),
+                src_node_id,
+                dst_node_id,
+                class: packet.class,
+            });
+        }
+        let thresholds = self.config.event_thresholds;
         let edge = self
             .edges
             .entry(key)
-            .or_insert_with(|| EdgeState::new(key, now, self.rate_window_secs));
+            .or_insert_with(|| EdgeState::new(key, now, self.config.window_secs));
         edge.last_seen = now;
         edge.packets += 1;
         edge.bytes += packet.declared_bytes as u64;
         edge.window_packets += 1;
+        edge.anomaly_window.packets += 1;
+        edge.threshold_window.packets += 1;
+        edge.alert_window.packets += 1;
         edge.rate_calculator
             .record_packet(now, packet.declared_bytes);
 
         let edge_loss_event = edge.seq_tracker.process_sequence(packet.class_seq, now);
         if let LossEvent::Loss { count } = edge_loss_event {
             edge.window_missing += count;
+            edge.anomaly_window.missing += count;
+            edge.threshold_window.missing += count;
+            edge.alert_window.missing += count;
+            if thresholds.loss_burst_packets > 0 && count >= thresholds.loss_burst_packets {
+                self.events.emit(TopologyEventKind::LossBurstDetected {
+                    edge_id: edge.edge_id,
+                    missing: count,
+                });
+            }
         }
 
         let server_timestamp_us = epoch_timestamp_us();
+        let mut latency_sample_us = None;
         if server_timestamp_us >= packet.timestamp_us {
             let latency_us = (server_timestamp_us - packet.timestamp_us) as f64;
+            latency_sample_us = Some(latency_us);
             if let Some(src_node) = self.nodes.get_mut(&src_node_id) {
                 src_node.latency_stats.add_rtt_sample(latency_us as u64);
             }
-            update_edge_latency(edge, latency_us);
+            update_edge_latency(edge, latency_us, &self.config);
+            if let Some(above) = cross_threshold(
+                &mut edge.latency_above_threshold,
+                edge.latency_ewma_us,
+                thresholds.latency_ewma_us,
+            ) {
+                self.events
+                    .emit(TopologyEventKind::EdgeMetricThresholdCrossed {
+                        edge_id: edge.edge_id,
+                        metric: EdgeMetric::LatencyEwmaUs,
+                        value: edge.latency_ewma_us,
+                        threshold: thresholds.latency_ewma_us,
+                        above,
+                    });
+            }
+        }
+        if !self.observers.packet.is_empty() {
+            let metrics = EdgeMetrics {
+                last_latency_us: latency_sample_us,
+                ..edge_metrics(edge)
+            };
+            for observer in &mut self.observers.packet {
+                observer.on_packet(packet, &metrics, now);
+            }
         }
 
-        AckPacket {
+        Some(AckPacket {
             original_seq: packet.global_seq,
             server_timestamp_us,
             server_processing_us: 0,
-        }
+        })
     }
 
     pub fn cleanup_stale(&mut self, node_ttl: Duration, edge_ttl: Duration, now: Instant) {
+        self.remove_stale(node_ttl, |_| edge_ttl, now);
+    }
+
+    /// Sweeps nodes and edges past their configured TTLs, honoring
+    /// per-class edge TTL overrides.
+    pub fn cleanup_expired(&mut self, now: Instant) {
+        let config = self.config.clone();
+        self.remove_stale(
+            Duration::from_secs(config.node_ttl_secs),
+            |class| Duration::from_secs(config.edge_ttl_secs_for(class)),
+            now,
+        );
+    }
+
+    fn remove_stale(
+        &mut self,
+        node_ttl: Duration,
+        edge_ttl: impl Fn(TrafficClass) -> Duration,
+        now: Instant,
+    ) {
+        self.refresh_liveness(now);
+        let changes_before = self.changes.last_seq();
         let stale_nodes: Vec<NodeId> = self
             .nodes
             .iter()
@@ -294,21 +787,341 @@ impl AnalyticsManager {
             .collect();
 
         for node_id in stale_nodes {
-            self.remove_node_and_edges(node_id);
+            self.remove_node_and_edges(node_id, RemovalReason::Expired, now);
         }
 
         let stale_edges: Vec<EdgeKey> = self
             .edges
             .iter()
-            .filter(|(_, edge)| now.duration_since(edge.last_seen) >= edge_ttl)
+            .filter(|(key, edge)| now.duration_since(edge.last_seen) >= edge_ttl(key.class))
             .map(|(key, _)| *key)
             .collect();
 
         for key in stale_edges {
-            if let Some(edge) = self.edges.remove(&key) {
-                self.removed_edges_since_last_snapshot.push(edge.edge_id);
+            self.remove_edge(key, RemovalReason::Expired, now);
+        }
+
+        // Retransmissions stop long before a node would expire.
+        self.control_replies
+            .retain(|_, reply| now.duration_since(reply.at) < node_ttl);
+
+        let retention = Duration::from_secs(self.config.tombstone_retention_secs);
+        let expired = |at: &Instant| now.saturating_duration_since(*at) >= retention;
+        while self
+            .node_tombstones
+            .front()
+            .is_some_and(|(at, _)| expired(at))
+        {
+            self.node_tombstones.pop_front();
+        }
+        while self
+            .edge_tombstones
+            .front()
+            .is_some_and(|(at, _)| expired(at))
+        {
+            self.edge_tombstones.pop_front();
+        }
+
+        if !self.observers.topology.is_empty() {
+            let edges: Vec<EdgeMetrics> = self.edges.values().map(edge_metrics).collect();
+            let mut removed_nodes = Vec::new();
+            let mut removed_edges = Vec::new();
+            for change in self.changes.since(changes_before) {
+                match change {
+                    Change::NodeRemoved { lineage } => removed_nodes.push(lineage[0]),
+                    Change::EdgeRemoved { edge_id, .. } => removed_edges.push(*edge_id),
+                    Change::Liveness(_) => {}
+                }
+            }
+            let sweep = CleanupSweep {
+                removed_nodes: &removed_nodes,
+                removed_edges: &removed_edges,
+                edges: &edges,
+            };
+            for observer in &mut self.observers.topology {
+                observer.on_cleanup(&sweep, now);
+            }
+        }
+    }
+
+    /// Re-evaluates every node's liveness, recording a [`LivenessEvent`] for
+    /// each change. Runs on every cleanup sweep and snapshot export.
+    pub fn refresh_liveness(&mut self, now: Instant) {
+        let node_ids: Vec<NodeId> = self.nodes.keys().copied().collect();
+        for node_id in node_ids {
+            self.update_liveness(node_id, now);
+        }
+    }
+
+    fn update_liveness(&mut self, node_id: NodeId, now: Instant) {
+        let Some(node) = self.nodes.get_mut(&node_id) else {
+            return;
+        };
+        let liveness = node_liveness(node, &self.config, now);
+        if node.liveness == Some(liveness) {
+            return;
+        }
+        let from = node.liveness.replace(liveness);
+        self.changes.record(Change::Liveness(LivenessEvent {
+            node_id,
+            from,
+            to: liveness,
+            at_epoch_us: epoch_timestamp_us(),
+        }));
+    }
+
+    /// Runs the alert rules against the current topology, without advancing
+    /// any snapshot state, and records every alert that fires or resolves as
+    /// a topology event.
+    pub fn evaluate_alerts(&mut self, now: Instant) {
+        if !self.alerts.has_rules() {
+            return;
+        }
+        // Samples straight from node and edge state; a full snapshot would
+        // also pay for graph metrics, layout and tombstones every second.
+        let nodes: Vec<NodeSample> = if self.alerts.has_node_rules() {
+            self.nodes
+                .values()
+                .map(|node| NodeSample {
+                    node_id: node.node_id,
+                    domain: node.domain,
+                    packets_per_second: total_rate_for_node(node, now).0,
+                    mean_rtt_us: latency_metrics_from_stats(&node.latency_stats).mean_rtt_us,
+                    missing_sequences: loss_metrics_from_trackers(&node.seq_trackers)
+                        .missing_sequences,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let edges: Vec<EdgeSample> = if self.alerts.has_edge_rules() {
+            self.edges
+                .values_mut()
+                .map(|edge| {
+                    let (packets_per_second, bytes_per_second) =
+                        edge.rate_calculator.calculate_rate(now);
+                    EdgeSample {
+                        metrics: edge_metrics(edge),
+                        loss_rate: edge.alert_window.take().unwrap_or(0.0),
+                        packets_per_second,
+                        bytes_per_second,
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut alerts = std::mem::take(&mut self.alerts);
+        let changes = alerts.evaluate(
+            &nodes,
+            &edges,
+            |node_id| self.nodes.get(&node_id).map(|node| node.domain),
+            |node_id, group| node_id == group || self.ancestors(node_id).contains(&group),
+            now,
+            epoch_timestamp_us(),
+        );
+        self.alerts = alerts;
+        for alert in changes {
+            log::info!(
+                "Alert {} {} for {:?} (value {:.4}, threshold {})",
+                alert.rule,
+                alert.state,
+                alert.target,
+                alert.value,
+                alert.threshold
+            );
+            self.events.emit(TopologyEventKind::AlertChanged { alert });
+        }
+    }
+
+    /// Checks every edge's loss rate since the previous call against
+    /// `analytics.event_thresholds.loss_rate`, recording each crossing as a
+    /// topology event. Meant to run once per second; edges that saw no
+    /// packets keep their side.
+    pub fn evaluate_loss_thresholds(&mut self) {
+        let threshold = self.config.event_thresholds.loss_rate;
+        for edge in self.edges.values_mut() {
+            let Some(loss_rate) = edge.threshold_window.take() else {
+                continue;
+            };
+            if let Some(above) =
+                cross_threshold(&mut edge.loss_above_threshold, loss_rate, threshold)
+            {
+                self.events
+                    .emit(TopologyEventKind::EdgeMetricThresholdCrossed {
+                        edge_id: edge.edge_id,
+                        metric: EdgeMetric::LossRate,
+                        value: loss_rate,
+                        threshold,
+                        above,
+                    });
+            }
+        }
+    }
+
+    /// Feeds every edge's current latency, jitter, packet rate and loss
+    /// since the previous call into its anomaly baselines, recording each
+    /// metric that starts or stops being anomalous as a topology event.
+    /// Meant to run once per second; a no-op while
+    /// `analytics.anomaly.score_threshold` is 0.
+    pub fn evaluate_anomalies(&mut self, now: Instant) {
+        let config = self.config.anomaly;
+        if config.score_threshold <= 0.0 {
+            return;
+        }
+        for edge in self.edges.values_mut() {
+            let (pps, _) = edge.rate_calculator.calculate_rate(now);
+            let loss_rate = edge.anomaly_window.take().unwrap_or(0.0);
+            let values = [edge.latency_ewma_us, edge.jitter_ewma_us, pps, loss_rate];
+            for change in edge.baselines.observe(values, &config) {
+                self.events.emit(TopologyEventKind::EdgeAnomalyChanged {
+                    edge_id: edge.edge_id,
+                    metric: change.metric,
+                    value: change.value,
+                    baseline_mean: change.baseline_mean,
+                    score: change.score,
+                    anomalous: change.anomalous,
+                });
+            }
+        }
+    }
+
+    /// Sequence number of the newest topology event, 0 before the first.
+    pub fn last_event_seq(&self) -> u64 {
+        self.events.last_seq()
+    }
+
+    /// Up to `limit` buffered topology events after `after_seq`.
+    pub fn events_since(&self, after_seq: u64, limit: usize) -> EventBatch {
+        self.events.since(after_seq, limit)
+    }
+
+    /// Calls `listener` with every topology event as it is recorded, for
+    /// in-process consumers.
+    pub fn add_event_listener(&mut self, listener: impl FnMut(&TopologyEvent) + Send + 'static) {
+        self.events.add_listener(listener);
+    }
+
+    /// Calls `observer` for every data packet, after it has been applied.
+    pub fn add_packet_observer(&mut self, observer: impl PacketObserver + 'static) {
+        self.observers.packet.push(Box::new(observer));
+    }
+
+    /// Calls `observer` on registrations, unregistrations and cleanup
+    /// sweeps.
+    pub fn add_topology_observer(&mut self, observer: impl TopologyObserver + 'static) {
+        self.observers.topology.push(Box::new(observer));
+    }
+
+    pub fn config(&self) -> &AnalyticsConfig {
+        &self.config
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Zeroes the global packet/byte counters. Nodes, edges and their
+    /// per-item statistics are kept.
+    pub fn reset_counters(&mut self) {
+        self.total_packets = 0;
+        self.total_bytes = 0;
+        self.packets_by_class = [0; 4];
+        self.bytes_by_class = [0; 4];
+        self.route_packets = [0; 4];
+        self.route_bytes = [0; 4];
+    }
+
+    /// Removes a node and its edges; they show up in the next snapshot's
+    /// removed lists. Returns `false` for unknown nodes.
+    pub fn evict_node(&mut self, node_id: NodeId, now: Instant) -> bool {
+        self.remove_node_and_edges(node_id, RemovalReason::Evicted, now)
+    }
+
+    pub fn evict_edge(&mut self, edge_id: EdgeId, now: Instant) -> bool {
+        let Some(key) = self
+            .edges
+            .iter()
+            .find(|(_, edge)| edge.edge_id == edge_id)
+            .map(|(key, _)| *key)
+        else {
+            return false;
+        };
+        self.remove_edge(key, RemovalReason::Evicted, now)
+    }
+
+    /// Starts a fresh experiment: every node and edge is removed (and
+    /// reported as such), counters restart and the epoch is bumped.
+    pub fn start_epoch(&mut self, now: Instant) -> u64 {
+        let edge_keys: Vec<EdgeKey> = self.edges.keys().copied().collect();
+        for key in edge_keys {
+            self.remove_edge(key, RemovalReason::EpochReset, now);
+        }
+        let node_ids: Vec<NodeId> = self.nodes.keys().copied().collect();
+        for node_id in node_ids {
+            self.remove_node_and_edges(node_id, RemovalReason::EpochReset, now);
+        }
+        self.reset_counters();
+        self.communities.clear();
+        self.modularity = None;
+        self.control_replies.clear();
+        self.epoch += 1;
+        self.epoch
+    }
+
+    /// Replaces the TTLs that are `Some`; per-class overrides are merged.
+    /// Values must already be validated as non-zero.
+    pub fn set_ttls(
+        &mut self,
+        node_ttl_secs: Option<u64>,
+        edge_ttl_secs: Option<u64>,
+        edge_ttl_secs_by_class: ClassTtls,
+    ) {
+        if let Some(ttl) = node_ttl_secs {
+            self.config.node_ttl_secs = ttl;
+        }
+        if let Some(ttl) = edge_ttl_secs {
+            self.config.edge_ttl_secs = ttl;
+        }
+        let class_ttls = &mut self.config.edge_ttl_secs_by_class;
+        class_ttls.api = edge_ttl_secs_by_class.api.or(class_ttls.api);
+        class_ttls.heavy_compute = edge_ttl_secs_by_class
+            .heavy_compute
+            .or(class_ttls.heavy_compute);
+        class_ttls.background = edge_ttl_secs_by_class.background.or(class_ttls.background);
+        class_ttls.health_check = edge_ttl_secs_by_class
+            .health_check
+            .or(class_ttls.health_check);
+    }
+
+    /// Changes the rate window for every node and edge. Rates are
+    /// computed over the new window from the next snapshot on; a derived
+    /// activity TTL follows the window.
+    pub fn set_window(&mut self, window_secs: u32) {
+        let follows_window =
+            self.config.activity_ttl_secs == Some((self.config.window_secs as u64) * 3);
+        self.config.window_secs = window_secs;
+        if follows_window {
+            self.config.activity_ttl_secs = Some((window_secs as u64) * 3);
+        }
+        for node in self.nodes.values_mut() {
+            for calculator in &mut node.rate_calculators {
+                calculator.set_window(window_secs);
             }
         }
+        for edge in self.edges.values_mut() {
+            edge.rate_calculator.set_window(window_secs);
+        }
+    }
+
+    /// Full read-only copy of the current state for `Checkpoint`.
+    pub fn checkpoint(&self, now: Instant) -> common::admin::Checkpoint {
+        common::admin::Checkpoint {
+            epoch: self.epoch,
+            taken_at_epoch_us: epoch_timestamp_us(),
+            topology: self.peek_topology_snapshot(now),
+            analytics: self.export_snapshot(),
+        }
     }
 
     pub fn cleanup_stale_clients(&mut self, timeout: Duration) {
@@ -319,14 +1132,50 @@ impl AnalyticsManager {
         &mut self,
         now: Instant,
     ) -> common::analytics::TopologySnapshot {
+        let build_started_at = Instant::now();
+        self.refresh_liveness(now);
+        self.graph_generation += 1;
+        let mut snapshot = self.peek_topology_snapshot(now);
+        self.last_snapshot_build_time = build_started_at.elapsed();
         self.snapshot_seq = self.snapshot_seq.saturating_add(1);
+        self.last_topology_epoch_us = snapshot.snapshot_timestamp_epoch_us;
+        snapshot.snapshot_seq = self.snapshot_seq;
+
+        for edge in self.edges.values_mut() {
+            let (pps, bps) = edge.rate_calculator.calculate_rate(now);
+            edge.prev_packets_per_second = pps;
+            edge.prev_bytes_per_second = bps;
+            edge.window_packets = 0;
+            edge.window_missing = 0;
+        }
+
+        self.exported_change_seq = snapshot.change_seq;
+        snapshot
+    }
+
+    /// Read-only view of the current topology for inspection (HTTP API,
+    /// health checks). Unlike `export_topology_snapshot` it leaves the
+    /// sequence number, delta rates and loss windows untouched:
+    /// `snapshot_seq` is the last exported one and the removed lists show
+    /// what the next export will carry.
+    pub fn peek_topology_snapshot(&self, now: Instant) -> common::analytics::TopologySnapshot {
+        self.peek_topology_snapshot_since(now, self.exported_change_seq)
+    }
+
+    /// Like `peek_topology_snapshot`, but with removals and liveness changes
+    /// after `after_change_seq`, for consumers following their own cursor.
+    pub fn peek_topology_snapshot_since(
+        &self,
+        now: Instant,
+        after_change_seq: u64,
+    ) -> common::analytics::TopologySnapshot {
         let snapshot_timestamp_epoch_us = epoch_timestamp_us();
         let snapshot_interval_us =
             snapshot_timestamp_epoch_us.saturating_sub(self.last_topology_epoch_us);
-        self.last_topology_epoch_us = snapshot_timestamp_epoch_us;
-        let activity_ttl = Duration::from_secs((self.rate_window_secs as u64).saturating_mul(3));
+        let activity_ttl = Duration::from_secs(self.config.activity_ttl_secs());
+        let retention = Duration::from_secs(self.config.tombstone_retention_secs);
 
-        let nodes: Vec<_> = self
+        let mut nodes: Vec<_> = self
             .nodes
             .values()
             .map(|node| {
@@ -339,60 +1188,228 @@ impl AnalyticsManager {
                         as u64,
                     last_seen_us: node.last_seen.duration_since(self.start_time).as_micros() as u64,
                     active: now.duration_since(node.last_seen) < activity_ttl,
+                    liveness: node.liveness.unwrap_or(NodeLiveness::Registered),
                     total_packets: node.packets_by_class.iter().sum(),
                     total_bytes: node.bytes_by_class.iter().sum(),
                     total_pps,
                     total_bps,
                     latency: latency_metrics_from_stats(&node.latency_stats),
                     loss: loss_metrics_from_trackers(&node.seq_trackers),
+                    parent_node_id: node.parent_node_id,
+                    member_count: 1,
+                    graph: None,
+                    layout: self
+                        .layout
+                        .as_ref()
+                        .and_then(|layout| layout.position(&node.node_id)),
+                    community_id: self.communities.get(&node.node_id).copied(),
                 }
             })
             .collect();
 
-        let mut edges: Vec<common::analytics::EdgeSnapshot> = Vec::with_capacity(self.edges.len());
-        for edge in self.edges.values_mut() {
-            let (pps, bps) = edge.rate_calculator.calculate_rate(now);
-            let delta_pps = pps - edge.prev_packets_per_second;
-            let delta_bps = bps - edge.prev_bytes_per_second;
-            edge.prev_packets_per_second = pps;
-            edge.prev_bytes_per_second = bps;
-            let loss_rate_window = if edge.window_packets == 0 {
-                0.0
-            } else {
-                edge.window_missing as f64 / edge.window_packets as f64
-            };
-            edge.window_packets = 0;
-            edge.window_missing = 0;
+        let edges: Vec<common::analytics::EdgeSnapshot> = self
+            .edges
+            .values()
+            .map(|edge| {
+                let (pps, bps) = edge.rate_calculator.calculate_rate(now);
+                let loss_rate_window = if edge.window_packets == 0 {
+                    0.0
+                } else {
+                    edge.window_missing as f64 / edge.window_packets as f64
+                };
+                common::analytics::EdgeSnapshot {
+                    edge_id: edge.edge_id,
+                    src_node_id: edge.src_node_id,
+                    dst_node_id: edge.dst_node_id,
+                    class: edge.class,
+                    first_seen_us: edge.first_seen.duration_since(self.start_time).as_micros()
+                        as u64,
+                    last_seen_us: edge.last_seen.duration_since(self.start_time).as_micros() as u64,
+                    packets: edge.packets,
+                    bytes: edge.bytes,
+                    packets_per_second: pps,
+                    bytes_per_second: bps,
+                    delta_packets_per_second: pps - edge.prev_packets_per_second,
+                    delta_bytes_per_second: bps - edge.prev_bytes_per_second,
+                    latency_ewma_us: edge.latency_ewma_us,
+                    latency_delta_us: edge.latency_delta_us,
+                    jitter_ewma_us: edge.jitter_ewma_us,
+                    loss_rate_window,
+                    reverse_loss_rate: edge.reverse_loss_rate,
+                    active: now.duration_since(edge.last_seen) < activity_ttl,
+                    member_edges: 1,
+                    anomaly: edge.baselines.scores(),
+                }
+            })
+            .collect();
 
-            edges.push(common::analytics::EdgeSnapshot {
-                edge_id: edge.edge_id,
-                src_node_id: edge.src_node_id,
-                dst_node_id: edge.dst_node_id,
-                class: edge.class,
-                packets: edge.packets,
-                bytes: edge.bytes,
-                packets_per_second: pps,
-                bytes_per_second: bps,
-                delta_packets_per_second: delta_pps,
-                delta_bytes_per_second: delta_bps,
-                latency_ewma_us: edge.latency_ewma_us,
-                latency_delta_us: edge.latency_delta_us,
-                jitter_ewma_us: edge.jitter_ewma_us,
-                loss_rate_window,
-                active: now.duration_since(edge.last_seen) < activity_ttl,
-            });
+        if self.config.graph_analytics {
+            let mut cache = self.graph_metrics.borrow_mut();
+            if cache
+                .as_ref()
+                .is_none_or(|(generation, _)| *generation != self.graph_generation)
+            {
+                *cache = Some((self.graph_generation, self.graph().node_metrics()));
+            }
+            let (_, metrics) = cache.as_ref().expect("graph metrics just cached");
+            for node in &mut nodes {
+                node.graph = metrics.get(&node.node_id).copied();
+            }
         }
 
-        common::analytics::TopologySnapshot {
+        let mut snapshot = common::analytics::TopologySnapshot {
             snapshot_seq: self.snapshot_seq,
             snapshot_timestamp_epoch_us,
             snapshot_interval_us,
             nodes,
             edges,
-            removed_nodes: std::mem::take(&mut self.removed_nodes_since_last_snapshot),
-            removed_edges: std::mem::take(&mut self.removed_edges_since_last_snapshot),
+            removed_nodes: Vec::new(),
+            removed_edges: Vec::new(),
+            liveness_events: Vec::new(),
+            change_seq: 0,
+            node_tombstones: retained(&self.node_tombstones, retention, now),
+            edge_tombstones: retained(&self.edge_tombstones, retention, now),
             global_stats: self.global_stats(),
+            modularity: self.modularity,
+            alerts: self.alerts.active(),
+        };
+        self.changes.fill(&mut snapshot, after_change_seq);
+        snapshot
+    }
+
+    /// Topology snapshot collapsed to `max_depth` levels of the grouping
+    /// hierarchy, without touching export state. Nodes deeper than that are
+    /// folded into their ancestor at `max_depth`, and their edges into group
+    /// super-edges.
+    pub fn peek_grouped_topology_snapshot(
+        &self,
+        now: Instant,
+        max_depth: u8,
+    ) -> common::analytics::TopologySnapshot {
+        let snapshot = self.peek_topology_snapshot(now);
+        self.rollup(snapshot, max_depth, self.exported_change_seq)
+    }
+
+    /// Topology snapshot restricted by `query`, without touching export
+    /// state. Removals follow `query.after_change_seq` when set.
+    pub fn peek_filtered_topology_snapshot(
+        &self,
+        now: Instant,
+        query: &common::analytics::TopologyQuery,
+    ) -> common::analytics::TopologySnapshot {
+        let after_change_seq = query.after_change_seq.unwrap_or(self.exported_change_seq);
+        let snapshot = self.peek_topology_snapshot_since(now, after_change_seq);
+        self.apply_query(snapshot, query, after_change_seq)
+    }
+
+    fn apply_query(
+        &self,
+        snapshot: common::analytics::TopologySnapshot,
+        query: &common::analytics::TopologyQuery,
+        after_change_seq: u64,
+    ) -> common::analytics::TopologySnapshot {
+        let snapshot = query::filter_snapshot(snapshot, query);
+        match query.group_depth {
+            Some(max_depth) => self.rollup(snapshot, max_depth, after_change_seq),
+            None => snapshot,
+        }
+    }
+
+    /// Rolls `snapshot` up to `max_depth`, mapping the removals after
+    /// `after_change_seq` onto groups and super-edges.
+    fn rollup(
+        &self,
+        snapshot: common::analytics::TopologySnapshot,
+        max_depth: u8,
+        after_change_seq: u64,
+    ) -> common::analytics::TopologySnapshot {
+        let mut snapshot =
+            grouping::rollup_snapshot(snapshot, &self.group_representatives(max_depth));
+        grouping::rollup_removals(
+            &mut snapshot,
+            self.changes.since(after_change_seq),
+            max_depth,
+        );
+        snapshot
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.start_time.elapsed()
+    }
+
+    /// Number of topology snapshots exported so far.
+    pub fn snapshot_count(&self) -> u64 {
+        self.snapshot_seq
+    }
+
+    /// Time spent building the most recent exported topology snapshot.
+    pub fn last_snapshot_build_time(&self) -> Duration {
+        self.last_snapshot_build_time
+    }
+
+    /// Lowest-latency directed route between two nodes over current edges.
+    pub fn shortest_path(
+        &self,
+        src_node_id: NodeId,
+        dst_node_id: NodeId,
+    ) -> common::analytics::PathSnapshot {
+        self.graph().shortest_path(src_node_id, dst_node_id)
+    }
+
+    fn graph(&self) -> Graph {
+        let mut graph = Graph::new(self.nodes.keys().copied());
+        for edge in self.edges.values() {
+            graph.add_edge(
+                edge.src_node_id,
+                edge.dst_node_id,
+                edge.edge_id,
+                edge.latency_ewma_us,
+            );
         }
+        graph
+    }
+
+    /// Ancestors of `node_id`, nearest first. Parents that are not (or no
+    /// longer) registered still count as a group but end the chain.
+    pub fn ancestors(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut chain = Vec::new();
+        let mut current = self
+            .nodes
+            .get(&node_id)
+            .and_then(|node| node.parent_node_id);
+        while let Some(parent) = current {
+            if parent == node_id || chain.contains(&parent) {
+                break;
+            }
+            chain.push(parent);
+            current = self.nodes.get(&parent).and_then(|node| node.parent_node_id);
+        }
+        chain
+    }
+
+    /// The node followed by its ancestors, nearest first.
+    fn lineage(&self, node_id: NodeId) -> Vec<NodeId> {
+        let mut lineage = vec![node_id];
+        lineage.extend(self.ancestors(node_id));
+        lineage
+    }
+
+    fn group_representatives(&self, max_depth: u8) -> HashMap<NodeId, NodeId> {
+        self.nodes
+            .keys()
+            .filter_map(|node_id| {
+                let group = grouping::representative(&self.lineage(*node_id), max_depth);
+                (group != *node_id).then_some((*node_id, group))
+            })
+            .collect()
     }
 
     pub fn export_snapshot(&self) -> common::analytics::AnalyticsSnapshot {
@@ -440,6 +1457,8 @@ impl AnalyticsManager {
         }
     }
 
+    /// Tracks `node_id`, creating it if needed. Returns false when it is
+    /// unknown and the node limit is reached.
     fn ensure_node(
         &mut self,
         node_id: NodeId,
@@ -448,52 +1467,136 @@ impl AnalyticsManager {
         addr: SocketAddr,
         now: Instant,
         refresh_desc: bool,
-    ) {
-        if !self.nodes.contains_key(&node_id) && self.nodes.len() >= self.max_nodes {
-            return;
+    ) -> bool {
+        if !self.nodes.contains_key(&node_id) && self.nodes.len() >= self.config.max_nodes {
+            return false;
         }
 
+        let created = !self.nodes.contains_key(&node_id);
         let node = self.nodes.entry(node_id).or_insert_with(|| {
-            NodeState::new(node_id, desc, domain, addr, now, self.rate_window_secs)
+            NodeState::new(node_id, desc, domain, addr, now, self.config.window_secs)
         });
 
+        let relabeled = refresh_desc && node.desc != desc;
         if refresh_desc {
             node.desc = desc;
         }
         node.addr = addr;
         node.last_seen = now;
+        if created {
+            self.graph_generation += 1;
+            self.events.emit(TopologyEventKind::NodeAdded {
+                node_id,
+                desc,
+                domain,
+            });
+        } else if relabeled {
+            self.events.emit(TopologyEventKind::NodeUpdated {
+                node_id,
+                desc,
+                domain: node.domain,
+            });
+        }
+        true
     }
 
-    fn remove_node_and_edges(&mut self, node_id: NodeId) {
-        if self.nodes.remove(&node_id).is_none() {
-            return;
+    /// Removes a node, leaving a tombstone; its edges go with it as
+    /// [`RemovalReason::EndpointRemoved`].
+    fn remove_node_and_edges(
+        &mut self,
+        node_id: NodeId,
+        reason: RemovalReason,
+        now: Instant,
+    ) -> bool {
+        if !self.nodes.contains_key(&node_id) {
+            return false;
         }
-
-        self.removed_nodes_since_last_snapshot.push(node_id);
-        let mut removed_edge_ids = HashSet::new();
+        // Edges go first, while both endpoints still resolve to their groups.
         let to_remove: Vec<EdgeKey> = self
             .edges
             .iter()
             .filter(|(_, edge)| edge.src_node_id == node_id || edge.dst_node_id == node_id)
             .map(|(key, _)| *key)
             .collect();
-
         for key in to_remove {
-            if let Some(edge) = self.edges.remove(&key) {
-                removed_edge_ids.insert(edge.edge_id);
-            }
+            self.remove_edge(key, RemovalReason::EndpointRemoved, now);
         }
 
-        self.removed_edges_since_last_snapshot
-            .extend(removed_edge_ids);
+        let lineage = self.lineage(node_id);
+        let Some(node) = self.nodes.remove(&node_id) else {
+            return false;
+        };
+        self.communities.remove(&node_id);
+        self.graph_generation += 1;
+        let removed_at_epoch_us = epoch_timestamp_us();
+        self.changes.record(Change::NodeRemoved { lineage });
+        self.changes.record(Change::Liveness(LivenessEvent {
+            node_id,
+            from: node.liveness,
+            to: NodeLiveness::Departed,
+            at_epoch_us: removed_at_epoch_us,
+        }));
+        self.node_tombstones.push_back((
+            now,
+            NodeTombstone {
+                node_id,
+                desc: node.desc,
+                domain: node.domain,
+                reason,
+                removed_at_epoch_us,
+                first_seen_us: node.first_seen.duration_since(self.start_time).as_micros() as u64,
+                last_seen_us: node.last_seen.duration_since(self.start_time).as_micros() as u64,
+                liveness: node.liveness.unwrap_or(NodeLiveness::Registered),
+                total_packets: node.packets_by_class.iter().sum(),
+                total_bytes: node.bytes_by_class.iter().sum(),
+            },
+        ));
+        self.events
+            .emit(TopologyEventKind::NodeRemoved { node_id, reason });
+        true
+    }
+
+    fn remove_edge(&mut self, key: EdgeKey, reason: RemovalReason, now: Instant) -> bool {
+        let Some(edge) = self.edges.remove(&key) else {
+            return false;
+        };
+        self.graph_generation += 1;
+        self.changes.record(Change::EdgeRemoved {
+            edge_id: edge.edge_id,
+            class: edge.class,
+            src_lineage: self.lineage(edge.src_node_id),
+            dst_lineage: self.lineage(edge.dst_node_id),
+        });
+        self.events.emit(TopologyEventKind::EdgeRemoved {
+            edge_id: edge.edge_id,
+            reason,
+        });
+        self.edge_tombstones.push_back((
+            now,
+            EdgeTombstone {
+                edge_id: edge.edge_id,
+                src_node_id: edge.src_node_id,
+                dst_node_id: edge.dst_node_id,
+                class: edge.class,
+                reason,
+                removed_at_epoch_us: epoch_timestamp_us(),
+                last_seen_us: edge.last_seen.duration_since(self.start_time).as_micros() as u64,
+                packets: edge.packets,
+                bytes: edge.bytes,
+                latency_ewma_us: edge.latency_ewma_us,
+                reverse_loss_rate: edge.reverse_loss_rate,
+            },
+        ));
+        true
     }
 
-    fn global_stats(&self) -> common::analytics::GlobalStats {
+    pub fn global_stats(&self) -> common::analytics::GlobalStats {
         common::analytics::GlobalStats {
             total_packets: self.total_packets,
             total_bytes: self.total_bytes,
             packets_by_class: self.packets_by_class,
             bytes_by_class: self.bytes_by_class,
+            epoch: self.epoch,
             route_stats: std::array::from_fn(|i| common::analytics::RouteStats {
                 packets: self.route_packets[i],
                 bytes: self.route_bytes[i],
@@ -503,6 +1606,67 @@ impl AnalyticsManager {
     }
 }
 
+fn edge_metrics(edge: &EdgeState) -> EdgeMetrics {
+    EdgeMetrics {
+        edge_id: edge.edge_id,
+        src_node_id: edge.src_node_id,
+        dst_node_id: edge.dst_node_id,
+        class: edge.class,
+        packets: edge.packets,
+        bytes: edge.bytes,
+        latency_ewma_us: edge.latency_ewma_us,
+        jitter_ewma_us: edge.jitter_ewma_us,
+        last_latency_us: edge.last_latency_sample_us,
+        window_packets: edge.window_packets,
+        window_missing: edge.window_missing,
+        reverse_loss_rate: edge.reverse_loss_rate,
+    }
+}
+
+/// Updates `above` for `value` against `threshold` (0 = disabled) and
+/// returns the new side when it changed.
+fn cross_threshold(above: &mut bool, value: f64, threshold: f64) -> Option<bool> {
+    if threshold <= 0.0 {
+        return None;
+    }
+    let now_above = value > threshold;
+    (std::mem::replace(above, now_above) != now_above).then_some(now_above)
+}
+
+/// Tombstones younger than `retention`, oldest first.
+fn retained<T: Clone>(
+    tombstones: &VecDeque<(Instant, T)>,
+    retention: Duration,
+    now: Instant,
+) -> Vec<T> {
+    tombstones
+        .iter()
+        .filter(|(at, _)| now.saturating_duration_since(*at) < retention)
+        .map(|(_, tombstone)| tombstone.clone())
+        .collect()
+}
+
+/// Silence decides suspect/down first; a node still heard from is active
+/// or idle by its traffic, or merely registered if it never had any.
+fn node_liveness(node: &NodeState, config: &AnalyticsConfig, now: Instant) -> NodeLiveness {
+    let silence = now.duration_since(node.last_heard.unwrap_or(node.last_seen));
+    if silence >= Duration::from_millis(config.down_after_ms) {
+        NodeLiveness::Down
+    } else if silence >= Duration::from_millis(config.suspect_after_ms) {
+        NodeLiveness::Suspect
+    } else {
+        match node.last_traffic {
+            None => NodeLiveness::Registered,
+            Some(at)
+                if now.duration_since(at) < Duration::from_secs(config.activity_ttl_secs()) =>
+            {
+                NodeLiveness::Active
+            }
+            Some(_) => NodeLiveness::Idle,
+        }
+    }
+}
+
 fn total_rate_for_node(node: &NodeState, now: Instant) -> (f64, f64) {
     node.rate_calculators.iter().fold((0.0, 0.0), |acc, calc| {
         let (pps, bps) = calc.calculate_rate(now);
@@ -571,15 +1735,15 @@ fn infer_domain(node_id: NodeId) -> NodeDomain {
     NodeDomain::Internal
 }
 
-fn update_edge_latency(edge: &mut EdgeState, latency_us: f64) {
-    const LATENCY_ALPHA: f64 = 0.2;
-    const JITTER_ALPHA: f64 = 0.2;
+fn update_edge_latency(edge: &mut EdgeState, latency_us: f64, config: &AnalyticsConfig) {
+    let latency_alpha = config.latency_ewma_alpha;
+    let jitter_alpha = config.jitter_ewma_alpha;
 
     if edge.latency_ewma_us == 0.0 {
         edge.latency_ewma_us = latency_us;
     } else {
         edge.latency_ewma_us =
-            LATENCY_ALPHA * latency_us + (1.0 - LATENCY_ALPHA) * edge.latency_ewma_us;
+            latency_alpha * latency_us + (1.0 - latency_alpha) * edge.latency_ewma_us;
     }
 
     if let Some(prev) = edge.last_latency_sample_us {
@@ -588,7 +1752,7 @@ fn update_edge_latency(edge: &mut EdgeState, latency_us: f64) {
             edge.jitter_ewma_us = jitter_sample;
         } else {
             edge.jitter_ewma_us =
-                JITTER_ALPHA * jitter_sample + (1.0 - JITTER_ALPHA) * edge.jitter_ewma_us;
+                jitter_alpha * jitter_sample + (1.0 - jitter_alpha) * edge.jitter_ewma_us;
         }
     }
 
@@ -596,7 +1760,7 @@ fn update_edge_latency(edge: &mut EdgeState, latency_us: f64) {
     edge.last_latency_sample_us = Some(latency_us);
 }
 
-fn edge_id_from_key(key: EdgeKey) -> EdgeId {
+pub(crate) fn edge_id_from_key(key: EdgeKey) -> EdgeId {
     let mut first = std::collections::hash_map::DefaultHasher::new();
     key.hash(&mut first);
     let first_hash = first.finish();
@@ -622,6 +1786,7 @@ fn epoch_timestamp_us() -> u64 {
 #[cfg(test)]
 mod tests {
     use super::AnalyticsManager;
+    use common::config::{AnalyticsConfig, ClassTtls};
     use common::{NodeDomain, NodeId, TrafficClass, WireMessage};
     use std::net::SocketAddr;
     use std::str::FromStr;
@@ -704,7 +1869,9 @@ mod tests {
             1000,
             src_desc,
         );
-        let ack1 = analytics.on_packet_received(addr, &packet1, now + Duration::from_millis(10));
+        let ack1 = analytics
+            .on_packet_received(addr, &packet1, now + Duration::from_millis(10))
+            .expect("packet should be tracked");
         assert_eq!(ack1.original_seq, 10);
 
         let packet2 = common::make_data_packet(
@@ -716,7 +1883,9 @@ mod tests {
             2000,
             src_desc,
         );
-        let ack2 = analytics.on_packet_received(addr, &packet2, now + Duration::from_millis(20));
+        let ack2 = analytics
+            .on_packet_received(addr, &packet2, now + Duration::from_millis(20))
+            .expect("packet should be tracked");
         assert_eq!(ack2.original_seq, 11);
 
         let packet3 = common::make_data_packet(
@@ -790,6 +1959,146 @@ mod tests {
         assert_eq!(snapshot.removed_edges.len(), 1);
     }
 
+    #[test]
+    fn liveness_follows_heartbeats_traffic_and_silence() {
+        use common::analytics::NodeLiveness::*;
+
+        let mut analytics = AnalyticsManager::with_config(AnalyticsConfig {
+            activity_ttl_secs: Some(1),
+            suspect_after_ms: 2000,
+            down_after_ms: 4000,
+            ..AnalyticsConfig::default()
+        });
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let node_id: NodeId = *b"NODE-PULSE-00001";
+        let peer_id: NodeId = *b"NODE-PULSE-00002";
+        let send = |analytics: &mut AnalyticsManager, class: TrafficClass, ms: u64| {
+            let packet =
+                common::make_data_packet(node_id, peer_id, 1, 1, class, 100, *b"pulse-node------");
+            analytics.on_packet_received(test_addr(), &packet, at(ms));
+        };
+
+        register_node(&mut analytics, node_id, NodeDomain::Internal, start);
+        send(&mut analytics, TrafficClass::Api, 100);
+        let mut events = analytics.export_topology_snapshot(at(200)).liveness_events;
+        // Health checks keep the node heard from without counting as traffic.
+        send(&mut analytics, TrafficClass::HealthCheck, 1500);
+        let snapshot = analytics.export_topology_snapshot(at(3600));
+        let liveness_of = |id: NodeId| {
+            snapshot
+                .nodes
+                .iter()
+                .find(|node| node.node_id == id)
+                .map(|node| node.liveness)
+        };
+        assert_eq!(liveness_of(node_id), Some(Suspect));
+        assert_eq!(liveness_of(peer_id), Some(Suspect));
+        events.extend(snapshot.liveness_events);
+        events.extend(analytics.export_topology_snapshot(at(5600)).liveness_events);
+
+        let heartbeat = common::make_heartbeat_packet(node_id);
+        assert!(analytics.on_heartbeat(&heartbeat, test_addr(), at(6000)));
+        let unknown = common::make_heartbeat_packet(*b"NODE-PULSE-GHOST");
+        assert!(!analytics.on_heartbeat(&unknown, test_addr(), at(6000)));
+        let unregister = common::make_unregister_node_packet(node_id);
+        analytics.on_node_unregistered(&unregister, at(6100));
+        events.extend(analytics.export_topology_snapshot(at(6200)).liveness_events);
+
+        let transitions: Vec<_> = events
+            .iter()
+            .filter(|event| event.node_id == node_id)
+            .map(|event| (event.from, event.to))
+            .collect();
+        assert_eq!(
+            transitions,
+            [
+                (None, Registered),
+                (Some(Registered), Active),
+                (Some(Active), Idle),
+                (Some(Idle), Suspect),
+                (Some(Suspect), Down),
+                (Some(Down), Idle),
+                (Some(Idle), Departed),
+            ]
+        );
+    }
+
+    #[test]
+    fn removals_leave_reasoned_tombstones_until_retention_passes() {
+        use common::analytics::RemovalReason;
+
+        let mut analytics = AnalyticsManager::with_config(AnalyticsConfig {
+            tombstone_retention_secs: 10,
+            ..AnalyticsConfig::default()
+        });
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let leaving: NodeId = *b"NODE-TOMB-LEAVE1";
+        let target: NodeId = *b"NODE-TOMB-TARGET";
+        let other: NodeId = *b"NODE-TOMB-OTHER1";
+        for node_id in [leaving, target, other] {
+            register_node(&mut analytics, node_id, NodeDomain::Internal, start);
+        }
+        for src in [leaving, other] {
+            let packet = common::make_data_packet(
+                src,
+                target,
+                1,
+                1,
+                TrafficClass::Api,
+                500,
+                *b"tomb-node-------",
+            );
+            analytics.on_packet_received(test_addr(), &packet, start);
+        }
+        let evicted_edge = analytics
+            .export_topology_snapshot(start)
+            .edges
+            .iter()
+            .find(|edge| edge.src_node_id == other)
+            .map(|edge| edge.edge_id)
+            .expect("edge from other node");
+
+        let unregister = common::make_unregister_node_packet(leaving);
+        analytics.on_node_unregistered(&unregister, at(1));
+        assert!(analytics.evict_edge(evicted_edge, at(2)));
+        analytics.cleanup_stale(Duration::from_secs(5), Duration::from_secs(5), at(8));
+
+        let snapshot = analytics.export_topology_snapshot(at(8));
+        let node_reasons: Vec<_> = snapshot
+            .node_tombstones
+            .iter()
+            .map(|tombstone| (tombstone.node_id, tombstone.reason))
+            .collect();
+        assert_eq!(node_reasons[0], (leaving, RemovalReason::Unregistered));
+        assert!(node_reasons.contains(&(target, RemovalReason::Expired)));
+        assert!(node_reasons.contains(&(other, RemovalReason::Expired)));
+        assert_eq!(snapshot.node_tombstones[0].total_packets, 1);
+        let edge_reasons: Vec<_> = snapshot
+            .edge_tombstones
+            .iter()
+            .map(|tombstone| (tombstone.src_node_id, tombstone.reason))
+            .collect();
+        assert_eq!(
+            edge_reasons,
+            [
+                (leaving, RemovalReason::EndpointRemoved),
+                (other, RemovalReason::Evicted)
+            ]
+        );
+
+        // Late joiners still see the departures after the removed ids are gone.
+        let later = analytics.export_topology_snapshot(at(9));
+        assert!(later.removed_nodes.is_empty());
+        assert_eq!(later.node_tombstones.len(), 3);
+
+        analytics.cleanup_stale(Duration::from_secs(5), Duration::from_secs(5), at(12));
+        let pruned = analytics.peek_topology_snapshot(at(12));
+        assert_eq!(pruned.node_tombstones.len(), 2);
+        assert!(pruned.edge_tombstones.is_empty());
+    }
+
     #[test]
     fn snapshot_contains_delta_rates_and_latency_trends() {
         let mut analytics = AnalyticsManager::new(5, 100);
@@ -854,6 +2163,344 @@ mod tests {
         assert!(edge2.jitter_ewma_us > 0.0);
     }
 
+    #[test]
+    fn grouped_snapshot_rolls_children_into_super_edges() {
+        let mut analytics = AnalyticsManager::new(5, 100);
+        let now = Instant::now();
+        let addr = test_addr();
+        let host_a: NodeId = *b"HOST-GROUP-0000A";
+        let host_b: NodeId = *b"HOST-GROUP-0000B";
+        let a1: NodeId = *b"NODE-GROUP-000A1";
+        let a2: NodeId = *b"NODE-GROUP-000A2";
+        let b1: NodeId = *b"NODE-GROUP-000B1";
+        let desc = *b"group-member----";
+
+        register_node(&mut analytics, host_a, NodeDomain::Internal, now);
+        register_node(&mut analytics, host_b, NodeDomain::External, now);
+        for (node_id, parent) in [(a1, host_a), (a2, host_a), (b1, host_b)] {
+            let register = common::make_register_child_node_packet(
+                node_id,
+                desc,
+                NodeDomain::Internal,
+                parent,
+            );
+            analytics.on_node_registered(&register, addr, now);
+        }
+
+        let sends = [(a1, b1, 1000), (a2, b1, 500), (a1, a2, 300)];
+        for (seq, (src, dst, bytes)) in sends.into_iter().enumerate() {
+            let packet =
+                common::make_data_packet(src, dst, seq as u32, 1, TrafficClass::Api, bytes, desc);
+            analytics.on_packet_received(addr, &packet, now + Duration::from_millis(10));
+        }
+
+        let snapshot = analytics.peek_grouped_topology_snapshot(now + Duration::from_millis(20), 0);
+        assert_eq!(snapshot.nodes.len(), 2);
+        let group_a = snapshot
+            .nodes
+            .iter()
+            .find(|node| node.node_id == host_a)
+            .expect("host a group should exist");
+        assert_eq!(group_a.member_count, 3);
+        assert_eq!(group_a.total_packets, 3);
+
+        assert_eq!(snapshot.edges.len(), 1);
+        let super_edge = &snapshot.edges[0];
+        assert_eq!(super_edge.src_node_id, host_a);
+        assert_eq!(super_edge.dst_node_id, host_b);
+        assert_eq!(super_edge.packets, 2);
+        assert_eq!(super_edge.bytes, 1500);
+        assert_eq!(super_edge.member_edges, 2);
+
+        let flat = analytics.peek_grouped_topology_snapshot(now + Duration::from_millis(30), 1);
+        assert_eq!(flat.nodes.len(), 5);
+        assert_eq!(flat.edges.len(), 3);
+
+        // Removed members stay hidden inside their group until the group or
+        // super-edge itself goes away.
+        let later = now + Duration::from_millis(40);
+        analytics.on_node_unregistered(&common::make_unregister_node_packet(a1), later);
+        let grouped = analytics.peek_grouped_topology_snapshot(later, 0);
+        assert!(grouped.removed_nodes.is_empty());
+        assert!(grouped.removed_edges.is_empty());
+
+        analytics.on_node_unregistered(&common::make_unregister_node_packet(a2), later);
+        let grouped = analytics.peek_grouped_topology_snapshot(later, 0);
+        assert!(grouped.removed_nodes.is_empty());
+        assert_eq!(grouped.removed_edges, vec![super_edge.edge_id]);
+    }
+
+    #[test]
+    fn filtered_snapshot_applies_class_and_neighborhood_filters() {
+        let mut analytics = AnalyticsManager::new(5, 100);
+        let now = Instant::now();
+        let addr = test_addr();
+        let a: NodeId = *b"NODE-FILTR-0000A";
+        let b: NodeId = *b"NODE-FILTR-0000B";
+        let c: NodeId = *b"NODE-FILTR-0000C";
+        let d: NodeId = *b"NODE-FILTR-0000D";
+        let x: NodeId = *b"NODE-FILTR-0000X";
+        let desc = *b"filter-node-----";
+
+        for node_id in [a, b, c, d] {
+            register_node(&mut analytics, node_id, NodeDomain::Internal, now);
+        }
+        register_node(&mut analytics, x, NodeDomain::External, now);
+        let sends = [
+            (a, b, TrafficClass::Api),
+            (b, c, TrafficClass::Api),
+            (c, d, TrafficClass::HealthCheck),
+            (a, x, TrafficClass::Background),
+            (x, d, TrafficClass::Background),
+        ];
+        for (seq, (src, dst, class)) in sends.into_iter().enumerate() {
+            let packet = common::make_data_packet(src, dst, seq as u32, 1, class, 100, desc);
+            analytics.on_packet_received(addr, &packet, now + Duration::from_millis(10));
+        }
+
+        let neighborhood = common::analytics::TopologyQuery {
+            node_ids: vec![a],
+            hops: 1,
+            ..Default::default()
+        };
+        let snapshot = analytics
+            .peek_filtered_topology_snapshot(now + Duration::from_millis(20), &neighborhood);
+        let mut node_ids: Vec<NodeId> = snapshot.nodes.iter().map(|node| node.node_id).collect();
+        node_ids.sort();
+        assert_eq!(node_ids, vec![a, b, x]);
+        assert_eq!(snapshot.edges.len(), 2);
+
+        // Two hops reach d only through the external x, which the domain
+        // filter removes first.
+        let internal_neighborhood = common::analytics::TopologyQuery {
+            node_ids: vec![a],
+            hops: 2,
+            domains: vec![NodeDomain::Internal],
+            ..Default::default()
+        };
+        let snapshot = analytics.peek_filtered_topology_snapshot(
+            now + Duration::from_millis(25),
+            &internal_neighborhood,
+        );
+        let mut node_ids: Vec<NodeId> = snapshot.nodes.iter().map(|node| node.node_id).collect();
+        node_ids.sort();
+        assert_eq!(node_ids, vec![a, b, c]);
+        assert_eq!(snapshot.edges.len(), 2);
+
+        let health_only = common::analytics::TopologyQuery {
+            classes: vec![TrafficClass::HealthCheck],
+            ..Default::default()
+        };
+        let snapshot = analytics
+            .peek_filtered_topology_snapshot(now + Duration::from_millis(30), &health_only);
+        assert_eq!(snapshot.nodes.len(), 2);
+        assert_eq!(snapshot.edges.len(), 1);
+        assert_eq!(snapshot.edges[0].src_node_id, c);
+
+        let busy_only = common::analytics::TopologyQuery {
+            min_packets_per_second: Some(1000.0),
+            ..Default::default()
+        };
+        let snapshot =
+            analytics.peek_filtered_topology_snapshot(now + Duration::from_millis(40), &busy_only);
+        assert!(snapshot.nodes.is_empty());
+        assert!(snapshot.edges.is_empty());
+    }
+
+    #[test]
+    fn graph_analytics_find_components_centrality_and_shortest_path() {
+        let mut analytics = AnalyticsManager::new(5, 100);
+        let now = Instant::now();
+        let addr = test_addr();
+        let a: NodeId = *b"NODE-GRAPH-0000A";
+        let b: NodeId = *b"NODE-GRAPH-0000B";
+        let c: NodeId = *b"NODE-GRAPH-0000C";
+        let lone: NodeId = *b"NODE-GRAPH-0000D";
+        let desc = *b"graph-node------";
+
+        for node_id in [a, b, c, lone] {
+            register_node(&mut analytics, node_id, NodeDomain::Internal, now);
+        }
+        let sends = [(a, b, 1_000), (b, c, 1_000), (a, c, 50_000)];
+        for (seq, (src, dst, latency_us)) in sends.into_iter().enumerate() {
+            let mut packet =
+                common::make_data_packet(src, dst, seq as u32, 1, TrafficClass::Api, 100, desc);
+            packet.timestamp_us = common::now_timestamp_us().saturating_sub(latency_us);
+            analytics.on_packet_received(addr, &packet, now + Duration::from_millis(10));
+        }
+
+        let path = analytics.shortest_path(a, c);
+        assert!(path.found);
+        assert_eq!(path.node_ids, vec![a, b, c]);
+        assert_eq!(path.edge_ids.len(), 2);
+        assert!(!analytics.shortest_path(c, a).found);
+
+        let snapshot = analytics.export_topology_snapshot(now + Duration::from_millis(20));
+        let metrics = |node_id: NodeId| {
+            snapshot
+                .nodes
+                .iter()
+                .find(|node| node.node_id == node_id)
+                .and_then(|node| node.graph)
+                .expect("graph metrics should be present")
+        };
+        assert_eq!(metrics(a).out_degree, 2);
+        assert_eq!(metrics(c).in_degree, 2);
+        assert!(metrics(b).betweenness_centrality > 0.0);
+        assert_eq!(metrics(a).betweenness_centrality, 0.0);
+        assert_eq!(metrics(a).component_id, metrics(c).component_id);
+        assert_ne!(metrics(a).component_id, metrics(lone).component_id);
+
+        // Cached metrics are dropped as soon as the topology changes.
+        let packet = common::make_data_packet(c, lone, 9, 1, TrafficClass::Api, 100, desc);
+        analytics.on_packet_received(addr, &packet, now + Duration::from_millis(30));
+        let peeked = analytics.peek_topology_snapshot(now + Duration::from_millis(30));
+        let lone_metrics = peeked
+            .nodes
+            .iter()
+            .find(|node| node.node_id == lone)
+            .and_then(|node| node.graph)
+            .expect("graph metrics should be present");
+        assert_eq!(lone_metrics.in_degree, 1);
+        assert_eq!(lone_metrics.component_id, metrics(a).component_id);
+    }
+
+    #[test]
+    fn layout_engine_pulls_busy_peers_together_and_stays_stable() {
+        let mut analytics = AnalyticsManager::new(5, 100);
+        let now = Instant::now();
+        let addr = test_addr();
+        let a: NodeId = *b"NODE-LAYOT-0000A";
+        let b: NodeId = *b"NODE-LAYOT-0000B";
+        let c: NodeId = *b"NODE-LAYOT-0000C";
+        let desc = *b"layout-node-----";
+
+        let snapshot = analytics.export_topology_snapshot(now);
+        assert!(snapshot.nodes.iter().all(|node| node.layout.is_none()));
+
+        analytics.enable_layout();
+        for node_id in [a, b, c] {
+            register_node(&mut analytics, node_id, NodeDomain::Internal, now);
+        }
+        for seq in 0..50 {
+            let packet = common::make_data_packet(a, b, seq, seq, TrafficClass::Api, 100, desc);
+            analytics.on_packet_received(addr, &packet, now);
+        }
+
+        analytics.relax_layout(now, 300);
+        let before = analytics.export_topology_snapshot(now);
+        analytics.relax_layout(now, 1);
+        let after = analytics.export_topology_snapshot(now);
+
+        let position = |snapshot: &common::analytics::TopologySnapshot, node_id: NodeId| {
+            snapshot
+                .nodes
+                .iter()
+                .find(|node| node.node_id == node_id)
+                .and_then(|node| node.layout)
+                .expect("layout position should exist")
+                .position_2d
+        };
+        let distance =
+            |p: [f64; 2], q: [f64; 2]| ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt();
+
+        let (pa, pb, pc) = (
+            position(&after, a),
+            position(&after, b),
+            position(&after, c),
+        );
+        assert!(distance(pa, pb) < distance(pa, pc));
+        assert!(distance(pa, pb) < distance(pb, pc));
+        assert!(distance(position(&before, a), pa) <= 1.0 + f64::EPSILON);
+    }
+
+    #[test]
+    fn cleanup_expired_applies_per_class_edge_ttls() {
+        let mut analytics = AnalyticsManager::with_config(AnalyticsConfig {
+            edge_ttl_secs: 30,
+            edge_ttl_secs_by_class: ClassTtls {
+                health_check: Some(2),
+                ..ClassTtls::default()
+            },
+            ..AnalyticsConfig::default()
+        });
+        let addr = test_addr();
+        let now = Instant::now();
+        let src = *b"TTL-TEST-SRC-ND1";
+        let dst = *b"TTL-TEST-DST-ND1";
+        for (seq, class) in [(1, TrafficClass::Api), (2, TrafficClass::HealthCheck)] {
+            let packet =
+                common::make_data_packet(src, dst, seq, 1, class, 64, *b"ttl-test-node---");
+            analytics.on_packet_received(addr, &packet, now);
+        }
+        let _ = analytics.export_topology_snapshot(now);
+
+        analytics.cleanup_expired(now + Duration::from_secs(3));
+        let snapshot = analytics.export_topology_snapshot(now + Duration::from_secs(3));
+        let classes: Vec<TrafficClass> = snapshot.edges.iter().map(|edge| edge.class).collect();
+        assert_eq!(classes, vec![TrafficClass::Api]);
+        assert_eq!(snapshot.removed_edges.len(), 1);
+        assert_eq!(snapshot.nodes.len(), 2, "nodes outlive the short edge TTL");
+    }
+
+    #[test]
+    fn community_detection_labels_nodes_and_reports_modularity() {
+        let mut analytics = AnalyticsManager::new(5, 100);
+        let now = Instant::now();
+        let addr = test_addr();
+        let desc = *b"community-node--";
+        let left: [NodeId; 3] = [
+            *b"NODE-COMMU-000L1",
+            *b"NODE-COMMU-000L2",
+            *b"NODE-COMMU-000L3",
+        ];
+        let right: [NodeId; 3] = [
+            *b"NODE-COMMU-000R1",
+            *b"NODE-COMMU-000R2",
+            *b"NODE-COMMU-000R3",
+        ];
+
+        let mut seq = 0;
+        let mut send = |analytics: &mut AnalyticsManager, src: NodeId, dst: NodeId, count| {
+            for _ in 0..count {
+                seq += 1;
+                let packet =
+                    common::make_data_packet(src, dst, seq, seq, TrafficClass::Api, 100, desc);
+                analytics.on_packet_received(addr, &packet, now);
+            }
+        };
+        for cluster in [left, right] {
+            send(&mut analytics, cluster[0], cluster[1], 50);
+            send(&mut analytics, cluster[1], cluster[2], 50);
+            send(&mut analytics, cluster[2], cluster[0], 50);
+        }
+        send(&mut analytics, left[0], right[0], 1);
+
+        let before = analytics.export_topology_snapshot(now);
+        assert!(before.modularity.is_none());
+        assert!(before.nodes.iter().all(|node| node.community_id.is_none()));
+
+        analytics.detect_communities(now);
+        let snapshot = analytics.export_topology_snapshot(now);
+        let community = |node_id: NodeId| {
+            snapshot
+                .nodes
+                .iter()
+                .find(|node| node.node_id == node_id)
+                .and_then(|node| node.community_id)
+                .expect("community should be assigned")
+        };
+        assert_eq!(community(left[0]), community(left[2]));
+        assert_eq!(community(right[0]), community(right[1]));
+        assert_ne!(community(left[1]), community(right[1]));
+        assert!(snapshot.modularity.expect("modularity should be reported") > 0.3);
+
+        // Unregistered nodes do not keep a stale community label around.
+        analytics.on_node_unregistered(&common::make_unregister_node_packet(left[1]), now);
+        assert!(!analytics.communities.contains_key(&left[1]));
+        assert_eq!(analytics.communities.len(), 5);
+    }
+
     #[test]
     fn request_topology_wire_roundtrip_includes_graph_state() {
         let mut analytics = AnalyticsManager::new(5, 100);
@@ -869,7 +2516,9 @@ mod tests {
 
         let packet =
             common::make_data_packet(node_id, dst_node_id, 1, 1, TrafficClass::Api, 1200, desc);
-        let ack = analytics.on_packet_received(addr, &packet, now);
+        let ack = analytics
+            .on_packet_received(addr, &packet, now)
+            .expect("packet should be tracked");
         assert_eq!(ack.original_seq, 1);
 
         let req_bytes =
@@ -0,0 +1,165 @@
+use common::analytics::AnomalyScores;
+use common::config::AnomalyConfig;
+use common::events::EdgeMetric;
+
+/// Metrics each edge keeps a baseline for, in `EdgeBaselines` order, with
+/// the smallest standard deviation a score divides by. Without a floor a
+/// perfectly steady edge would flag the first microsecond of drift.
+const METRICS: [(EdgeMetric, f64); 4] = [
+    (EdgeMetric::LatencyEwmaUs, 100.0),
+    (EdgeMetric::JitterEwmaUs, 100.0),
+    (EdgeMetric::PacketsPerSecond, 0.5),
+    (EdgeMetric::LossRate, 0.005),
+];
+
+/// The floor also never drops below this share of the mean.
+const RELATIVE_STD_FLOOR: f64 = 0.05;
+
+/// Exponentially weighted mean and variance of one metric.
+#[derive(Debug, Default, Clone, Copy)]
+struct Baseline {
+    mean: f64,
+    variance: f64,
+    samples: u32,
+}
+
+impl Baseline {
+    /// Scores `value` against the baseline so far (`None` during warmup),
+    /// then folds it in.
+    fn observe(&mut self, value: f64, config: &AnomalyConfig, std_floor: f64) -> Option<f64> {
+        let score = (self.samples >= config.warmup_samples).then(|| {
+            let std_dev = self
+                .variance
+                .sqrt()
+                .max(std_floor)
+                .max(self.mean.abs() * RELATIVE_STD_FLOOR);
+            (value - self.mean).abs() / std_dev
+        });
+        if self.samples == 0 {
+            self.mean = value;
+        } else {
+            let diff = value - self.mean;
+            let increment = config.baseline_alpha * diff;
+            self.mean += increment;
+            self.variance = (1.0 - config.baseline_alpha) * (self.variance + diff * increment);
+        }
+        self.samples = self.samples.saturating_add(1);
+        score
+    }
+}
+
+/// A metric that started or stopped being anomalous.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnomalyChange {
+    pub metric: EdgeMetric,
+    pub value: f64,
+    pub baseline_mean: f64,
+    pub score: f64,
+    pub anomalous: bool,
+}
+
+/// Rolling baselines for one edge plus its latest scores.
+#[derive(Debug, Default, Clone)]
+pub struct EdgeBaselines {
+    baselines: [Baseline; 4],
+    anomalous: [bool; 4],
+    scores: Option<AnomalyScores>,
+}
+
+impl EdgeBaselines {
+    /// Scores one sample of latency EWMA, jitter EWMA, packet rate and
+    /// loss rate, in that order, and learns from it. Returns the
+    /// metrics whose anomalous state flipped.
+    pub fn observe(&mut self, values: [f64; 4], config: &AnomalyConfig) -> Vec<AnomalyChange> {
+        let mut scores = [0.0; 4];
+        let mut warmed_up = true;
+        let mut changes = Vec::new();
+        for (index, (metric, std_floor)) in METRICS.into_iter().enumerate() {
+            let baseline_mean = self.baselines[index].mean;
+            let Some(score) = self.baselines[index].observe(values[index], config, std_floor)
+            else {
+                warmed_up = false;
+                continue;
+            };
+            scores[index] = score;
+            let anomalous = score >= config.score_threshold;
+            if std::mem::replace(&mut self.anomalous[index], anomalous) != anomalous {
+                changes.push(AnomalyChange {
+                    metric,
+                    value: values[index],
+                    baseline_mean,
+                    score,
+                    anomalous,
+                });
+            }
+        }
+        self.scores = warmed_up.then(|| AnomalyScores {
+            latency_ewma_us: scores[0],
+            jitter_ewma_us: scores[1],
+            packets_per_second: scores[2],
+            loss_rate: scores[3],
+            anomalous: self.anomalous.contains(&true),
+        });
+        changes
+    }
+
+    /// Scores from the latest sample, once every baseline has warmed up.
+    pub fn scores(&self) -> Option<AnomalyScores> {
+        self.scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AnomalyConfig {
+        AnomalyConfig {
+            score_threshold: 3.0,
+            baseline_alpha: 0.1,
+            warmup_samples: 10,
+        }
+    }
+
+    #[test]
+    fn steady_metrics_stay_quiet_and_a_spike_is_flagged_then_cleared() {
+        let config = config();
+        let mut edge = EdgeBaselines::default();
+        for sample in 0..40 {
+            // Latency wobbles +-1 ms around 20 ms; the rest is steady.
+            let latency = 20_000.0 + if sample % 2 == 0 { 1_000.0 } else { -1_000.0 };
+            let changes = edge.observe([latency, 500.0, 10.0, 0.0], &config);
+            assert!(changes.is_empty(), "sample {sample}: {changes:?}");
+            assert_eq!(edge.scores().is_some(), sample >= 10);
+        }
+        assert!(!edge.scores().expect("warmed up").anomalous);
+
+        let changes = edge.observe([60_000.0, 500.0, 10.0, 0.0], &config);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].metric, EdgeMetric::LatencyEwmaUs);
+        assert!(changes[0].anomalous);
+        assert!((changes[0].baseline_mean - 20_000.0).abs() < 1_000.0);
+        let scores = edge.scores().expect("scores");
+        assert!(scores.anomalous && scores.latency_ewma_us >= 3.0);
+        assert_eq!(scores.packets_per_second, 0.0);
+
+        let changes = edge.observe([20_000.0, 500.0, 10.0, 0.0], &config);
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].anomalous);
+        assert!(!edge.scores().expect("scores").anomalous);
+    }
+
+    #[test]
+    fn std_floor_keeps_tiny_drift_on_a_constant_metric_quiet() {
+        let config = config();
+        let mut edge = EdgeBaselines::default();
+        for _ in 0..20 {
+            edge.observe([1_000.0, 0.0, 5.0, 0.0], &config);
+        }
+        assert!(edge.observe([1_010.0, 1.0, 5.1, 0.001], &config).is_empty());
+        // A real rate drop still stands out.
+        let changes = edge.observe([1_000.0, 0.0, 0.0, 0.0], &config);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].metric, EdgeMetric::PacketsPerSecond);
+    }
+}
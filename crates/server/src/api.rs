@@ -0,0 +1,154 @@
+use crate::analytics::AnalyticsManager;
+use crate::http::{HttpRequest, HttpResponse};
+use common::analytics::TopologyQuery;
+use common::{NodeDomain, TrafficClass};
+use serde::Serialize;
+use std::time::Instant;
+
+const JSON: &str = "application/json";
+
+/// Read-only JSON API over `AnalyticsManager`.
+///
+/// Routes:
+/// - `GET /topology` with optional `class`, `domain`, `node`, `hops`,
+///   `active_only`, `min_pps`, `min_loss`, `group_depth` and
+///   `after_change_seq` query parameters (list parameters take
+///   comma-separated values or repeat)
+/// - `GET /nodes/{uuid}`
+/// - `GET /edges/{uuid}`
+/// - `GET /stats`
+/// - `GET /health`
+///
+/// Responses come from `peek_topology_snapshot`, so polling the API never
+/// disturbs deltas or removals seen by topology subscribers. Returns `None`
+/// for paths outside the API.
+pub fn handle(
+    analytics: &AnalyticsManager,
+    request: &HttpRequest,
+    now: Instant,
+) -> Option<HttpResponse> {
+    let path = request.path.trim_end_matches('/');
+    let response = match path {
+        "/topology" => match parse_query(request.query.as_deref().unwrap_or("")) {
+            Ok(query) => json(&analytics.peek_filtered_topology_snapshot(now, &query)),
+            Err(message) => HttpResponse::error(400, &message),
+        },
+        "/stats" => json(&analytics.global_stats()),
+        "/health" => json(&Health {
+            status: "ok",
+            uptime_secs: analytics.uptime().as_secs(),
+            nodes: analytics.node_count(),
+            edges: analytics.edge_count(),
+        }),
+        _ => {
+            if let Some(id) = path.strip_prefix("/nodes/") {
+                let Some(node_id) = parse_id(id) else {
+                    return Some(HttpResponse::error(400, "invalid node id"));
+                };
+                let snapshot = analytics.peek_topology_snapshot(now);
+                match snapshot.nodes.iter().find(|node| node.node_id == node_id) {
+                    Some(node) => json(node),
+                    None => HttpResponse::not_found(),
+                }
+            } else if let Some(id) = path.strip_prefix("/edges/") {
+                let Some(edge_id) = parse_id(id) else {
+                    return Some(HttpResponse::error(400, "invalid edge id"));
+                };
+                let snapshot = analytics.peek_topology_snapshot(now);
+                match snapshot.edges.iter().find(|edge| edge.edge_id == edge_id) {
+                    Some(edge) => json(edge),
+                    None => HttpResponse::not_found(),
+                }
+            } else {
+                return None;
+            }
+        }
+    };
+    Some(response)
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+    uptime_secs: u64,
+    nodes: usize,
+    edges: usize,
+}
+
+fn json(value: &impl Serialize) -> HttpResponse {
+    match serde_json::to_vec(value) {
+        Ok(body) => HttpResponse::ok(JSON, body),
+        Err(err) => HttpResponse::error(500, &err.to_string()),
+    }
+}
+
+/// Parses a UUID in hyphenated or plain hex form into raw id bytes.
+fn parse_id(text: &str) -> Option<[u8; 16]> {
+    uuid::Uuid::parse_str(text).ok().map(uuid::Uuid::into_bytes)
+}
+
+fn parse_query(query: &str) -> Result<TopologyQuery, String> {
+    let mut parsed = TopologyQuery::default();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let invalid = || format!("invalid value for {key}: {value}");
+        let values = value.split(',').filter(|value| !value.is_empty());
+        match key {
+            "class" => {
+                for value in values {
+                    parsed.classes.push(parse_class(value).ok_or_else(invalid)?);
+                }
+            }
+            "domain" => {
+                for value in values {
+                    parsed
+                        .domains
+                        .push(parse_domain(value).ok_or_else(invalid)?);
+                }
+            }
+            "node" => {
+                for value in values {
+                    parsed.node_ids.push(parse_id(value).ok_or_else(invalid)?);
+                }
+            }
+            "hops" => parsed.hops = value.parse().map_err(|_| invalid())?,
+            "active_only" => parsed.active_only = parse_bool(value).ok_or_else(invalid)?,
+            "min_pps" => {
+                parsed.min_packets_per_second = Some(value.parse().map_err(|_| invalid())?)
+            }
+            "min_loss" => parsed.min_loss_rate = Some(value.parse().map_err(|_| invalid())?),
+            "group_depth" => parsed.group_depth = Some(value.parse().map_err(|_| invalid())?),
+            "after_change_seq" => {
+                parsed.after_change_seq = Some(value.parse().map_err(|_| invalid())?)
+            }
+            _ => return Err(format!("unknown parameter: {key}")),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_class(value: &str) -> Option<TrafficClass> {
+    match value.to_ascii_lowercase().replace('_', "").as_str() {
+        "api" => Some(TrafficClass::Api),
+        "heavycompute" => Some(TrafficClass::HeavyCompute),
+        "background" => Some(TrafficClass::Background),
+        "healthcheck" => Some(TrafficClass::HealthCheck),
+        _ => None,
+    }
+}
+
+fn parse_domain(value: &str) -> Option<NodeDomain> {
+    match value.to_ascii_lowercase().as_str() {
+        "internal" => Some(NodeDomain::Internal),
+        "external" => Some(NodeDomain::External),
+        _ => None,
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "" | "1" | "true" => Some(true),
+        "0" | "false" => Some(false),
+        _ => None,
+    }
+}
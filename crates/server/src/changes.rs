@@ -0,0 +1,76 @@
+use common::analytics::{LivenessEvent, TopologySnapshot};
+use common::{EdgeId, NodeId, TrafficClass};
+use std::collections::VecDeque;
+
+/// Changes kept for snapshot consumers; a cursor older than the buffer
+/// misses the rest and has to fall back to the tombstones.
+const MAX_BUFFERED_CHANGES: usize = 4096;
+
+/// A removal or liveness change, as carried by topology snapshots.
+/// Removals keep the grouping lineage (the node followed by its ancestors,
+/// nearest first) as of removal time, so grouped snapshots can still tell
+/// which group a departed node belonged to.
+#[derive(Debug, Clone)]
+pub enum Change {
+    NodeRemoved {
+        lineage: Vec<NodeId>,
+    },
+    EdgeRemoved {
+        edge_id: EdgeId,
+        class: TrafficClass,
+        src_lineage: Vec<NodeId>,
+        dst_lineage: Vec<NodeId>,
+    },
+    Liveness(LivenessEvent),
+}
+
+/// Sequenced removals and liveness changes. Reading never consumes them, so
+/// any number of snapshot consumers can each follow their own cursor.
+#[derive(Default)]
+pub struct ChangeLog {
+    buffer: VecDeque<(u64, Change)>,
+    last_seq: u64,
+}
+
+impl ChangeLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, change: Change) {
+        self.last_seq += 1;
+        if self.buffer.len() == MAX_BUFFERED_CHANGES {
+            self.buffer.pop_front();
+        }
+        self.buffer.push_back((self.last_seq, change));
+    }
+
+    /// Sequence number of the newest change, 0 before the first.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Buffered changes after `after_seq`, oldest first.
+    pub fn since(&self, after_seq: u64) -> impl Iterator<Item = &Change> {
+        self.buffer
+            .iter()
+            .skip_while(move |(seq, _)| *seq <= after_seq)
+            .map(|(_, change)| change)
+    }
+
+    /// Fills the removed ids and liveness events of `snapshot` with the
+    /// changes after `after_seq` and sets its `change_seq`.
+    pub fn fill(&self, snapshot: &mut TopologySnapshot, after_seq: u64) {
+        snapshot.removed_nodes.clear();
+        snapshot.removed_edges.clear();
+        snapshot.liveness_events.clear();
+        for change in self.since(after_seq) {
+            match change {
+                Change::NodeRemoved { lineage } => snapshot.removed_nodes.push(lineage[0]),
+                Change::EdgeRemoved { edge_id, .. } => snapshot.removed_edges.push(*edge_id),
+                Change::Liveness(event) => snapshot.liveness_events.push(*event),
+            }
+        }
+        snapshot.change_seq = self.last_seq;
+    }
+}
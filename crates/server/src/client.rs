@@ -187,12 +187,12 @@ impl RateCalculator {
             }
         }
 
-        if let Some(last) = self.buckets.back_mut() {
-            if now.duration_since(last.timestamp) < Duration::from_secs(1) {
-                last.packets += 1;
-                last.bytes += bytes as u64;
-                return;
-            }
+        if let Some(last) = self.buckets.back_mut()
+            && now.duration_since(last.timestamp) < Duration::from_secs(1)
+        {
+            last.packets += 1;
+            last.bytes += bytes as u64;
+            return;
         }
 
         self.buckets.push_back(RateBucket {
@@ -202,6 +202,11 @@ impl RateCalculator {
         });
     }
 
+    /// Buckets outside a shorter window are ignored and pruned later.
+    pub fn set_window(&mut self, window_secs: u32) {
+        self.window_duration = Duration::from_secs(window_secs as u64);
+    }
+
     pub fn calculate_rate(&self, now: Instant) -> (f64, f64) {
         let mut packets = 0;
         let mut bytes = 0;
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+
+/// Safety net against oscillating moves caused by floating point ties.
+const MAX_PASSES: usize = 100;
+/// Same for aggregation levels; every level merges at least one
+/// community and real graphs settle within a handful.
+const MAX_LEVELS: usize = 64;
+
+/// Result of a community detection run over nodes `0..n`.
+pub struct Partition {
+    /// Community per node, numbered by first appearance in node order.
+    pub community: Vec<u32>,
+    /// Newman modularity of the partition on the input graph.
+    pub modularity: f64,
+}
+
+/// Louvain community detection on an undirected weighted graph.
+///
+/// `edges` holds `(a, b, weight)` triples over node indices; parallel edges
+/// are summed and self-loops ignored. Node order drives move order, so
+/// callers should pass nodes in a stable order to get stable communities.
+pub fn louvain(n: usize, edges: &[(usize, usize, f64)]) -> Partition {
+    let edges: Vec<(usize, usize, f64)> = edges
+        .iter()
+        .copied()
+        .filter(|&(a, b, weight)| a != b && a < n && b < n && weight > 0.0)
+        .collect();
+
+    let mut membership: Vec<usize> = (0..n).collect();
+    let mut level_size = n;
+    let mut level_edges = edges.clone();
+
+    for _ in 0..MAX_LEVELS {
+        let (level_community, moved) = local_moving(level_size, &level_edges);
+        if !moved {
+            break;
+        }
+        let (relabeled, count) = relabel(&level_community);
+        if count == level_size {
+            break;
+        }
+        for community in &mut membership {
+            *community = relabeled[*community];
+        }
+        level_edges = aggregate(&level_edges, &relabeled);
+        level_size = count;
+    }
+
+    let (community, _) = relabel(&membership);
+    let modularity = modularity(n, &edges, &community);
+    Partition {
+        community: community.into_iter().map(|c| c as u32).collect(),
+        modularity,
+    }
+}
+
+/// Phase one: greedily move single nodes to the neighboring community with
+/// the best modularity gain until no move helps.
+fn local_moving(n: usize, edges: &[(usize, usize, f64)]) -> (Vec<usize>, bool) {
+    let mut adjacency: Vec<Vec<(usize, f64)>> = vec![Vec::new(); n];
+    let mut degree = vec![0.0; n];
+    for &(a, b, weight) in edges {
+        if a == b {
+            degree[a] += 2.0 * weight;
+        } else {
+            adjacency[a].push((b, weight));
+            adjacency[b].push((a, weight));
+            degree[a] += weight;
+            degree[b] += weight;
+        }
+    }
+    let total: f64 = degree.iter().sum();
+    let mut community: Vec<usize> = (0..n).collect();
+    if total == 0.0 {
+        return (community, false);
+    }
+
+    let mut community_degree = degree.clone();
+    let mut moved_any = false;
+    for _ in 0..MAX_PASSES {
+        let mut moved = false;
+        for node in 0..n {
+            let current = community[node];
+            community_degree[current] -= degree[node];
+
+            let mut links: HashMap<usize, f64> = HashMap::new();
+            for &(neighbor, weight) in &adjacency[node] {
+                *links.entry(community[neighbor]).or_default() += weight;
+            }
+
+            let gain = |target: usize, weight_to: f64| {
+                weight_to - community_degree[target] * degree[node] / total
+            };
+            let mut best = current;
+            let mut best_gain = gain(current, links.get(&current).copied().unwrap_or(0.0));
+            let mut candidates: Vec<(usize, f64)> = links.into_iter().collect();
+            candidates.sort_unstable_by_key(|(target, _)| *target);
+            for (target, weight_to) in candidates {
+                let candidate_gain = gain(target, weight_to);
+                if candidate_gain > best_gain {
+                    best = target;
+                    best_gain = candidate_gain;
+                }
+            }
+
+            community[node] = best;
+            community_degree[best] += degree[node];
+            if best != current {
+                moved = true;
+                moved_any = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+    (community, moved_any)
+}
+
+/// Renumbers communities densely in order of first appearance.
+fn relabel(community: &[usize]) -> (Vec<usize>, usize) {
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    let relabeled = community
+        .iter()
+        .map(|c| {
+            let next = ids.len();
+            *ids.entry(*c).or_insert(next)
+        })
+        .collect();
+    (relabeled, ids.len())
+}
+
+/// Phase two: collapse each community into one node. Internal weight
+/// becomes a self-loop so degrees are preserved.
+fn aggregate(edges: &[(usize, usize, f64)], community: &[usize]) -> Vec<(usize, usize, f64)> {
+    let mut merged: HashMap<(usize, usize), f64> = HashMap::new();
+    for &(a, b, weight) in edges {
+        let (ca, cb) = (community[a], community[b]);
+        let key = if ca <= cb { (ca, cb) } else { (cb, ca) };
+        *merged.entry(key).or_default() += weight;
+    }
+    let mut aggregated: Vec<(usize, usize, f64)> =
+        merged.into_iter().map(|((a, b), w)| (a, b, w)).collect();
+    aggregated.sort_unstable_by_key(|&(a, b, _)| (a, b));
+    aggregated
+}
+
+fn modularity(n: usize, edges: &[(usize, usize, f64)], community: &[usize]) -> f64 {
+    let mut degree_sum: HashMap<usize, f64> = HashMap::new();
+    let mut internal: HashMap<usize, f64> = HashMap::new();
+    let mut total = 0.0;
+    for &(a, b, weight) in edges {
+        total += 2.0 * weight;
+        *degree_sum.entry(community[a]).or_default() += weight;
+        *degree_sum.entry(community[b]).or_default() += weight;
+        if community[a] == community[b] {
+            *internal.entry(community[a]).or_default() += 2.0 * weight;
+        }
+    }
+    if total == 0.0 || n == 0 {
+        return 0.0;
+    }
+    degree_sum
+        .iter()
+        .map(|(c, degree)| {
+            internal.get(c).copied().unwrap_or(0.0) / total - (degree / total).powi(2)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::louvain;
+
+    #[test]
+    fn louvain_separates_two_cliques_joined_by_a_bridge() {
+        let mut edges = Vec::new();
+        for clique in [[0, 1, 2, 3], [4, 5, 6, 7]] {
+            for i in 0..clique.len() {
+                for j in (i + 1)..clique.len() {
+                    edges.push((clique[i], clique[j], 10.0));
+                }
+            }
+        }
+        edges.push((3, 4, 1.0));
+
+        let partition = louvain(8, &edges);
+        let c = &partition.community;
+        assert!(c[0..4].iter().all(|id| *id == c[0]));
+        assert!(c[4..8].iter().all(|id| *id == c[4]));
+        assert_ne!(c[0], c[4]);
+        assert!(partition.modularity > 0.4);
+    }
+}
@@ -0,0 +1,421 @@
+use crate::logging::LogFilter;
+use common::alerts::AlertRule;
+use common::config::ServerConfig;
+use std::fmt::{self, Display};
+use std::path::Path;
+
+/// A config problem, tagged with the dotted key it concerns (empty when
+/// the whole file is at fault, e.g. a TOML syntax error).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigError {
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.key.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.key, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Parses a `key=value` CLI override. Values are read as TOML (numbers,
+/// booleans, quoted strings) and fall back to a bare string, so
+/// `server.ws_addr=127.0.0.1:8081` works without quoting.
+pub fn parse_override(spec: &str) -> Result<(String, toml::Value), ConfigError> {
+    let Some((key, raw)) = spec.split_once('=') else {
+        return Err(ConfigError::new(spec, "expected key=value"));
+    };
+    let key = key.trim();
+    let raw = raw.trim();
+    let value = toml::from_str::<toml::Table>(&format!("value = {raw}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()));
+    Ok((key.to_string(), value))
+}
+
+/// Reads `path` (if any), applies `overrides` in order on top of it and
+/// deserializes the result. Does not validate; see [`validate`].
+pub fn load(
+    path: Option<&Path>,
+    overrides: &[(String, toml::Value)],
+) -> Result<ServerConfig, ConfigError> {
+    let mut table = match path {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|err| ConfigError::new("", format!("{}: {}", path.display(), err)))?;
+            text.parse::<toml::Table>()
+                .map_err(|err| ConfigError::new("", format!("{}: {}", path.display(), err)))?
+        }
+        None => toml::Table::new(),
+    };
+    for (key, value) in overrides {
+        set_path(&mut table, key, value.clone())?;
+    }
+
+    serde_path_to_error::deserialize(toml::Value::Table(table)).map_err(|err| {
+        let key = err.path().to_string();
+        let key = if key == "." { String::new() } else { key };
+        ConfigError::new(key, err.into_inner().message())
+    })
+}
+
+fn set_path(table: &mut toml::Table, key: &str, value: toml::Value) -> Result<(), ConfigError> {
+    let mut parts = key.split('.').peekable();
+    let mut current = table;
+    while let Some(part) = parts.next() {
+        if part.is_empty() {
+            return Err(ConfigError::new(key, "empty key segment"));
+        }
+        if parts.peek().is_none() {
+            current.insert(part.to_string(), value);
+            return Ok(());
+        }
+        let entry = current
+            .entry(part.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+        current = entry
+            .as_table_mut()
+            .ok_or_else(|| ConfigError::new(key, format!("`{part}` is not a table")))?;
+    }
+    Ok(())
+}
+
+/// Checks value ranges and addresses, then fills in derived defaults
+/// (`analytics.activity_ttl_secs`) so the config reads as resolved.
+pub fn validate(config: &mut ServerConfig) -> Result<(), ConfigError> {
+    check_addr("server.bind_addr", &config.server.bind_addr)?;
+    if let Some(addr) = &config.server.ws_addr {
+        check_addr("server.ws_addr", addr)?;
+    }
+    if let Some(addr) = &config.server.http_addr {
+        check_addr("server.http_addr", addr)?;
+    }
+    config
+        .server
+        .ack_mode
+        .validate()
+        .map_err(|err| ConfigError::new("server.ack_mode", err))?;
+
+    let analytics = &config.analytics;
+    check_positive("analytics.window_secs", analytics.window_secs as u64)?;
+    check_positive("analytics.max_nodes", analytics.max_nodes as u64)?;
+    check_positive(
+        "analytics.cleanup_interval_ms",
+        analytics.cleanup_interval_ms,
+    )?;
+    check_positive("analytics.node_ttl_secs", analytics.node_ttl_secs)?;
+    check_positive("analytics.edge_ttl_secs", analytics.edge_ttl_secs)?;
+    if let Some(ttl) = analytics.activity_ttl_secs {
+        check_positive("analytics.activity_ttl_secs", ttl)?;
+    }
+    let class_ttls = analytics.edge_ttl_secs_by_class;
+    for (name, ttl) in [
+        ("api", class_ttls.api),
+        ("heavy_compute", class_ttls.heavy_compute),
+        ("background", class_ttls.background),
+        ("health_check", class_ttls.health_check),
+    ] {
+        if let Some(ttl) = ttl {
+            check_positive(&format!("analytics.edge_ttl_secs_by_class.{name}"), ttl)?;
+        }
+    }
+    check_positive("analytics.suspect_after_ms", analytics.suspect_after_ms)?;
+    if analytics.down_after_ms <= analytics.suspect_after_ms {
+        return Err(ConfigError::new(
+            "analytics.down_after_ms",
+            format!(
+                "must be greater than analytics.suspect_after_ms ({})",
+                analytics.suspect_after_ms
+            ),
+        ));
+    }
+    check_alpha("analytics.latency_ewma_alpha", analytics.latency_ewma_alpha)?;
+    check_alpha("analytics.jitter_ewma_alpha", analytics.jitter_ewma_alpha)?;
+    let thresholds = analytics.event_thresholds;
+    if !(thresholds.latency_ewma_us >= 0.0 && thresholds.latency_ewma_us.is_finite()) {
+        return Err(ConfigError::new(
+            "analytics.event_thresholds.latency_ewma_us",
+            format!(
+                "must be a finite value >= 0, got {}",
+                thresholds.latency_ewma_us
+            ),
+        ));
+    }
+    if !(0.0..=1.0).contains(&thresholds.loss_rate) {
+        return Err(ConfigError::new(
+            "analytics.event_thresholds.loss_rate",
+            format!("must be in [0, 1], got {}", thresholds.loss_rate),
+        ));
+    }
+
+    let anomaly = analytics.anomaly;
+    if !(anomaly.score_threshold >= 0.0 && anomaly.score_threshold.is_finite()) {
+        return Err(ConfigError::new(
+            "analytics.anomaly.score_threshold",
+            format!(
+                "must be a finite value >= 0, got {}",
+                anomaly.score_threshold
+            ),
+        ));
+    }
+    check_alpha("analytics.anomaly.baseline_alpha", anomaly.baseline_alpha)?;
+    check_alert_rules(&analytics.alert_rules)?;
+
+    LogFilter::parse(&config.logging.filter)
+        .map_err(|err| ConfigError::new("logging.filter", err))?;
+
+    config.analytics.activity_ttl_secs = Some(config.analytics.activity_ttl_secs());
+    Ok(())
+}
+
+fn check_addr(key: &str, addr: &str) -> Result<(), ConfigError> {
+    match addr.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => Err(ConfigError::new(
+            key,
+            format!("expected host:port, got `{addr}`"),
+        )),
+    }
+}
+
+fn check_positive(key: &str, value: u64) -> Result<(), ConfigError> {
+    if value == 0 {
+        return Err(ConfigError::new(key, "must be greater than 0"));
+    }
+    Ok(())
+}
+
+fn check_alert_rules(rules: &[AlertRule]) -> Result<(), ConfigError> {
+    for (index, rule) in rules.iter().enumerate() {
+        let key = format!("analytics.alert_rules[{index}]");
+        let invalid =
+            |field: &str, message: String| Err(ConfigError::new(format!("{key}.{field}"), message));
+        if rule.name.is_empty() {
+            return invalid("name", "must not be empty".to_string());
+        }
+        if rules[..index].iter().any(|other| other.name == rule.name) {
+            return invalid("name", format!("duplicate rule name `{}`", rule.name));
+        }
+        if !rule.threshold.is_finite() {
+            return invalid(
+                "threshold",
+                format!("must be finite, got {}", rule.threshold),
+            );
+        }
+        let clear = rule.clear_threshold();
+        if !clear.is_finite() || rule.op.breached(clear, rule.threshold) {
+            return invalid(
+                "clear_threshold",
+                format!(
+                    "must be finite and on the clearing side of threshold {}, got {clear}",
+                    rule.threshold
+                ),
+            );
+        }
+        let scope = &rule.scope;
+        if rule.metric.is_node_metric()
+            && (scope.class.is_some()
+                || scope.dst_domain.is_some()
+                || scope.dst_group.is_some()
+                || scope.either_direction)
+        {
+            return invalid(
+                "scope",
+                format!(
+                    "{:?} is a node metric; only src_domain and src_group apply",
+                    rule.metric
+                ),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_alpha(key: &str, value: f64) -> Result<(), ConfigError> {
+    if !(value > 0.0 && value <= 1.0) {
+        return Err(ConfigError::new(
+            key,
+            format!("must be in (0, 1], got {value}"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::TrafficClass;
+
+    fn write_config(name: &str, text: &str) -> std::path::PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("simd-config-{}-{name}.toml", std::process::id()));
+        std::fs::write(&path, text).expect("write config");
+        path
+    }
+
+    #[test]
+    fn file_values_and_overrides_layer_over_defaults() {
+        let path = write_config(
+            "layering",
+            r#"
+            [server]
+            http_addr = "127.0.0.1:8082"
+
+            [analytics]
+            window_secs = 10
+            edge_ttl_secs = 45
+
+            [analytics.edge_ttl_secs_by_class]
+            health_check = 5
+            "#,
+        );
+        let overrides = vec![
+            parse_override("analytics.window_secs=2").expect("override"),
+            parse_override("server.ws_addr=127.0.0.1:8081").expect("override"),
+        ];
+        let mut config = load(Some(&path), &overrides).expect("load");
+        validate(&mut config).expect("valid");
+        std::fs::remove_file(path).ok();
+
+        assert_eq!(config.analytics.window_secs, 2);
+        assert_eq!(config.analytics.max_nodes, 1000);
+        assert_eq!(config.analytics.activity_ttl_secs, Some(6));
+        assert_eq!(config.analytics.edge_ttl_secs_for(TrafficClass::Api), 45);
+        assert_eq!(
+            config
+                .analytics
+                .edge_ttl_secs_for(TrafficClass::HealthCheck),
+            5
+        );
+        assert_eq!(config.server.ws_addr.as_deref(), Some("127.0.0.1:8081"));
+        assert_eq!(config.server.http_addr.as_deref(), Some("127.0.0.1:8082"));
+    }
+
+    #[test]
+    fn alert_rules_load_from_tables_and_are_checked() {
+        let rules = r#"
+            [[analytics.alert_rules]]
+            name = "api-loss"
+            metric = "LossRateWindow"
+            threshold = 0.02
+            for_secs = 10
+            scope = { class = "Api" }
+
+            [[analytics.alert_rules]]
+            name = "slow-zone-link"
+            metric = "LatencyEwmaUs"
+            threshold = 50000.0
+            clear_threshold = 40000.0
+            scope = { src_group = "00000000-0000-0000-0000-00000000000a", dst_group = "00000000-0000-0000-0000-00000000000b", either_direction = true }
+            "#;
+        let path = write_config("alerts", rules);
+        let mut config = load(Some(&path), &[]).expect("load");
+        validate(&mut config).expect("valid");
+        std::fs::remove_file(path).ok();
+
+        let loaded = &config.analytics.alert_rules;
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            loaded[0].to_string(),
+            "LossRateWindow > 0.02 for 10s on class=Api"
+        );
+        assert_eq!(loaded[1].clear_threshold(), 40000.0);
+        let mut group_b = [0; 16];
+        group_b[15] = 0xb;
+        assert_eq!(loaded[1].scope.dst_group, Some(group_b));
+
+        let invalid = |extra: &str| {
+            let path = write_config("alerts-invalid", &format!("{rules}\n{extra}"));
+            let mut config = load(Some(&path), &[]).expect("load");
+            std::fs::remove_file(path).ok();
+            validate(&mut config).expect_err("should be invalid").key
+        };
+        assert_eq!(
+            invalid(
+                "[[analytics.alert_rules]]\nname = \"api-loss\"\nmetric = \"JitterEwmaUs\"\nthreshold = 1.0"
+            ),
+            "analytics.alert_rules[2].name"
+        );
+        assert_eq!(
+            invalid(
+                "[[analytics.alert_rules]]\nname = \"flap\"\nmetric = \"JitterEwmaUs\"\nthreshold = 1.0\nclear_threshold = 2.0"
+            ),
+            "analytics.alert_rules[2].clear_threshold"
+        );
+        assert_eq!(
+            invalid(
+                "[[analytics.alert_rules]]\nname = \"busy\"\nmetric = \"NodePacketsPerSecond\"\nthreshold = 1.0\nscope = { class = \"Api\" }"
+            ),
+            "analytics.alert_rules[2].scope"
+        );
+    }
+
+    #[test]
+    fn errors_name_the_offending_key() {
+        let load_err = |spec: &str| {
+            let overrides = vec![parse_override(spec).expect("override")];
+            load(None, &overrides).expect_err("should fail")
+        };
+        assert_eq!(
+            load_err("analytics.window_secs=\"five\"").key,
+            "analytics.window_secs"
+        );
+        assert_eq!(
+            load_err("analytics.edge_ttl_secs_by_class.api=-1").key,
+            "analytics.edge_ttl_secs_by_class.api"
+        );
+        let unknown = load_err("analytics.windw_secs=5");
+        assert_eq!(unknown.key, "analytics.windw_secs");
+        assert!(unknown.message.contains("unknown field"));
+
+        let validate_err = |spec: &str| {
+            let overrides = vec![parse_override(spec).expect("override")];
+            let mut config = load(None, &overrides).expect("load");
+            validate(&mut config).expect_err("should be invalid")
+        };
+        assert_eq!(
+            validate_err("analytics.latency_ewma_alpha=1.5").key,
+            "analytics.latency_ewma_alpha"
+        );
+        assert_eq!(
+            validate_err("analytics.edge_ttl_secs_by_class.background=0").key,
+            "analytics.edge_ttl_secs_by_class.background"
+        );
+        assert_eq!(
+            validate_err("analytics.down_after_ms=5000").key,
+            "analytics.down_after_ms"
+        );
+        assert_eq!(
+            validate_err("analytics.event_thresholds.loss_rate=1.5").key,
+            "analytics.event_thresholds.loss_rate"
+        );
+        assert_eq!(
+            validate_err("analytics.anomaly.baseline_alpha=0").key,
+            "analytics.anomaly.baseline_alpha"
+        );
+        assert_eq!(
+            validate_err("server.bind_addr=localhost").key,
+            "server.bind_addr"
+        );
+        assert_eq!(
+            validate_err("logging.filter=server=loud").key,
+            "logging.filter"
+        );
+    }
+}
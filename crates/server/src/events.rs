@@ -0,0 +1,73 @@
+use common::events::{EventBatch, TopologyEvent, TopologyEventKind};
+use std::collections::VecDeque;
+
+/// Events kept for `RequestEvents` and late subscribers; older ones are
+/// reported as `missed`.
+const MAX_BUFFERED_EVENTS: usize = 4096;
+
+type Listener = Box<dyn FnMut(&TopologyEvent) + Send>;
+
+/// Sequenced topology change stream: a bounded replay buffer plus Rust
+/// callbacks invoked as each event is recorded.
+pub struct EventLog {
+    buffer: VecDeque<TopologyEvent>,
+    last_seq: u64,
+    listeners: Vec<Listener>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            last_seq: 0,
+            listeners: Vec::new(),
+        }
+    }
+
+    pub fn emit(&mut self, kind: TopologyEventKind) {
+        self.last_seq += 1;
+        let event = TopologyEvent {
+            seq: self.last_seq,
+            at_epoch_us: common::now_timestamp_us(),
+            kind,
+        };
+        for listener in &mut self.listeners {
+            listener(&event);
+        }
+        if self.buffer.len() == MAX_BUFFERED_EVENTS {
+            self.buffer.pop_front();
+        }
+        self.buffer.push_back(event);
+    }
+
+    /// Sequence number of the newest event, 0 before the first.
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Up to `limit` buffered events after `after_seq`.
+    pub fn since(&self, after_seq: u64, limit: usize) -> EventBatch {
+        let oldest = self
+            .buffer
+            .front()
+            .map_or(self.last_seq + 1, |event| event.seq);
+        let missed = oldest.saturating_sub(after_seq.saturating_add(1));
+        let skip = after_seq
+            .saturating_sub(oldest - 1)
+            .min(self.buffer.len() as u64) as usize;
+        EventBatch {
+            events: self.buffer.iter().skip(skip).take(limit).cloned().collect(),
+            missed,
+        }
+    }
+
+    pub fn add_listener(&mut self, listener: impl FnMut(&TopologyEvent) + Send + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
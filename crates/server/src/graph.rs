@@ -0,0 +1,347 @@
+use common::analytics::{GraphMetrics, PathSnapshot};
+use common::{EdgeId, NodeId};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Hop cost used for edges without a latency sample yet.
+const MIN_EDGE_WEIGHT_US: f64 = 1.0;
+
+/// Directed, latency-weighted view of the traffic graph.
+///
+/// Parallel edges between the same pair (one per traffic class) collapse into
+/// the lowest-latency one, which is what a path query would route over.
+pub struct Graph {
+    ids: Vec<NodeId>,
+    index: HashMap<NodeId, usize>,
+    outgoing: Vec<Vec<Arc>>,
+    incoming: Vec<Vec<usize>>,
+}
+
+#[derive(Clone, Copy)]
+struct Arc {
+    to: usize,
+    weight: f64,
+    edge_id: EdgeId,
+}
+
+impl Graph {
+    /// Node ids are sorted so component ids are stable between calls.
+    pub fn new(node_ids: impl IntoIterator<Item = NodeId>) -> Self {
+        let mut ids: Vec<NodeId> = node_ids.into_iter().collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let index = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let len = ids.len();
+        Self {
+            ids,
+            index,
+            outgoing: vec![Vec::new(); len],
+            incoming: vec![Vec::new(); len],
+        }
+    }
+
+    /// Adds an edge; edges touching unknown nodes and self-loops are ignored.
+    pub fn add_edge(&mut self, src: NodeId, dst: NodeId, edge_id: EdgeId, latency_us: f64) {
+        let (Some(&from), Some(&to)) = (self.index.get(&src), self.index.get(&dst)) else {
+            return;
+        };
+        if from == to {
+            return;
+        }
+        let weight = latency_us.max(MIN_EDGE_WEIGHT_US);
+        match self.outgoing[from].iter_mut().find(|arc| arc.to == to) {
+            Some(existing) if existing.weight > weight => {
+                existing.weight = weight;
+                existing.edge_id = edge_id;
+            }
+            Some(_) => {}
+            None => {
+                self.outgoing[from].push(Arc {
+                    to,
+                    weight,
+                    edge_id,
+                });
+                self.incoming[to].push(from);
+            }
+        }
+    }
+
+    /// Weakly connected component, degree and betweenness for every node.
+    pub fn node_metrics(&self) -> HashMap<NodeId, GraphMetrics> {
+        let components = self.components();
+        let betweenness = self.betweenness();
+        let n = self.ids.len();
+        let degree_norm = if n > 1 { (n - 1) as f64 } else { 1.0 };
+
+        (0..n)
+            .map(|i| {
+                let mut neighbors: Vec<usize> = self.outgoing[i]
+                    .iter()
+                    .map(|arc| arc.to)
+                    .chain(self.incoming[i].iter().copied())
+                    .collect();
+                neighbors.sort_unstable();
+                neighbors.dedup();
+                let metrics = GraphMetrics {
+                    component_id: components[i],
+                    in_degree: self.incoming[i].len() as u32,
+                    out_degree: self.outgoing[i].len() as u32,
+                    degree_centrality: neighbors.len() as f64 / degree_norm,
+                    betweenness_centrality: betweenness[i],
+                };
+                (self.ids[i], metrics)
+            })
+            .collect()
+    }
+
+    /// Lowest total latency route from `src` to `dst` following edge direction.
+    pub fn shortest_path(&self, src: NodeId, dst: NodeId) -> PathSnapshot {
+        let mut path = PathSnapshot {
+            src_node_id: src,
+            dst_node_id: dst,
+            found: false,
+            node_ids: Vec::new(),
+            edge_ids: Vec::new(),
+            total_latency_us: 0.0,
+        };
+        let (Some(&from), Some(&to)) = (self.index.get(&src), self.index.get(&dst)) else {
+            return path;
+        };
+
+        let search = self.dijkstra(from);
+        if !search.dist[to].is_finite() {
+            return path;
+        }
+
+        let mut current = to;
+        path.node_ids.push(self.ids[to]);
+        while current != from {
+            let (prev, edge_id) = search.via[current].expect("reachable node has a predecessor");
+            path.node_ids.push(self.ids[prev]);
+            path.edge_ids.push(edge_id);
+            current = prev;
+        }
+        path.node_ids.reverse();
+        path.edge_ids.reverse();
+        path.found = true;
+        path.total_latency_us = search.dist[to];
+        path
+    }
+
+    fn components(&self) -> Vec<u32> {
+        let n = self.ids.len();
+        let mut component = vec![u32::MAX; n];
+        let mut next_id = 0;
+        for start in 0..n {
+            if component[start] != u32::MAX {
+                continue;
+            }
+            component[start] = next_id;
+            let mut stack = vec![start];
+            while let Some(i) = stack.pop() {
+                let neighbors = self.outgoing[i]
+                    .iter()
+                    .map(|arc| arc.to)
+                    .chain(self.incoming[i].iter().copied());
+                for j in neighbors {
+                    if component[j] == u32::MAX {
+                        component[j] = next_id;
+                        stack.push(j);
+                    }
+                }
+            }
+            next_id += 1;
+        }
+        component
+    }
+
+    /// Brandes' algorithm over latency-weighted shortest paths, normalized by
+    /// the number of ordered pairs excluding the node itself.
+    fn betweenness(&self) -> Vec<f64> {
+        let n = self.ids.len();
+        let mut centrality = vec![0.0; n];
+        if n < 3 {
+            return centrality;
+        }
+
+        for source in 0..n {
+            let search = self.dijkstra(source);
+            let mut dependency = vec![0.0; n];
+            for &w in search.settled.iter().rev() {
+                for &v in &search.preds[w] {
+                    dependency[v] += search.sigma[v] / search.sigma[w] * (1.0 + dependency[w]);
+                }
+                if w != source {
+                    centrality[w] += dependency[w];
+                }
+            }
+        }
+
+        let norm = ((n - 1) * (n - 2)) as f64;
+        for value in &mut centrality {
+            *value /= norm;
+        }
+        centrality
+    }
+
+    fn dijkstra(&self, source: usize) -> Search {
+        let n = self.ids.len();
+        let mut search = Search {
+            dist: vec![f64::INFINITY; n],
+            sigma: vec![0.0; n],
+            preds: vec![Vec::new(); n],
+            via: vec![None; n],
+            settled: Vec::with_capacity(n),
+        };
+        search.dist[source] = 0.0;
+        search.sigma[source] = 1.0;
+
+        let mut done = vec![false; n];
+        let mut heap = BinaryHeap::new();
+        heap.push(Candidate {
+            dist: 0.0,
+            node: source,
+        });
+        while let Some(Candidate { dist, node }) = heap.pop() {
+            if done[node] {
+                continue;
+            }
+            done[node] = true;
+            search.settled.push(node);
+            for arc in &self.outgoing[node] {
+                let candidate = dist + arc.weight;
+                let known = search.dist[arc.to];
+                if candidate < known {
+                    search.dist[arc.to] = candidate;
+                    search.sigma[arc.to] = search.sigma[node];
+                    search.preds[arc.to] = vec![node];
+                    search.via[arc.to] = Some((node, arc.edge_id));
+                    heap.push(Candidate {
+                        dist: candidate,
+                        node: arc.to,
+                    });
+                } else if candidate == known {
+                    search.sigma[arc.to] += search.sigma[node];
+                    search.preds[arc.to].push(node);
+                }
+            }
+        }
+        search
+    }
+}
+
+struct Search {
+    dist: Vec<f64>,
+    sigma: Vec<f64>,
+    preds: Vec<Vec<usize>>,
+    via: Vec<Option<(usize, EdgeId)>>,
+    /// Nodes in order of non-decreasing distance from the source.
+    settled: Vec<usize>,
+}
+
+struct Candidate {
+    dist: f64,
+    node: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    /// Reversed so `BinaryHeap` pops the closest node first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .dist
+            .total_cmp(&self.dist)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(tag: u8) -> NodeId {
+        [tag; 16]
+    }
+
+    fn graph(nodes: &[u8], edges: &[(u8, u8, f64)]) -> Graph {
+        let mut graph = Graph::new(nodes.iter().map(|tag| node(*tag)));
+        for (index, (src, dst, latency_us)) in edges.iter().enumerate() {
+            graph.add_edge(node(*src), node(*dst), [index as u8; 16], *latency_us);
+        }
+        graph
+    }
+
+    #[test]
+    fn unreachable_and_unknown_targets_have_no_path() {
+        let graph = graph(b"abc", &[(b'a', b'b', 10.0)]);
+        // Paths follow edge direction.
+        assert!(!graph.shortest_path(node(b'b'), node(b'a')).found);
+        assert!(!graph.shortest_path(node(b'a'), node(b'c')).found);
+        let unknown = graph.shortest_path(node(b'a'), node(b'z'));
+        assert!(!unknown.found && unknown.node_ids.is_empty());
+
+        let path = graph.shortest_path(node(b'a'), node(b'b'));
+        assert!(path.found);
+        assert_eq!(path.node_ids, [node(b'a'), node(b'b')]);
+        assert_eq!(path.total_latency_us, 10.0);
+    }
+
+    #[test]
+    fn equal_cost_paths_share_betweenness() {
+        // a -> b -> d and a -> c -> d cost the same; the slower parallel
+        // a -> b edge is ignored.
+        let graph = graph(
+            b"abcd",
+            &[
+                (b'a', b'b', 10.0),
+                (b'a', b'b', 50.0),
+                (b'b', b'd', 10.0),
+                (b'a', b'c', 10.0),
+                (b'c', b'd', 10.0),
+            ],
+        );
+        let path = graph.shortest_path(node(b'a'), node(b'd'));
+        assert!(path.found);
+        assert_eq!(path.total_latency_us, 20.0);
+        assert_eq!(path.node_ids.len(), 3);
+
+        let metrics = graph.node_metrics();
+        // b and c each carry half of the a -> d pair, out of 3 * 2 pairs.
+        for tag in [b'b', b'c'] {
+            let betweenness = metrics[&node(tag)].betweenness_centrality;
+            assert!((betweenness - 0.5 / 6.0).abs() < 1e-12, "{betweenness}");
+        }
+        assert_eq!(metrics[&node(b'a')].betweenness_centrality, 0.0);
+        assert_eq!(metrics[&node(b'a')].out_degree, 2);
+        assert_eq!(metrics[&node(b'd')].in_degree, 2);
+    }
+
+    #[test]
+    fn components_are_weakly_connected_and_stable() {
+        let graph = graph(
+            b"abcdef",
+            &[(b'a', b'b', 1.0), (b'c', b'b', 1.0), (b'd', b'e', 1.0)],
+        );
+        let metrics = graph.node_metrics();
+        let component = |tag| metrics[&node(tag)].component_id;
+        assert_eq!(
+            [b'a', b'b', b'c', b'd', b'e', b'f'].map(component),
+            [0, 0, 0, 1, 1, 2]
+        );
+        assert_eq!(metrics[&node(b'f')].degree_centrality, 0.0);
+        assert!(!graph.shortest_path(node(b'a'), node(b'e')).found);
+    }
+}
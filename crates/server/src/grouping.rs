@@ -0,0 +1,295 @@
+use crate::analytics::{EdgeKey, edge_id_from_key};
+use crate::changes::Change;
+use common::analytics::{
+    EdgeSnapshot, LatencyMetrics, LossMetrics, NodeLayout, NodeLiveness, NodeSnapshot,
+    TopologySnapshot,
+};
+use common::{EdgeId, NodeId};
+use std::collections::{HashMap, HashSet};
+
+const GROUP_DESC: [u8; 16] = *b"node-group------";
+
+/// Folds nodes into their group representative and edges into group
+/// super-edges.
+///
+/// `representatives` maps a node id to the group it collapses into; nodes
+/// without an entry stand for themselves. Traffic between members of the
+/// same group disappears inside the group node. Tombstones are passed
+/// through untouched; see `rollup_removals` for the removed ids.
+pub fn rollup_snapshot(
+    mut snapshot: TopologySnapshot,
+    representatives: &HashMap<NodeId, NodeId>,
+) -> TopologySnapshot {
+    if representatives.is_empty() {
+        return snapshot;
+    }
+    let group_of = |node_id: NodeId| representatives.get(&node_id).copied().unwrap_or(node_id);
+
+    let mut bases: HashMap<NodeId, NodeSnapshot> = HashMap::new();
+    let mut members: HashMap<NodeId, Vec<NodeSnapshot>> = HashMap::new();
+    for node in std::mem::take(&mut snapshot.nodes) {
+        let group = group_of(node.node_id);
+        if group == node.node_id {
+            bases.insert(group, node);
+        } else {
+            members.entry(group).or_default().push(node);
+        }
+    }
+
+    for (group, group_members) in members {
+        let base = bases.entry(group).or_insert_with(|| {
+            let first = &group_members[0];
+            empty_group_node(group, first)
+        });
+        for member in &group_members {
+            merge_node(base, member);
+        }
+    }
+    snapshot.nodes = bases.into_values().collect();
+
+    let mut super_edges: HashMap<EdgeKey, EdgeAccumulator> = HashMap::new();
+    for edge in std::mem::take(&mut snapshot.edges) {
+        let key = EdgeKey {
+            src_node_id: group_of(edge.src_node_id),
+            dst_node_id: group_of(edge.dst_node_id),
+            class: edge.class,
+        };
+        if key.src_node_id == key.dst_node_id && edge.src_node_id != edge.dst_node_id {
+            continue;
+        }
+        super_edges
+            .entry(key)
+            .or_insert_with(|| EdgeAccumulator::new(key))
+            .add(&edge);
+    }
+    snapshot.edges = super_edges
+        .into_values()
+        .map(EdgeAccumulator::finish)
+        .collect();
+
+    snapshot
+}
+
+/// Group a node collapses into at `max_depth`, from its lineage (the node
+/// followed by its ancestors, nearest first).
+pub fn representative(lineage: &[NodeId], max_depth: u8) -> NodeId {
+    let depth = lineage.len() - 1;
+    lineage[depth.saturating_sub(max_depth as usize)]
+}
+
+/// Replaces the removed ids of a snapshot rolled up to `max_depth` with
+/// ids that exist at that depth. A removed member is reported as its group
+/// and a removed member edge as its super-edge, but only once the group or
+/// super-edge has itself left `snapshot`; edges inside a group are dropped.
+pub fn rollup_removals<'a>(
+    snapshot: &mut TopologySnapshot,
+    removals: impl Iterator<Item = &'a Change>,
+    max_depth: u8,
+) {
+    let nodes: HashSet<NodeId> = snapshot.nodes.iter().map(|node| node.node_id).collect();
+    let edges: HashSet<EdgeId> = snapshot.edges.iter().map(|edge| edge.edge_id).collect();
+    snapshot.removed_nodes.clear();
+    snapshot.removed_edges.clear();
+    for removal in removals {
+        match removal {
+            Change::NodeRemoved { lineage } => {
+                let group = representative(lineage, max_depth);
+                if !nodes.contains(&group) && !snapshot.removed_nodes.contains(&group) {
+                    snapshot.removed_nodes.push(group);
+                }
+            }
+            Change::EdgeRemoved {
+                class,
+                src_lineage,
+                dst_lineage,
+                ..
+            } => {
+                let key = EdgeKey {
+                    src_node_id: representative(src_lineage, max_depth),
+                    dst_node_id: representative(dst_lineage, max_depth),
+                    class: *class,
+                };
+                if key.src_node_id == key.dst_node_id && src_lineage[0] != dst_lineage[0] {
+                    continue;
+                }
+                let edge_id = edge_id_from_key(key);
+                if !edges.contains(&edge_id) && !snapshot.removed_edges.contains(&edge_id) {
+                    snapshot.removed_edges.push(edge_id);
+                }
+            }
+            Change::Liveness(_) => {}
+        }
+    }
+}
+
+fn empty_group_node(group: NodeId, first_member: &NodeSnapshot) -> NodeSnapshot {
+    NodeSnapshot {
+        node_id: group,
+        desc: GROUP_DESC,
+        domain: first_member.domain,
+        first_seen_us: u64::MAX,
+        last_seen_us: 0,
+        active: false,
+        liveness: NodeLiveness::Departed,
+        total_packets: 0,
+        total_bytes: 0,
+        total_pps: 0.0,
+        total_bps: 0.0,
+        latency: LatencyMetrics::default(),
+        loss: LossMetrics::default(),
+        parent_node_id: None,
+        member_count: 0,
+        graph: None,
+        layout: None,
+        community_id: first_member.community_id,
+    }
+}
+
+fn merge_node(group: &mut NodeSnapshot, member: &NodeSnapshot) {
+    group.first_seen_us = group.first_seen_us.min(member.first_seen_us);
+    group.last_seen_us = group.last_seen_us.max(member.last_seen_us);
+    group.active |= member.active;
+    if liveness_rank(member.liveness) < liveness_rank(group.liveness) {
+        group.liveness = member.liveness;
+    }
+    group.total_packets += member.total_packets;
+    group.total_bytes += member.total_bytes;
+    group.total_pps += member.total_pps;
+    group.total_bps += member.total_bps;
+    group.latency = merge_latency(&group.latency, &member.latency);
+    group.loss.missing_sequences += member.loss.missing_sequences;
+    group.loss.out_of_order += member.loss.out_of_order;
+    group.loss.duplicates += member.loss.duplicates;
+    group.loss.total_gaps += member.loss.total_gaps;
+    group.layout = match (group.layout, member.layout) {
+        (Some(a), Some(b)) => Some(centroid(a, group.member_count, b, member.member_count)),
+        (a, b) => a.or(b),
+    };
+    if group.community_id != member.community_id {
+        group.community_id = None;
+    }
+    group.member_count += member.member_count;
+    group.graph = None;
+}
+
+/// A group is as alive as its liveliest member.
+fn liveness_rank(liveness: NodeLiveness) -> u8 {
+    match liveness {
+        NodeLiveness::Active => 0,
+        NodeLiveness::Idle => 1,
+        NodeLiveness::Registered => 2,
+        NodeLiveness::Suspect => 3,
+        NodeLiveness::Down => 4,
+        NodeLiveness::Departed => 5,
+    }
+}
+
+/// Count-weighted midpoint so a collapsed group sits among its members.
+fn centroid(a: NodeLayout, a_count: u32, b: NodeLayout, b_count: u32) -> NodeLayout {
+    let (wa, wb) = (a_count.max(1) as f64, b_count.max(1) as f64);
+    let mean = |x: f64, y: f64| (x * wa + y * wb) / (wa + wb);
+    NodeLayout {
+        position_2d: std::array::from_fn(|i| mean(a.position_2d[i], b.position_2d[i])),
+        position_3d: std::array::from_fn(|i| mean(a.position_3d[i], b.position_3d[i])),
+    }
+}
+
+fn merge_latency(a: &LatencyMetrics, b: &LatencyMetrics) -> LatencyMetrics {
+    if b.samples == 0 {
+        return *a;
+    }
+    if a.samples == 0 {
+        return *b;
+    }
+    let samples = a.samples + b.samples;
+    let weighted = |x: f64, y: f64| (x * a.samples as f64 + y * b.samples as f64) / samples as f64;
+    LatencyMetrics {
+        min_rtt_us: a.min_rtt_us.min(b.min_rtt_us),
+        max_rtt_us: a.max_rtt_us.max(b.max_rtt_us),
+        mean_rtt_us: weighted(a.mean_rtt_us, b.mean_rtt_us),
+        mean_jitter_us: weighted(a.mean_jitter_us, b.mean_jitter_us),
+        samples,
+    }
+}
+
+/// Latency and jitter are averaged weighted by packet count, window loss
+/// weighted by the window packet rate; anomaly scores keep the worst member.
+struct EdgeAccumulator {
+    edge: EdgeSnapshot,
+    latency_sum: f64,
+    latency_delta_sum: f64,
+    jitter_sum: f64,
+    loss_sum: f64,
+    reverse_loss_sum: f64,
+}
+
+impl EdgeAccumulator {
+    fn new(key: EdgeKey) -> Self {
+        Self {
+            edge: EdgeSnapshot {
+                edge_id: edge_id_from_key(key),
+                src_node_id: key.src_node_id,
+                dst_node_id: key.dst_node_id,
+                class: key.class,
+                first_seen_us: u64::MAX,
+                last_seen_us: 0,
+                packets: 0,
+                bytes: 0,
+                packets_per_second: 0.0,
+                bytes_per_second: 0.0,
+                delta_packets_per_second: 0.0,
+                delta_bytes_per_second: 0.0,
+                latency_ewma_us: 0.0,
+                latency_delta_us: 0.0,
+                jitter_ewma_us: 0.0,
+                loss_rate_window: 0.0,
+                reverse_loss_rate: 0.0,
+                active: false,
+                member_edges: 0,
+                anomaly: None,
+            },
+            latency_sum: 0.0,
+            latency_delta_sum: 0.0,
+            jitter_sum: 0.0,
+            loss_sum: 0.0,
+            reverse_loss_sum: 0.0,
+        }
+    }
+
+    fn add(&mut self, edge: &EdgeSnapshot) {
+        let packets = edge.packets as f64;
+        self.edge.first_seen_us = self.edge.first_seen_us.min(edge.first_seen_us);
+        self.edge.last_seen_us = self.edge.last_seen_us.max(edge.last_seen_us);
+        self.edge.packets += edge.packets;
+        self.edge.bytes += edge.bytes;
+        self.edge.packets_per_second += edge.packets_per_second;
+        self.edge.bytes_per_second += edge.bytes_per_second;
+        self.edge.delta_packets_per_second += edge.delta_packets_per_second;
+        self.edge.delta_bytes_per_second += edge.delta_bytes_per_second;
+        self.edge.active |= edge.active;
+        self.edge.member_edges += edge.member_edges;
+        self.edge.anomaly = match (self.edge.anomaly, edge.anomaly) {
+            (Some(a), Some(b)) => Some(a.merge(b)),
+            (a, b) => a.or(b),
+        };
+        self.latency_sum += edge.latency_ewma_us * packets;
+        self.latency_delta_sum += edge.latency_delta_us * packets;
+        self.jitter_sum += edge.jitter_ewma_us * packets;
+        self.loss_sum += edge.loss_rate_window * edge.packets_per_second;
+        self.reverse_loss_sum += edge.reverse_loss_rate * edge.packets_per_second;
+    }
+
+    fn finish(mut self) -> EdgeSnapshot {
+        if self.edge.packets > 0 {
+            let packets = self.edge.packets as f64;
+            self.edge.latency_ewma_us = self.latency_sum / packets;
+            self.edge.latency_delta_us = self.latency_delta_sum / packets;
+            self.edge.jitter_ewma_us = self.jitter_sum / packets;
+        }
+        if self.edge.packets_per_second > 0.0 {
+            self.edge.loss_rate_window = self.loss_sum / self.edge.packets_per_second;
+            self.edge.reverse_loss_rate = self.reverse_loss_sum / self.edge.packets_per_second;
+        }
+        self.edge
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::{ErrorKind, Read, Result, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Longest a client may stall the server loop while sending its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+const MAX_HEADERS: usize = 32;
+
+/// Minimal local HTTP/1.1 listener polled from the UDP loop.
+///
+/// One request per connection, answered with `Connection: close`. Only
+/// `GET` is supported and request bodies are ignored.
+pub struct HttpServer {
+    listener: TcpListener,
+}
+
+pub struct HttpRequest {
+    pub method: String,
+    /// Request target without the query string.
+    pub path: String,
+    pub query: Option<String>,
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn ok(content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::error(404, "not found")
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{message}\n").into_bytes(),
+        }
+    }
+}
+
+impl HttpServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answers every pending connection with `handle`.
+    pub fn poll(&mut self, mut handle: impl FnMut(&HttpRequest) -> HttpResponse) {
+        while let Ok((stream, peer)) = self.listener.accept() {
+            if let Err(err) = serve(stream, &mut handle) {
+                log::warn!("HTTP request from {} failed: {}", peer, err);
+            }
+        }
+    }
+}
+
+fn serve(
+    mut stream: TcpStream,
+    handle: &mut impl FnMut(&HttpRequest) -> HttpResponse,
+) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let response = match read_request(&mut stream)? {
+        Some(request) if request.method == "GET" => handle(&request),
+        Some(_) => HttpResponse::error(405, "method not allowed"),
+        None => HttpResponse::error(400, "bad request"),
+    };
+    write_response(&mut stream, &response)
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Option<HttpRequest>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        let amt = match stream.read(&mut chunk) {
+            Ok(0) => return Ok(None),
+            Ok(amt) => amt,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        buf.extend_from_slice(&chunk[..amt]);
+
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut request = httparse::Request::new(&mut headers);
+        match request.parse(&buf) {
+            Ok(httparse::Status::Complete(_)) => {
+                let (Some(method), Some(target)) = (request.method, request.path) else {
+                    return Ok(None);
+                };
+                let (path, query) = match target.split_once('?') {
+                    Some((path, query)) => (path, Some(query.to_string())),
+                    None => (target, None),
+                };
+                return Ok(Some(HttpRequest {
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    query,
+                }));
+            }
+            Ok(httparse::Status::Partial) if buf.len() < MAX_REQUEST_BYTES => {}
+            Ok(httparse::Status::Partial) | Err(_) => return Ok(None),
+        }
+    }
+}
+
+fn write_response(stream: &mut TcpStream, response: &HttpResponse) -> Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
@@ -0,0 +1,268 @@
+use common::NodeId;
+use common::analytics::NodeLayout;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Ideal distance between connected nodes, in layout units.
+const IDEAL_DISTANCE: f64 = 100.0;
+/// Pull toward the origin so disconnected components do not drift apart.
+const GRAVITY: f64 = 0.02;
+/// Largest step a node may take right after the graph changed.
+const INITIAL_TEMPERATURE: f64 = IDEAL_DISTANCE;
+/// Steps never shrink below this, so the layout keeps tracking traffic.
+const MIN_TEMPERATURE: f64 = 1.0;
+const COOLING: f64 = 0.95;
+const MIN_DISTANCE: f64 = 0.01;
+
+/// Traffic-weighted spring between two nodes. Weight 1.0 is an idle edge;
+/// busier edges pull harder.
+#[derive(Clone, Copy)]
+pub struct Spring {
+    pub a: NodeId,
+    pub b: NodeId,
+    pub weight: f64,
+}
+
+/// Reference force-directed layout kept alive between snapshots.
+///
+/// Uses Fruchterman-Reingold forces with a temperature that cools toward a
+/// floor instead of zero, so each relaxation moves nodes a little and
+/// positions stay stable from one snapshot to the next. New nodes are placed
+/// next to an already placed neighbor and reheat the layout.
+pub struct LayoutEngine {
+    planar: Layout<2>,
+    spatial: Layout<3>,
+}
+
+impl Default for LayoutEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LayoutEngine {
+    pub fn new() -> Self {
+        Self {
+            planar: Layout::new(),
+            spatial: Layout::new(),
+        }
+    }
+
+    /// Runs `iterations` relaxation steps over the current node set. Nodes
+    /// missing from `node_ids` are forgotten.
+    pub fn relax(&mut self, node_ids: &[NodeId], springs: &[Spring], iterations: usize) {
+        self.planar.relax(node_ids, springs, iterations);
+        self.spatial.relax(node_ids, springs, iterations);
+    }
+
+    pub fn position(&self, node_id: &NodeId) -> Option<NodeLayout> {
+        Some(NodeLayout {
+            position_2d: *self.planar.positions.get(node_id)?,
+            position_3d: *self.spatial.positions.get(node_id)?,
+        })
+    }
+}
+
+struct Layout<const D: usize> {
+    positions: HashMap<NodeId, [f64; D]>,
+    temperature: f64,
+}
+
+impl<const D: usize> Layout<D> {
+    fn new() -> Self {
+        Self {
+            positions: HashMap::new(),
+            temperature: INITIAL_TEMPERATURE,
+        }
+    }
+
+    fn relax(&mut self, node_ids: &[NodeId], springs: &[Spring], iterations: usize) {
+        let live: HashSet<&NodeId> = node_ids.iter().collect();
+        self.positions.retain(|node_id, _| live.contains(node_id));
+        self.place_new_nodes(node_ids, springs);
+
+        // Sorted so forces add up in the same order on every run.
+        let mut ids: Vec<NodeId> = self.positions.keys().copied().collect();
+        ids.sort_unstable();
+        let index: HashMap<NodeId, usize> =
+            ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut positions: Vec<[f64; D]> = ids.iter().map(|id| self.positions[id]).collect();
+        let springs: Vec<(usize, usize, f64)> = springs
+            .iter()
+            .filter_map(|spring| {
+                let a = *index.get(&spring.a)?;
+                let b = *index.get(&spring.b)?;
+                (a != b).then_some((a, b, spring.weight))
+            })
+            .collect();
+
+        for _ in 0..iterations {
+            let mut displacement = vec![[0.0; D]; positions.len()];
+
+            for i in 0..positions.len() {
+                for j in (i + 1)..positions.len() {
+                    let (delta, distance) = offset(&positions[i], &positions[j]);
+                    let force = IDEAL_DISTANCE * IDEAL_DISTANCE / distance;
+                    for axis in 0..D {
+                        let push = delta[axis] / distance * force;
+                        displacement[i][axis] += push;
+                        displacement[j][axis] -= push;
+                    }
+                }
+            }
+
+            for &(a, b, weight) in &springs {
+                let (delta, distance) = offset(&positions[a], &positions[b]);
+                let force = weight * distance * distance / IDEAL_DISTANCE;
+                for axis in 0..D {
+                    let pull = delta[axis] / distance * force;
+                    displacement[a][axis] -= pull;
+                    displacement[b][axis] += pull;
+                }
+            }
+
+            for (position, moved) in positions.iter_mut().zip(displacement.iter_mut()) {
+                for axis in 0..D {
+                    moved[axis] -= position[axis] * GRAVITY;
+                }
+                let length = moved.iter().map(|v| v * v).sum::<f64>().sqrt();
+                if length > 0.0 {
+                    let step = length.min(self.temperature);
+                    for axis in 0..D {
+                        position[axis] += moved[axis] / length * step;
+                    }
+                }
+            }
+
+            self.temperature = (self.temperature * COOLING).max(MIN_TEMPERATURE);
+        }
+
+        for (id, position) in ids.into_iter().zip(positions) {
+            self.positions.insert(id, position);
+        }
+    }
+
+    fn place_new_nodes(&mut self, node_ids: &[NodeId], springs: &[Spring]) {
+        let mut added = false;
+        for node_id in node_ids {
+            if self.positions.contains_key(node_id) {
+                continue;
+            }
+            let anchor = springs
+                .iter()
+                .filter_map(|spring| {
+                    let other = if spring.a == *node_id {
+                        spring.b
+                    } else if spring.b == *node_id {
+                        spring.a
+                    } else {
+                        return None;
+                    };
+                    self.positions.get(&other).copied()
+                })
+                .next();
+            let jitter = seeded_offset::<D>(node_id);
+            let position = match anchor {
+                Some(anchor) => std::array::from_fn(|axis| anchor[axis] + jitter[axis] * 0.5),
+                None => std::array::from_fn(|axis| jitter[axis] * 4.0),
+            };
+            self.positions.insert(*node_id, position);
+            added = true;
+        }
+        if added {
+            self.temperature = INITIAL_TEMPERATURE;
+        }
+    }
+}
+
+fn offset<const D: usize>(a: &[f64; D], b: &[f64; D]) -> ([f64; D], f64) {
+    let delta: [f64; D] = std::array::from_fn(|axis| a[axis] - b[axis]);
+    let distance = delta
+        .iter()
+        .map(|v| v * v)
+        .sum::<f64>()
+        .sqrt()
+        .max(MIN_DISTANCE);
+    (delta, distance)
+}
+
+/// Deterministic offset in [-IDEAL_DISTANCE, IDEAL_DISTANCE] per axis, so a
+/// restarted server lays the same graph out the same way.
+fn seeded_offset<const D: usize>(node_id: &NodeId) -> [f64; D] {
+    std::array::from_fn(|axis| {
+        let mut hasher = DefaultHasher::new();
+        node_id.hash(&mut hasher);
+        axis.hash(&mut hasher);
+        let unit = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+        (unit * 2.0 - 1.0) * IDEAL_DISTANCE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(index: usize) -> NodeId {
+        let mut id = *b"LAYOUT-NODE-0000";
+        id[12..].copy_from_slice(format!("{index:04}").as_bytes());
+        id
+    }
+
+    /// A ring of busy edges, a pendant chain and a few isolated nodes.
+    fn topology() -> (Vec<NodeId>, Vec<Spring>) {
+        let node_ids: Vec<NodeId> = (0..24).map(node).collect();
+        let mut springs: Vec<Spring> = (0..12)
+            .map(|i| Spring {
+                a: node(i),
+                b: node((i + 1) % 12),
+                weight: 1_000.0,
+            })
+            .collect();
+        springs.extend((12..20).map(|i| Spring {
+            a: node(i - 1),
+            b: node(i),
+            weight: 1.0,
+        }));
+        (node_ids, springs)
+    }
+
+    fn positions(engine: &LayoutEngine, node_ids: &[NodeId]) -> Vec<([f64; 2], [f64; 3])> {
+        node_ids
+            .iter()
+            .map(|node_id| engine.position(node_id).expect("placed"))
+            .map(|layout| (layout.position_2d, layout.position_3d))
+            .collect()
+    }
+
+    #[test]
+    fn same_graph_lays_out_the_same_way() {
+        let (node_ids, springs) = topology();
+        let run = || {
+            let mut engine = LayoutEngine::new();
+            engine.relax(&node_ids[..12], &springs, 20);
+            engine.relax(&node_ids, &springs, 50);
+            positions(&engine, &node_ids)
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn coordinates_stay_finite_and_bounded() {
+        let (node_ids, springs) = topology();
+        let mut engine = LayoutEngine::new();
+        for _ in 0..20 {
+            engine.relax(&node_ids, &springs, 50);
+        }
+        for (planar, spatial) in positions(&engine, &node_ids) {
+            for value in planar.iter().chain(&spatial) {
+                assert!(
+                    value.is_finite() && value.abs() < 100.0 * IDEAL_DISTANCE,
+                    "{value}"
+                );
+            }
+        }
+        // Forgotten nodes lose their position.
+        engine.relax(&node_ids[..4], &springs, 1);
+        assert!(engine.position(&node_ids[4]).is_none());
+    }
+}
@@ -1,2 +1,22 @@
+pub mod ack;
+pub mod admin;
+pub mod alerts;
 pub mod analytics;
+pub mod anomaly;
+pub mod api;
+pub mod changes;
 pub mod client;
+pub mod community;
+pub mod config;
+pub mod events;
+pub mod graph;
+pub mod grouping;
+pub mod http;
+pub mod layout;
+pub mod logging;
+pub mod metrics;
+pub mod observer;
+pub mod query;
+pub mod subscription;
+pub mod viewer;
+pub mod websocket;
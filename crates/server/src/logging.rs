@@ -0,0 +1,283 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Messages a single call site may emit per `RATE_WINDOW` before the rest
+/// are dropped and counted.
+const RATE_BURST: u32 = 20;
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Level filter in `env_logger` syntax: a default level plus optional
+/// per-module overrides, e.g. `info,server::analytics=debug`. The most
+/// specific matching module prefix wins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            default: LevelFilter::Info,
+            modules: Vec::new(),
+        }
+    }
+}
+
+impl LogFilter {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    let level = parse_level(level)?;
+                    filter.modules.push((module.trim().to_string(), level));
+                }
+                None => match parse_level(directive) {
+                    Ok(level) => filter.default = level,
+                    // A bare module name enables everything for it.
+                    Err(_) => filter
+                        .modules
+                        .push((directive.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+        // Longest prefixes first so the most specific directive matches.
+        filter
+            .modules
+            .sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Ok(filter)
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid log level: {level}"))
+}
+
+/// Per-call-site token bucket refilled every window. Reports how many
+/// messages were dropped once a call site is allowed to log again.
+#[derive(Default)]
+pub struct RateLimiter {
+    callsites: HashMap<(&'static str, u32), Callsite>,
+}
+
+struct Callsite {
+    window_start: Instant,
+    emitted: u32,
+    suppressed: u64,
+}
+
+impl RateLimiter {
+    /// Returns `None` when the message should be dropped, otherwise the
+    /// number of messages suppressed at this call site since the last one.
+    pub fn admit(&mut self, callsite: (&'static str, u32), now: Instant) -> Option<u64> {
+        let state = self.callsites.entry(callsite).or_insert(Callsite {
+            window_start: now,
+            emitted: 0,
+            suppressed: 0,
+        });
+        if now.duration_since(state.window_start) >= RATE_WINDOW {
+            state.window_start = now;
+            state.emitted = 0;
+        }
+        if state.emitted >= RATE_BURST {
+            state.suppressed += 1;
+            return None;
+        }
+        state.emitted += 1;
+        Some(std::mem::take(&mut state.suppressed))
+    }
+}
+
+/// `log` backend for the server: leveled, filtered per module, rate
+/// limited per call site, written to stderr as text or JSON lines.
+pub struct Logger {
+    filter: LogFilter,
+    format: LogFormat,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl Logger {
+    pub fn new(filter: LogFilter, format: LogFormat) -> Self {
+        Self {
+            filter,
+            format,
+            limiter: Mutex::new(RateLimiter::default()),
+        }
+    }
+
+    /// Installs the logger as the global `log` backend.
+    pub fn init(self) -> Result<(), log::SetLoggerError> {
+        log::set_max_level(self.filter.max_level());
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // Records from the `log` macros carry a static file name; others
+        // (e.g. forwarded from another framework) are never rate limited.
+        let suppressed = match record.file_static() {
+            Some(file) => {
+                let callsite = (file, record.line().unwrap_or(0));
+                let mut limiter = self.limiter.lock().unwrap_or_else(|err| err.into_inner());
+                match limiter.admit(callsite, Instant::now()) {
+                    Some(suppressed) => suppressed,
+                    None => return,
+                }
+            }
+            None => 0,
+        };
+        let line = format_record(self.format, epoch_timestamp_us(), record, suppressed);
+        let _ = writeln!(std::io::stderr().lock(), "{line}");
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    ts_us: u64,
+    level: &'a str,
+    target: &'a str,
+    message: String,
+    #[serde(skip_serializing_if = "is_zero")]
+    suppressed: u64,
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// Renders one log line. `suppressed` counts earlier messages from the
+/// same call site dropped by rate limiting.
+pub fn format_record(format: LogFormat, ts_us: u64, record: &Record, suppressed: u64) -> String {
+    match format {
+        LogFormat::Text => {
+            let mut line = format!(
+                "{}.{:06} {:<5} {}: {}",
+                ts_us / 1_000_000,
+                ts_us % 1_000_000,
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            if suppressed > 0 {
+                line.push_str(&format!(" ({suppressed} similar messages suppressed)"));
+            }
+            line
+        }
+        LogFormat::Json => serde_json::to_string(&JsonLine {
+            ts_us,
+            level: record.level().as_str(),
+            target: record.target(),
+            message: record.args().to_string(),
+            suppressed,
+        })
+        .unwrap_or_default(),
+    }
+}
+
+fn epoch_timestamp_us() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn filter_applies_most_specific_module_directive() {
+        let filter = LogFilter::parse("warn,server=info,server::analytics=trace").expect("parse");
+        assert_eq!(
+            filter.level_for("tungstenite::handshake"),
+            LevelFilter::Warn
+        );
+        assert_eq!(filter.level_for("server"), LevelFilter::Info);
+        assert_eq!(filter.level_for("server::websocket"), LevelFilter::Info);
+        assert_eq!(filter.level_for("server::analytics"), LevelFilter::Trace);
+        assert_eq!(filter.level_for("server_extra"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+        assert!(LogFilter::parse("server=loud").is_err());
+    }
+
+    #[test]
+    fn rate_limiter_drops_bursts_and_reports_suppressed_count() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        let callsite = ("main.rs", 10);
+        for _ in 0..RATE_BURST {
+            assert_eq!(limiter.admit(callsite, start), Some(0));
+        }
+        assert_eq!(limiter.admit(callsite, start), None);
+        assert_eq!(limiter.admit(callsite, start), None);
+        assert_eq!(limiter.admit(("main.rs", 11), start), Some(0));
+        assert_eq!(limiter.admit(callsite, start + RATE_WINDOW), Some(2));
+    }
+
+    #[test]
+    fn json_lines_carry_level_target_and_suppressed_count() {
+        let args = format_args!("loss on {}", "edge");
+        let record = Record::builder()
+            .level(Level::Debug)
+            .target("server::analytics")
+            .args(args)
+            .build();
+        let line = format_record(LogFormat::Json, 1_500_000, &record, 3);
+        let value: serde_json::Value = serde_json::from_str(&line).expect("json line");
+        assert_eq!(value["level"], "DEBUG");
+        assert_eq!(value["target"], "server::analytics");
+        assert_eq!(value["message"], "loss on edge");
+        assert_eq!(value["suppressed"], 3);
+        assert_eq!(
+            format_record(LogFormat::Text, 1_500_000, &record, 0),
+            "1.500000 DEBUG server::analytics: loss on edge"
+        );
+    }
+}
@@ -1,127 +1,447 @@
-use common::WireMessage;
+use common::config::{ServerConfig, ServerInfo};
+use common::{
+    Codec, ErrorCode, RegisterAckPacket, RegisterOutcome, UnregisterAckPacket, WireMessage,
+};
+use server::ack::{AckSessions, MAX_ACK_SESSIONS};
+use server::admin;
 use server::analytics::AnalyticsManager;
+use server::api;
+use server::config;
+use server::http::{HttpRequest, HttpResponse, HttpServer};
+use server::logging::{LogFilter, LogFormat, Logger};
+use server::metrics::{self, ServerMetrics};
+use server::observer;
+use server::subscription::{DeltaTracker, EventSubscriber, LeasedSubscribers, Subscriber};
+use server::viewer::viewer_page;
+use server::websocket::WebSocketServer;
+use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
 use std::{
     env,
     io::Result,
-    net::UdpSocket,
+    net::{SocketAddr, UdpSocket},
     time::{Duration, Instant},
 };
 
-fn encode_wire_message(message: &WireMessage) -> Result<Vec<u8>> {
-    common::encode_message(message).map_err(Error::other)
+const LAYOUT_INTERVAL: Duration = Duration::from_millis(250);
+const COMMUNITY_INTERVAL: Duration = Duration::from_secs(5);
+const LAYOUT_ITERATIONS_PER_TICK: usize = 5;
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Anomaly baselines and alert rules are evaluated this often.
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(1);
+/// Most events carried by one `Events` message, keeping UDP datagrams small.
+const MAX_EVENTS_PER_MESSAGE: usize = 64;
+/// UDP read timeout while TCP listeners (WebSocket, HTTP) also need polling.
+const TCP_POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// Decode-failure and unexpected-message `Error` replies sent to one source
+/// per second; anything beyond that is only counted, so a spoofed source
+/// cannot use the server as a reflector.
+const ERROR_REPLIES_PER_SEC: u32 = 5;
+
+/// Counts replies per source within the current one-second window.
+struct ReplyBudget {
+    window_start: Instant,
+    sent: HashMap<SocketAddr, u32>,
+}
+
+impl ReplyBudget {
+    fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            sent: HashMap::new(),
+        }
+    }
+
+    fn admit(&mut self, src: SocketAddr, now: Instant) -> bool {
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.sent.clear();
+        }
+        let sent = self.sent.entry(src).or_default();
+        if *sent >= ERROR_REPLIES_PER_SEC {
+            return false;
+        }
+        *sent += 1;
+        true
+    }
+}
+
+fn encode_wire_message(codec: Codec, message: &WireMessage) -> Result<Vec<u8>> {
+    codec.encode_message(message).map_err(Error::other)
 }
 
-fn parse_bind_addr_args() -> Result<String> {
-    let mut server = String::from("127.0.0.1");
-    let mut port: u16 = 8080;
+/// Sends an unsolicited push; the caller drops the subscriber on failure.
+fn send_push(
+    socket: &UdpSocket,
+    codec: Codec,
+    message: &WireMessage,
+    dst: SocketAddr,
+) -> Result<()> {
+    socket.send_to(&encode_wire_message(codec, message)?, dst)?;
+    Ok(())
+}
+
+/// Builds the effective config: defaults, then `--config <file>`, then
+/// `SIMD_LOG`/`SIMD_ADMIN_TOKEN`, then the remaining flags in order. `-s`/`-p` replace the
+/// host or port of `server.bind_addr`.
+fn load_server_config() -> Result<ServerConfig> {
+    let invalid = |message: String| Error::new(ErrorKind::InvalidInput, message);
+    let mut config_path: Option<PathBuf> = None;
+    let mut host: Option<String> = None;
+    let mut port: Option<u16> = None;
+    let mut overrides: Vec<(String, toml::Value)> = Vec::new();
+    if let Ok(spec) = env::var("SIMD_LOG") {
+        overrides.push(("logging.filter".to_string(), toml::Value::String(spec)));
+    }
+    if let Ok(token) = env::var("SIMD_ADMIN_TOKEN") {
+        overrides.push(("admin.token".to_string(), toml::Value::String(token)));
+    }
     let mut args = env::args().skip(1);
 
     while let Some(arg) = args.next() {
+        let mut value = |flag: &str| {
+            args.next()
+                .ok_or_else(|| invalid(format!("missing value for {flag}")))
+        };
         match arg.as_str() {
-            "-s" | "--server" => {
-                let value = args.next().ok_or_else(|| {
-                    Error::new(ErrorKind::InvalidInput, "missing value for -s/--server")
-                })?;
-                server = value;
-            }
+            "-c" | "--config" => config_path = Some(PathBuf::from(value("--config")?)),
+            "-s" | "--server" => host = Some(value("-s/--server")?),
             "-p" | "--port" => {
-                let value = args.next().ok_or_else(|| {
-                    Error::new(ErrorKind::InvalidInput, "missing value for -p/--port")
-                })?;
-                port = value.parse::<u16>().map_err(|_| {
-                    Error::new(ErrorKind::InvalidInput, format!("invalid port: {value}"))
-                })?;
+                let raw = value("-p/--port")?;
+                port = Some(
+                    raw.parse::<u16>()
+                        .map_err(|_| invalid(format!("invalid port: {raw}")))?,
+                );
             }
+            "--layout" => overrides.push(("server.layout".to_string(), true.into())),
+            "--ws" => overrides.push(("server.ws_addr".to_string(), value("--ws")?.into())),
+            "--http" => overrides.push(("server.http_addr".to_string(), value("--http")?.into())),
+            "--log" => overrides.push(("logging.filter".to_string(), value("--log")?.into())),
+            "--log-json" => overrides.push(("logging.json".to_string(), true.into())),
+            "--set" => overrides.push(
+                config::parse_override(&value("--set")?).map_err(|err| invalid(err.to_string()))?,
+            ),
             "-h" | "--help" => {
-                println!("Usage: server [-s|--server <host>] [-p|--port <port>]");
+                println!(
+                    "Usage: server [-c|--config <file.toml>] [-s|--server <host>] [-p|--port <port>] [--layout] [--ws <host:port>] [--http <host:port>] [--log <filter>] [--log-json] [--set <key>=<value>]..."
+                );
                 std::process::exit(0);
             }
-            _ => {
-                return Err(Error::new(
-                    ErrorKind::InvalidInput,
-                    format!("unknown argument: {arg}"),
-                ));
-            }
+            _ => return Err(invalid(format!("unknown argument: {arg}"))),
         }
     }
 
-    Ok(format!("{server}:{port}"))
+    let mut server_config = config::load(config_path.as_deref(), &overrides)
+        .map_err(|err| invalid(format!("config error: {err}")))?;
+    if host.is_some() || port.is_some() {
+        let bind_addr = &server_config.server.bind_addr;
+        let (current_host, current_port) = bind_addr.rsplit_once(':').unwrap_or((bind_addr, ""));
+        let host = host.unwrap_or_else(|| current_host.to_string());
+        let port = port.map_or_else(|| current_port.to_string(), |port| port.to_string());
+        server_config.server.bind_addr = format!("{host}:{port}");
+    }
+    config::validate(&mut server_config).map_err(|err| invalid(format!("config error: {err}")))?;
+    Ok(server_config)
 }
 
 fn main() -> Result<()> {
+    let server_config = load_server_config()?;
+    let log_format = if server_config.logging.json {
+        LogFormat::Json
+    } else {
+        LogFormat::Text
+    };
+    let log_filter = LogFilter::parse(&server_config.logging.filter).map_err(Error::other)?;
+    Logger::new(log_filter, log_format)
+        .init()
+        .map_err(Error::other)?;
     let args: Vec<String> = env::args().collect();
-    println!("Program path: {}", args[0]);
-    let server_addr = parse_bind_addr_args()?;
+    log::debug!("Program path: {}", args[0]);
+    log::debug!("Effective config: {:?}", redacted(&server_config));
+    let network = &server_config.server;
+    let server_addr = &network.bind_addr;
+
+    let socket = UdpSocket::bind(server_addr).expect("Couldn't bind to socket");
+    let read_timeout = if network.ws_addr.is_some() || network.http_addr.is_some() {
+        TCP_POLL_INTERVAL
+    } else {
+        Duration::from_millis(250)
+    };
+    socket.set_read_timeout(Some(read_timeout))?;
+    let mut current_read_timeout = read_timeout;
+    log::info!("Server listening on {}...", server_addr);
 
-    let socket = UdpSocket::bind(&server_addr).expect("Couldn't bind to socket");
-    socket.set_read_timeout(Some(Duration::from_millis(250)))?;
-    println!("Server listening on {}...", server_addr);
+    let mut websocket = match &network.ws_addr {
+        Some(ws_addr) => {
+            let websocket = WebSocketServer::bind(ws_addr)?;
+            log::info!("WebSocket listening on {}...", websocket.local_addr()?);
+            Some(websocket)
+        }
+        None => None,
+    };
+    let ws_port = match &websocket {
+        Some(websocket) => Some(websocket.local_addr()?.port()),
+        None => None,
+    };
+    let mut http = match &network.http_addr {
+        Some(http_addr) => {
+            let http = HttpServer::bind(http_addr)?;
+            log::info!("Topology viewer at http://{}/", http.local_addr()?);
+            Some(http)
+        }
+        None => None,
+    };
 
-    let mut analytics = AnalyticsManager::new(5, 1000); // 5-sec window, max 1000 clients
+    let cleanup_interval = Duration::from_millis(server_config.analytics.cleanup_interval_ms);
+    let mut analytics = AnalyticsManager::with_config(server_config.analytics.clone());
+    if network.layout {
+        analytics.enable_layout();
+    }
+    observer::install(&mut analytics, &server_config.observers)?;
+    let mut udp_subscribers: LeasedSubscribers<Subscriber> = LeasedSubscribers::new();
+    let mut udp_event_subscribers: LeasedSubscribers<EventSubscriber> = LeasedSubscribers::new();
+    let mut delta_tracker = DeltaTracker::new();
+    let mut server_metrics = ServerMetrics::default();
+    let mut error_reply_budget = ReplyBudget::new(Instant::now());
+    let mut ack_sessions = AckSessions::new(network.ack_mode);
     let mut buf = [0u8; 65535];
     let mut last_cleanup_at = Instant::now();
+    let mut last_layout_at = Instant::now();
+    let mut last_community_at = Instant::now();
+    let mut last_push_at = Instant::now();
+    let mut last_evaluation_at = Instant::now();
 
     loop {
         let now = Instant::now();
-        if now.duration_since(last_cleanup_at) >= Duration::from_secs(1) {
-            analytics.cleanup_stale(Duration::from_secs(60), Duration::from_secs(30), now);
+        if now.duration_since(last_cleanup_at) >= cleanup_interval {
+            analytics.cleanup_expired(now);
             last_cleanup_at = now;
         }
+        if network.layout && now.duration_since(last_layout_at) >= LAYOUT_INTERVAL {
+            analytics.relax_layout(now, LAYOUT_ITERATIONS_PER_TICK);
+            last_layout_at = now;
+        }
+        if now.duration_since(last_community_at) >= COMMUNITY_INTERVAL {
+            analytics.detect_communities(now);
+            last_community_at = now;
+        }
+        if now.duration_since(last_evaluation_at) >= EVALUATION_INTERVAL {
+            analytics.evaluate_loss_thresholds();
+            analytics.evaluate_anomalies(now);
+            analytics.evaluate_alerts(now);
+            last_evaluation_at = now;
+        }
+        if now.duration_since(last_push_at) >= PUSH_INTERVAL {
+            for addr in udp_subscribers.expire(now) {
+                log::info!("Topology subscription of {} expired", addr);
+            }
+            for addr in udp_event_subscribers.expire(now) {
+                log::info!("Event subscription of {} expired", addr);
+            }
+            let has_ws_subscribers = websocket.as_ref().is_some_and(|ws| ws.has_subscribers());
+            if !udp_subscribers.is_empty() || has_ws_subscribers {
+                // Pushes follow their own change cursor, leaving the export
+                // state to clients that request snapshots.
+                let snapshot =
+                    analytics.peek_topology_snapshot_since(now, delta_tracker.change_seq());
+                let push = delta_tracker.push(snapshot);
+                let mut failed = Vec::new();
+                for (addr, subscriber) in udp_subscribers.iter_mut() {
+                    let snapshot = push.for_subscriber(subscriber).clone();
+                    let message = WireMessage::Topology(snapshot);
+                    if let Err(err) = send_push(&socket, subscriber.codec, &message, addr) {
+                        log::warn!("Dropping topology subscriber {}: {}", addr, err);
+                        failed.push(addr);
+                    }
+                }
+                for addr in failed {
+                    udp_subscribers.remove(&addr);
+                }
+                if let Some(websocket) = websocket.as_mut() {
+                    websocket.push(&push);
+                }
+            }
+            last_push_at = now;
+        }
+
+        let last_event_seq = analytics.last_event_seq();
+        let events_since = |after_seq| analytics.events_since(after_seq, MAX_EVENTS_PER_MESSAGE);
+        let mut failed = Vec::new();
+        for (addr, subscriber) in udp_event_subscribers.iter_mut() {
+            for batch in subscriber.drain(last_event_seq, events_since) {
+                let message = WireMessage::Events(batch);
+                if let Err(err) = send_push(&socket, subscriber.codec, &message, addr) {
+                    log::warn!("Dropping event subscriber {}: {}", addr, err);
+                    failed.push(addr);
+                    break;
+                }
+            }
+        }
+        for addr in failed {
+            udp_event_subscribers.remove(&addr);
+        }
+        if let Some(websocket) = websocket.as_mut() {
+            websocket.push_events(last_event_seq, events_since);
+        }
+
+        for (addr, codec, message) in ack_sessions.due(now) {
+            send_reply(&socket, &mut server_metrics, codec, &message, addr)?;
+        }
+
+        if let Some(websocket) = websocket.as_mut() {
+            websocket
+                .poll(|message, src| handle_request(&mut analytics, &server_config, message, src));
+        }
+
+        if let Some(http) = http.as_mut() {
+            http.poll(|request| handle_http(&analytics, &server_metrics, request, ws_port));
+        }
+
+        // Wake up in time for the next delayed or selective ack.
+        let timeout = ack_sessions
+            .next_deadline()
+            .map_or(read_timeout, |deadline| {
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .clamp(Duration::from_millis(1), read_timeout)
+            });
+        if timeout != current_read_timeout {
+            socket.set_read_timeout(Some(timeout))?;
+            current_read_timeout = timeout;
+        }
 
         match socket.recv_from(&mut buf) {
             Ok((amt, src)) => {
-                println!("Received {} bytes from {}", amt, src);
+                log::trace!("Received {} bytes from {}", amt, src);
+                server_metrics.datagrams_received += 1;
+                server_metrics.bytes_received += amt as u64;
 
-                if let Ok(message) = common::decode_message(&buf[..amt]) {
-                    match message {
-                        WireMessage::RegisterNode(packet) => {
-                            analytics.on_node_registered(&packet, src, Instant::now());
-                            println!("Registered node {:?}", packet.node_id);
+                // Replies go back in whichever codec the request used.
+                let codec = Codec::detect(&buf[..amt]);
+                let message = match codec.decode_message(&buf[..amt]) {
+                    Ok(message) => message,
+                    Err(err) => {
+                        server_metrics.decode_failures += 1;
+                        log::warn!("Failed to decode packet from {}: {}", src, err);
+                        if !error_reply_budget.admit(src, Instant::now()) {
+                            continue;
                         }
-                        WireMessage::UnregisterNode(packet) => {
-                            analytics.on_node_unregistered(&packet, Instant::now());
-                            println!("Unregistered node {:?}", packet.node_id);
+                        let reply = WireMessage::Error {
+                            code: ErrorCode::DecodeFailed,
+                            detail: format!("could not decode {amt}-byte datagram"),
+                            related_seq: None,
+                        };
+                        if let Err(err) = socket.send_to(&encode_wire_message(codec, &reply)?, src)
+                        {
+                            log::warn!("Failed to send error to {}: {}", src, err);
                         }
-                        WireMessage::Data(packet) => {
-                            let ack = analytics.on_packet_received(src, &packet, Instant::now());
-                            let ack_bytes = encode_wire_message(&WireMessage::Ack(ack))?;
-                            socket.send_to(&ack_bytes, src)?;
-                            println!(
-                                "seq={} class={} class_seq={} → ACK sent",
-                                packet.global_seq, packet.class, packet.class_seq
-                            );
-                        }
-                        WireMessage::RequestTopology => {
-                            let snapshot = analytics.export_topology_snapshot(Instant::now());
-                            let topology_bytes =
-                                encode_wire_message(&WireMessage::Topology(snapshot))?;
-                            socket.send_to(&topology_bytes, src)?;
-                            println!(
-                                "Topology snapshot sent to {} ({} bytes)",
-                                src,
-                                topology_bytes.len()
-                            );
+                        continue;
+                    }
+                };
+                match message {
+                    WireMessage::Subscribe(subscription) => {
+                        let subscribed = udp_subscribers.subscribe(
+                            src,
+                            Instant::now(),
+                            || Subscriber::new(codec, subscription),
+                            |subscriber| subscriber.renew(codec, subscription),
+                        );
+                        if subscribed {
+                            log::info!("{} subscribed to topology pushes", src);
+                        } else {
+                            log::warn!("Refused topology subscription from {}: limit reached", src);
+                            let reply = WireMessage::Error {
+                                code: ErrorCode::InvalidRequest,
+                                detail: format!(
+                                    "topology subscriber limit ({}) reached",
+                                    udp_subscribers.len()
+                                ),
+                                related_seq: None,
+                            };
+                            send_reply(&socket, &mut server_metrics, codec, &reply, src)?;
                         }
-                        WireMessage::RequestAnalytics => {
-                            let snapshot = analytics.export_snapshot();
-                            let analytics_bytes =
-                                encode_wire_message(&WireMessage::Analytics(snapshot))?;
-                            socket.send_to(&analytics_bytes, src)?;
-                            println!(
-                                "Analytics snapshot sent to {} ({} bytes)",
-                                src,
-                                analytics_bytes.len()
-                            );
+                    }
+                    WireMessage::Unsubscribe => {
+                        udp_subscribers.remove(&src);
+                        log::info!("{} unsubscribed from topology pushes", src);
+                    }
+                    WireMessage::UnsubscribeEvents => {
+                        udp_event_subscribers.remove(&src);
+                        log::info!("{} unsubscribed from topology events", src);
+                    }
+                    WireMessage::SetAckMode(mode) => {
+                        let now = Instant::now();
+                        let replies = match mode.validate() {
+                            Ok(()) => match ack_sessions.set_mode(src, codec, mode, now) {
+                                Some(mut replies) => {
+                                    log::info!("{} switched to ack mode {}", src, mode);
+                                    replies.push(WireMessage::AckModeSet(mode));
+                                    replies
+                                }
+                                None => {
+                                    log::warn!("Refused ack mode from {}: limit reached", src);
+                                    vec![WireMessage::Error {
+                                        code: ErrorCode::InvalidRequest,
+                                        detail: format!(
+                                            "ack session limit ({MAX_ACK_SESSIONS}) reached"
+                                        ),
+                                        related_seq: None,
+                                    }]
+                                }
+                            },
+                            Err(detail) => vec![WireMessage::Error {
+                                code: ErrorCode::InvalidRequest,
+                                detail,
+                                related_seq: None,
+                            }],
+                        };
+                        for reply in replies {
+                            send_reply(&socket, &mut server_metrics, codec, &reply, src)?;
                         }
-                        WireMessage::Ack(_)
-                        | WireMessage::Analytics(_)
-                        | WireMessage::Topology(_) => {
-                            println!("Ignoring unexpected server-side message from {}", src);
+                    }
+                    message => {
+                        let reply = handle_request(&mut analytics, &server_config, message, src)
+                            .and_then(|reply| {
+                                ack_sessions.filter(src, codec, reply, Instant::now())
+                            });
+                        let reply = match reply {
+                            Some(WireMessage::EventsSubscribed { after_seq }) => {
+                                let subscriber = EventSubscriber::new(codec, after_seq);
+                                let subscribed = udp_event_subscribers.subscribe(
+                                    src,
+                                    Instant::now(),
+                                    || subscriber,
+                                    |existing| *existing = subscriber,
+                                );
+                                if subscribed {
+                                    log::info!("{} subscribed to topology events", src);
+                                    reply
+                                } else {
+                                    log::warn!(
+                                        "Refused event subscription from {}: limit reached",
+                                        src
+                                    );
+                                    Some(WireMessage::Error {
+                                        code: ErrorCode::InvalidRequest,
+                                        detail: format!(
+                                            "event subscriber limit ({}) reached",
+                                            udp_event_subscribers.len()
+                                        ),
+                                        related_seq: None,
+                                    })
+                                }
+                            }
+                            Some(WireMessage::Error {
+                                code: ErrorCode::UnexpectedMessage,
+                                ..
+                            }) if !error_reply_budget.admit(src, Instant::now()) => None,
+                            reply => reply,
+                        };
+                        if let Some(reply) = reply {
+                            send_reply(&socket, &mut server_metrics, codec, &reply, src)?;
                         }
                     }
-                } else {
-                    println!("Failed to decode packet from {}", src);
                 }
             }
             Err(err)
@@ -130,3 +450,263 @@ fn main() -> Result<()> {
         }
     }
 }
+
+fn send_reply(
+    socket: &UdpSocket,
+    server_metrics: &mut ServerMetrics,
+    codec: Codec,
+    reply: &WireMessage,
+    dst: SocketAddr,
+) -> Result<()> {
+    let reply_bytes = encode_wire_message(codec, reply)?;
+    match socket.send_to(&reply_bytes, dst) {
+        Ok(_) => log::trace!("Reply sent to {} ({} bytes)", dst, reply_bytes.len()),
+        Err(err) => {
+            if matches!(
+                reply,
+                WireMessage::Ack(_) | WireMessage::AckBatch(_) | WireMessage::SelectiveAck(_)
+            ) {
+                server_metrics.ack_send_errors += 1;
+            }
+            log::warn!("Failed to send reply to {}: {}", dst, err);
+        }
+    }
+    Ok(())
+}
+
+fn handle_http(
+    analytics: &AnalyticsManager,
+    server_metrics: &ServerMetrics,
+    request: &HttpRequest,
+    ws_port: Option<u16>,
+) -> HttpResponse {
+    let now = Instant::now();
+    if let Some(response) = api::handle(analytics, request, now) {
+        return response;
+    }
+    match request.path.as_str() {
+        "/" | "/index.html" => HttpResponse::ok("text/html; charset=utf-8", viewer_page(ws_port)),
+        "/metrics" => HttpResponse::ok(
+            metrics::CONTENT_TYPE,
+            metrics::render(analytics, server_metrics, now),
+        ),
+        _ => HttpResponse::not_found(),
+    }
+}
+
+fn node_limit_error(related_seq: u32, detail: &str) -> WireMessage {
+    WireMessage::Error {
+        code: ErrorCode::NodeLimitReached,
+        detail: detail.to_string(),
+        related_seq: Some(related_seq),
+    }
+}
+
+/// Copy of `config` that is safe to log or send to clients.
+fn redacted(config: &ServerConfig) -> ServerConfig {
+    let mut config = config.clone();
+    if config.admin.token.is_some() {
+        config.admin.token = Some("<redacted>".to_string());
+    }
+    config
+}
+
+/// Answers one request, shared by the UDP and WebSocket transports.
+/// Subscriptions are transport state and never reach this function, except
+/// `SubscribeEvents`, whose reply carries the cursor the transport records.
+fn handle_request(
+    analytics: &mut AnalyticsManager,
+    server_config: &ServerConfig,
+    message: WireMessage,
+    src: SocketAddr,
+) -> Option<WireMessage> {
+    let now = Instant::now();
+    match message {
+        WireMessage::RegisterNode(packet) => {
+            let outcome = analytics.on_node_registered(&packet, src, now);
+            match outcome {
+                RegisterOutcome::NodeLimitReached => {
+                    log::warn!("Rejected node {:?}: node limit reached", packet.node_id)
+                }
+                _ => log::info!("Registered node {:?} ({:?})", packet.node_id, outcome),
+            }
+            Some(WireMessage::RegisterAck(RegisterAckPacket {
+                node_id: packet.node_id,
+                control_id: packet.control_id,
+                outcome,
+                server_timestamp_us: common::now_timestamp_us(),
+            }))
+        }
+        WireMessage::UnregisterNode(packet) => {
+            let outcome = analytics.on_node_unregistered(&packet, now);
+            log::info!("Unregistered node {:?} ({:?})", packet.node_id, outcome);
+            Some(WireMessage::UnregisterAck(UnregisterAckPacket {
+                node_id: packet.node_id,
+                control_id: packet.control_id,
+                outcome,
+                server_timestamp_us: common::now_timestamp_us(),
+            }))
+        }
+        WireMessage::Data(packet) => match analytics.on_packet_received(src, &packet, now) {
+            Some(ack) => {
+                log::trace!(
+                    "seq={} class={} class_seq={} → ACK",
+                    packet.global_seq,
+                    packet.class,
+                    packet.class_seq
+                );
+                Some(WireMessage::Ack(ack))
+            }
+            None => {
+                log::warn!(
+                    "Dropped data seq={} from {}: node limit reached",
+                    packet.global_seq,
+                    src
+                );
+                Some(node_limit_error(packet.global_seq, "data record dropped"))
+            }
+        },
+        WireMessage::DataBatch(packets) if packets.len() > common::MAX_DATA_BATCH_RECORDS => {
+            log::warn!("Rejected batch of {} records from {}", packets.len(), src);
+            Some(WireMessage::Error {
+                code: ErrorCode::InvalidRequest,
+                detail: format!(
+                    "batch of {} records exceeds the limit of {}",
+                    packets.len(),
+                    common::MAX_DATA_BATCH_RECORDS
+                ),
+                related_seq: packets.first().map(|packet| packet.global_seq),
+            })
+        }
+        WireMessage::DataBatch(packets) => {
+            let (ack, dropped_seqs) = analytics.on_batch_received(src, &packets, now);
+            log::trace!("batch of {} records → ACK", packets.len());
+            match dropped_seqs.first() {
+                // A partly dropped batch is still acked; the missing seqs
+                // tell the sender which records were not counted.
+                Some(&seq) if ack.original_seqs.is_empty() => {
+                    log::warn!(
+                        "Dropped batch of {} records from {}: node limit reached",
+                        packets.len(),
+                        src
+                    );
+                    Some(node_limit_error(seq, "every record of the batch dropped"))
+                }
+                Some(_) => {
+                    log::warn!(
+                        "Dropped {} of {} batch records from {}: node limit reached",
+                        dropped_seqs.len(),
+                        packets.len(),
+                        src
+                    );
+                    Some(WireMessage::AckBatch(ack))
+                }
+                None => Some(WireMessage::AckBatch(ack)),
+            }
+        }
+        WireMessage::RequestTopology => {
+            log::debug!("Topology snapshot requested by {}", src);
+            Some(WireMessage::Topology(
+                analytics.export_topology_snapshot(now),
+            ))
+        }
+        WireMessage::RequestGroupedTopology { max_depth } => {
+            log::debug!(
+                "Grouped topology snapshot (depth {}) requested by {}",
+                max_depth,
+                src
+            );
+            Some(WireMessage::Topology(
+                analytics.peek_grouped_topology_snapshot(now, max_depth),
+            ))
+        }
+        WireMessage::RequestFilteredTopology(query) => {
+            log::debug!("Filtered topology snapshot requested by {}", src);
+            Some(WireMessage::Topology(
+                analytics.peek_filtered_topology_snapshot(now, &query),
+            ))
+        }
+        WireMessage::RequestPath {
+            src_node_id,
+            dst_node_id,
+        } => {
+            log::debug!("Path requested by {}", src);
+            Some(WireMessage::Path(
+                analytics.shortest_path(src_node_id, dst_node_id),
+            ))
+        }
+        WireMessage::RequestAnalytics => {
+            log::debug!("Analytics snapshot requested by {}", src);
+            Some(WireMessage::Analytics(analytics.export_snapshot()))
+        }
+        WireMessage::AckLossReport(report) => {
+            analytics.on_ack_loss_report(&report);
+            None
+        }
+        WireMessage::Heartbeat(packet) => {
+            if !analytics.on_heartbeat(&packet, src, now) {
+                log::debug!("Heartbeat from unknown node {:?}", packet.node_id);
+            }
+            None
+        }
+        // UDP sessions handle this before dispatch; WebSocket replies are
+        // always immediate.
+        WireMessage::SetAckMode(_) => Some(WireMessage::Error {
+            code: ErrorCode::InvalidRequest,
+            detail: "ack modes apply to UDP sessions only".to_string(),
+            related_seq: None,
+        }),
+        WireMessage::RequestServerInfo => {
+            log::debug!("Server info requested by {}", src);
+            let mut config = redacted(server_config);
+            config.analytics = analytics.config().clone();
+            Some(WireMessage::ServerInfo(ServerInfo {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                uptime_us: analytics.uptime().as_micros() as u64,
+                config,
+            }))
+        }
+        WireMessage::RequestEvents { after_seq } => {
+            log::debug!("Events after {} requested by {}", after_seq, src);
+            Some(WireMessage::Events(
+                analytics.events_since(after_seq, MAX_EVENTS_PER_MESSAGE),
+            ))
+        }
+        WireMessage::SubscribeEvents { after_seq } => Some(WireMessage::EventsSubscribed {
+            after_seq: after_seq.unwrap_or_else(|| analytics.last_event_seq()),
+        }),
+        WireMessage::Admin(request) => {
+            let command = request.command.clone();
+            let reply = admin::execute(analytics, &server_config.admin, request, now);
+            match &reply.result {
+                Ok(outcome) => log::info!("Admin {:?} from {}: {}", command, src, outcome),
+                Err(err) => log::warn!("Admin {:?} from {} rejected: {}", command, src, err),
+            }
+            Some(WireMessage::AdminReply(reply))
+        }
+        WireMessage::Ack(_)
+        | WireMessage::Analytics(_)
+        | WireMessage::ServerInfo(_)
+        | WireMessage::AdminReply(_)
+        | WireMessage::Topology(_)
+        | WireMessage::Path(_)
+        | WireMessage::Error { .. }
+        | WireMessage::RegisterAck(_)
+        | WireMessage::UnregisterAck(_)
+        | WireMessage::AckBatch(_)
+        | WireMessage::AckModeSet(_)
+        | WireMessage::SelectiveAck(_)
+        | WireMessage::Events(_)
+        | WireMessage::EventsSubscribed { .. }
+        | WireMessage::Subscribe(_)
+        | WireMessage::Unsubscribe
+        | WireMessage::UnsubscribeEvents => {
+            log::warn!("Ignoring unexpected server-side message from {}", src);
+            Some(WireMessage::Error {
+                code: ErrorCode::UnexpectedMessage,
+                detail: "server-to-client message sent to the server".to_string(),
+                related_seq: None,
+            })
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::analytics::AnalyticsManager;
+use common::TrafficClass;
+use common::analytics::{EdgeSnapshot, NodeLiveness, NodeSnapshot, TopologySnapshot};
+use std::fmt::Write;
+use std::time::Instant;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Transport-level counters kept by the server loop.
+#[derive(Debug, Default, Clone)]
+pub struct ServerMetrics {
+    pub datagrams_received: u64,
+    pub bytes_received: u64,
+    pub decode_failures: u64,
+    pub ack_send_errors: u64,
+}
+
+/// Metric name, help text, type and value accessor for one per-item series.
+type Series<T> = (&'static str, &'static str, &'static str, fn(&T) -> f64);
+
+const ROUTE_LABELS: [(&str, &str); 4] = [
+    ("internal", "internal"),
+    ("internal", "external"),
+    ("external", "internal"),
+    ("external", "external"),
+];
+
+/// Renders node, edge, route and server metrics in the Prometheus text
+/// exposition format. Built from a read-only topology view so scrapes do
+/// not disturb snapshot subscribers.
+pub fn render(analytics: &AnalyticsManager, server: &ServerMetrics, now: Instant) -> String {
+    let snapshot = analytics.peek_topology_snapshot(now);
+    let mut out = String::new();
+    write_server_metrics(&mut out, analytics, server, &snapshot);
+    write_route_metrics(&mut out, &snapshot);
+    write_liveness_metrics(&mut out, &snapshot);
+    write_node_metrics(&mut out, &snapshot);
+    write_edge_metrics(&mut out, &snapshot);
+    out
+}
+
+fn write_server_metrics(
+    out: &mut String,
+    analytics: &AnalyticsManager,
+    server: &ServerMetrics,
+    snapshot: &TopologySnapshot,
+) {
+    let counters = [
+        (
+            "simd_datagrams_received_total",
+            "UDP datagrams received.",
+            server.datagrams_received,
+        ),
+        (
+            "simd_datagram_bytes_received_total",
+            "UDP payload bytes received.",
+            server.bytes_received,
+        ),
+        (
+            "simd_decode_failures_total",
+            "Datagrams that failed to decode.",
+            server.decode_failures,
+        ),
+        (
+            "simd_ack_send_errors_total",
+            "ACKs that could not be sent.",
+            server.ack_send_errors,
+        ),
+        (
+            "simd_snapshots_total",
+            "Topology snapshots exported.",
+            analytics.snapshot_count(),
+        ),
+    ];
+    for (name, help, value) in counters {
+        write_header(out, name, help, "counter");
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    let gauges = [
+        (
+            "simd_snapshot_build_seconds",
+            "Time spent building the last exported topology snapshot.",
+            analytics.last_snapshot_build_time().as_secs_f64(),
+        ),
+        (
+            "simd_uptime_seconds",
+            "Seconds since the analytics engine started.",
+            analytics.uptime().as_secs_f64(),
+        ),
+        (
+            "simd_nodes",
+            "Nodes currently tracked.",
+            snapshot.nodes.len() as f64,
+        ),
+        (
+            "simd_edges",
+            "Edges currently tracked.",
+            snapshot.edges.len() as f64,
+        ),
+    ];
+    for (name, help, value) in gauges {
+        write_header(out, name, help, "gauge");
+        let _ = writeln!(out, "{name} {value}");
+    }
+}
+
+fn write_route_metrics(out: &mut String, snapshot: &TopologySnapshot) {
+    let routes = &snapshot.global_stats.route_stats;
+    write_header(
+        out,
+        "simd_route_packets_total",
+        "Packets by source and destination domain.",
+        "counter",
+    );
+    for ((src, dst), stats) in ROUTE_LABELS.iter().zip(routes) {
+        let _ = writeln!(
+            out,
+            "simd_route_packets_total{{src_domain=\"{src}\",dst_domain=\"{dst}\"}} {}",
+            stats.packets
+        );
+    }
+    write_header(
+        out,
+        "simd_route_bytes_total",
+        "Bytes by source and destination domain.",
+        "counter",
+    );
+    for ((src, dst), stats) in ROUTE_LABELS.iter().zip(routes) {
+        let _ = writeln!(
+            out,
+            "simd_route_bytes_total{{src_domain=\"{src}\",dst_domain=\"{dst}\"}} {}",
+            stats.bytes
+        );
+    }
+}
+
+fn write_liveness_metrics(out: &mut String, snapshot: &TopologySnapshot) {
+    write_header(
+        out,
+        "simd_nodes_by_liveness",
+        "Nodes currently in each liveness state.",
+        "gauge",
+    );
+    for liveness in NodeLiveness::ALL {
+        if liveness == NodeLiveness::Departed {
+            continue;
+        }
+        let count = snapshot
+            .nodes
+            .iter()
+            .filter(|node| node.liveness == liveness)
+            .count();
+        let _ = writeln!(
+            out,
+            "simd_nodes_by_liveness{{liveness=\"{liveness}\"}} {count}"
+        );
+    }
+}
+
+fn write_node_metrics(out: &mut String, snapshot: &TopologySnapshot) {
+    let labels: Vec<String> = snapshot
+        .nodes
+        .iter()
+        .map(|node| {
+            let name = String::from_utf8_lossy(&node.desc);
+            format!(
+                "node=\"{}\",name=\"{}\",domain=\"{}\"",
+                format_id(&node.node_id),
+                escape_label(name.trim_end_matches('\0')),
+                node.domain
+            )
+        })
+        .collect();
+
+    let series: [Series<NodeSnapshot>; 4] = [
+        (
+            "simd_node_packets_total",
+            "Packets sent by node.",
+            "counter",
+            |node| node.total_packets as f64,
+        ),
+        (
+            "simd_node_bytes_total",
+            "Bytes sent by node.",
+            "counter",
+            |node| node.total_bytes as f64,
+        ),
+        (
+            "simd_node_packets_per_second",
+            "Node send rate over the rate window.",
+            "gauge",
+            |node| node.total_pps,
+        ),
+        (
+            "simd_node_bytes_per_second",
+            "Node byte rate over the rate window.",
+            "gauge",
+            |node| node.total_bps,
+        ),
+    ];
+    for (name, help, kind, value) in series {
+        write_header(out, name, help, kind);
+        for (node, labels) in snapshot.nodes.iter().zip(&labels) {
+            let _ = writeln!(out, "{name}{{{labels}}} {}", value(node));
+        }
+    }
+}
+
+fn write_edge_metrics(out: &mut String, snapshot: &TopologySnapshot) {
+    let labels: Vec<String> = snapshot
+        .edges
+        .iter()
+        .map(|edge| {
+            format!(
+                "src=\"{}\",dst=\"{}\",class=\"{}\"",
+                format_id(&edge.src_node_id),
+                format_id(&edge.dst_node_id),
+                class_label(edge.class)
+            )
+        })
+        .collect();
+
+    let series: [Series<EdgeSnapshot>; 7] = [
+        (
+            "simd_edge_packets_per_second",
+            "Edge packet rate over the rate window.",
+            "gauge",
+            |edge| edge.packets_per_second,
+        ),
+        (
+            "simd_edge_bytes_per_second",
+            "Edge byte rate over the rate window.",
+            "gauge",
+            |edge| edge.bytes_per_second,
+        ),
+        (
+            "simd_edge_latency_ewma_seconds",
+            "Smoothed one-way latency.",
+            "gauge",
+            |edge| edge.latency_ewma_us / 1e6,
+        ),
+        (
+            "simd_edge_jitter_ewma_seconds",
+            "Smoothed latency jitter.",
+            "gauge",
+            |edge| edge.jitter_ewma_us / 1e6,
+        ),
+        (
+            "simd_edge_loss_ratio",
+            "Share of packets missing in the current loss window.",
+            "gauge",
+            |edge| edge.loss_rate_window,
+        ),
+        (
+            "simd_edge_reverse_loss_ratio",
+            "Share of acks lost on the way back, as reported by the sender.",
+            "gauge",
+            |edge| edge.reverse_loss_rate,
+        ),
+        (
+            "simd_edge_anomaly_score",
+            "Largest deviation from the edge's learned baseline, in standard deviations.",
+            "gauge",
+            |edge| edge.anomaly.map_or(0.0, |scores| scores.max()),
+        ),
+    ];
+    for (name, help, kind, value) in series {
+        write_header(out, name, help, kind);
+        for (edge, labels) in snapshot.edges.iter().zip(&labels) {
+            let _ = writeln!(out, "{name}{{{labels}}} {}", value(edge));
+        }
+    }
+}
+
+fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn class_label(class: TrafficClass) -> &'static str {
+    match class {
+        TrafficClass::Api => "api",
+        TrafficClass::HeavyCompute => "heavy_compute",
+        TrafficClass::Background => "background",
+        TrafficClass::HealthCheck => "health_check",
+    }
+}
+
+/// Formats an id as a hyphenated UUID, matching the JSON codec.
+pub(crate) fn format_id(id: &[u8; 16]) -> String {
+    uuid::Uuid::from_bytes(*id).to_string()
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
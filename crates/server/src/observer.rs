@@ -0,0 +1,208 @@
+use crate::analytics::AnalyticsManager;
+use crate::metrics::format_id;
+use common::config::ObserversConfig;
+use common::events::{TopologyEvent, TopologyEventKind};
+use common::{
+    DataPacket, EdgeId, NodeId, RegisterNodePacket, RegisterOutcome, TrafficClass,
+    UnregisterNodePacket, UnregisterOutcome,
+};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+/// An edge's metrics as computed by `AnalyticsManager`, handed to
+/// observers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeMetrics {
+    pub edge_id: EdgeId,
+    pub src_node_id: NodeId,
+    pub dst_node_id: NodeId,
+    pub class: TrafficClass,
+    pub packets: u64,
+    pub bytes: u64,
+    pub latency_ewma_us: f64,
+    pub jitter_ewma_us: f64,
+    /// Latest one-way latency sample. In `on_packet`, `None` unless that
+    /// packet carried a usable timestamp.
+    pub last_latency_us: Option<f64>,
+    /// Packets received and missing in the current rate window.
+    pub window_packets: u64,
+    pub window_missing: u64,
+    pub reverse_loss_rate: f64,
+}
+
+impl EdgeMetrics {
+    /// Missing per received packet in the current window, as reported in
+    /// `EdgeSnapshot::loss_rate_window`.
+    pub fn window_loss_rate(&self) -> f64 {
+        if self.window_packets == 0 {
+            0.0
+        } else {
+            self.window_missing as f64 / self.window_packets as f64
+        }
+    }
+}
+
+/// Sees every data packet after it has been applied.
+pub trait PacketObserver: Send {
+    fn on_packet(&mut self, packet: &DataPacket, edge: &EdgeMetrics, now: Instant);
+}
+
+/// Sees node lifecycle changes and every cleanup sweep. All methods default
+/// to doing nothing.
+pub trait TopologyObserver: Send {
+    /// Also called for retransmissions, with the replayed outcome.
+    fn on_node_registered(
+        &mut self,
+        _packet: &RegisterNodePacket,
+        _outcome: RegisterOutcome,
+        _now: Instant,
+    ) {
+    }
+
+    fn on_node_unregistered(
+        &mut self,
+        _packet: &UnregisterNodePacket,
+        _outcome: UnregisterOutcome,
+        _now: Instant,
+    ) {
+    }
+
+    fn on_cleanup(&mut self, _sweep: &CleanupSweep<'_>, _now: Instant) {}
+}
+
+/// What a cleanup sweep removed, plus the metrics of every edge left.
+pub struct CleanupSweep<'a> {
+    pub removed_nodes: &'a [NodeId],
+    pub removed_edges: &'a [EdgeId],
+    pub edges: &'a [EdgeMetrics],
+}
+
+/// Observers registered with an `AnalyticsManager`, called in registration
+/// order.
+#[derive(Default)]
+pub struct Observers {
+    pub(crate) packet: Vec<Box<dyn PacketObserver>>,
+    pub(crate) topology: Vec<Box<dyn TopologyObserver>>,
+}
+
+/// Registers the built-in observers enabled in `config`.
+pub fn install(analytics: &mut AnalyticsManager, config: &ObserversConfig) -> io::Result<()> {
+    if let Some(path) = &config.edge_csv {
+        analytics.add_topology_observer(EdgeCsvExporter::create(path)?);
+        log::info!("Writing edge metrics to {}", path);
+    }
+    if let Some(path) = &config.alert_log {
+        let mut log = AlertLog::create(path)?;
+        analytics.add_event_listener(move |event| log.record(event));
+        log::info!("Writing alerts to {}", path);
+    }
+    if config.slow_packet_us > 0 {
+        analytics.add_packet_observer(SlowPacketLogger {
+            threshold_us: config.slow_packet_us as f64,
+        });
+    }
+    Ok(())
+}
+
+/// Appends one CSV row per edge after every cleanup sweep.
+pub struct EdgeCsvExporter {
+    out: BufWriter<File>,
+}
+
+impl EdgeCsvExporter {
+    pub const HEADER: &'static str = "epoch_us,edge_id,src_node_id,dst_node_id,class,packets,bytes,latency_ewma_us,jitter_ewma_us,window_loss_rate,reverse_loss_rate";
+
+    /// Appends to `path`, writing the header first if the file is new or
+    /// empty.
+    pub fn create(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let empty = file.metadata()?.len() == 0;
+        let mut out = BufWriter::new(file);
+        if empty {
+            writeln!(out, "{}", Self::HEADER)?;
+            out.flush()?;
+        }
+        Ok(Self { out })
+    }
+
+    fn write_sweep(&mut self, sweep: &CleanupSweep<'_>) -> io::Result<()> {
+        let epoch_us = common::now_timestamp_us();
+        for edge in sweep.edges {
+            writeln!(
+                self.out,
+                "{},{},{},{},{},{},{},{:.1},{:.1},{:.4},{:.4}",
+                epoch_us,
+                format_id(&edge.edge_id),
+                format_id(&edge.src_node_id),
+                format_id(&edge.dst_node_id),
+                edge.class,
+                edge.packets,
+                edge.bytes,
+                edge.latency_ewma_us,
+                edge.jitter_ewma_us,
+                edge.window_loss_rate(),
+                edge.reverse_loss_rate
+            )?;
+        }
+        self.out.flush()
+    }
+}
+
+impl TopologyObserver for EdgeCsvExporter {
+    fn on_cleanup(&mut self, sweep: &CleanupSweep<'_>, _now: Instant) {
+        if let Err(err) = self.write_sweep(sweep) {
+            log::warn!("Failed to write edge metrics: {}", err);
+        }
+    }
+}
+
+/// Appends one JSON line per alert that goes pending, fires or resolves.
+pub struct AlertLog {
+    out: BufWriter<File>,
+}
+
+impl AlertLog {
+    pub fn create(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            out: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, event: &TopologyEvent) {
+        let TopologyEventKind::AlertChanged { alert } = &event.kind else {
+            return;
+        };
+        let written = serde_json::to_writer(&mut self.out, alert)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(self.out))
+            .and_then(|()| self.out.flush());
+        if let Err(err) = written {
+            log::warn!("Failed to write alert log: {}", err);
+        }
+    }
+}
+
+/// Logs packets whose one-way latency exceeds `threshold_us`.
+pub struct SlowPacketLogger {
+    pub threshold_us: f64,
+}
+
+impl PacketObserver for SlowPacketLogger {
+    fn on_packet(&mut self, packet: &DataPacket, edge: &EdgeMetrics, _now: Instant) {
+        if let Some(latency_us) = edge.last_latency_us
+            && latency_us > self.threshold_us
+        {
+            log::warn!(
+                "Slow packet seq={} {:?} -> {:?} class={}: {:.0} us (edge EWMA {:.0} us)",
+                packet.global_seq,
+                packet.src_node_id,
+                packet.dst_node_id,
+                packet.class,
+                latency_us,
+                edge.latency_ewma_us
+            );
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use common::NodeId;
+use common::analytics::{EdgeSnapshot, TopologyQuery, TopologySnapshot};
+use std::collections::{HashMap, HashSet};
+
+/// Applies a `TopologyQuery` to a snapshot.
+///
+/// Edge filters (class, rate, loss) run first. When any of them is set, nodes
+/// are pruned to the endpoints of the surviving edges so a dashboard asking
+/// for "lossy api edges" does not receive every idle node. Node filters
+/// (domain, activity) then drop nodes and the edges touching them, and the
+/// neighborhood is walked over what is left, so it never reaches a node
+/// through a filtered-out hop. Removed ids, tombstones and global stats are
+/// passed through untouched.
+pub fn filter_snapshot(mut snapshot: TopologySnapshot, query: &TopologyQuery) -> TopologySnapshot {
+    snapshot
+        .edges
+        .retain(|edge| edge_matches(edge, query) && (!query.active_only || edge.active));
+
+    let edge_filtered = !query.classes.is_empty()
+        || query.min_packets_per_second.is_some()
+        || query.min_loss_rate.is_some();
+    let edge_endpoints: HashSet<NodeId> = snapshot
+        .edges
+        .iter()
+        .flat_map(|edge| [edge.src_node_id, edge.dst_node_id])
+        .collect();
+
+    snapshot.nodes.retain(|node| {
+        (!edge_filtered || edge_endpoints.contains(&node.node_id))
+            && (query.domains.is_empty() || query.domains.contains(&node.domain))
+            && (!query.active_only || node.active)
+    });
+
+    retain_edges_between_nodes(&mut snapshot);
+
+    if !query.node_ids.is_empty() {
+        let neighborhood = neighborhood(&snapshot.edges, &query.node_ids, query.hops);
+        snapshot
+            .nodes
+            .retain(|node| neighborhood.contains(&node.node_id));
+        retain_edges_between_nodes(&mut snapshot);
+    }
+
+    snapshot
+}
+
+fn retain_edges_between_nodes(snapshot: &mut TopologySnapshot) {
+    let kept: HashSet<NodeId> = snapshot.nodes.iter().map(|node| node.node_id).collect();
+    snapshot
+        .edges
+        .retain(|edge| kept.contains(&edge.src_node_id) && kept.contains(&edge.dst_node_id));
+}
+
+fn edge_matches(edge: &EdgeSnapshot, query: &TopologyQuery) -> bool {
+    (query.classes.is_empty() || query.classes.contains(&edge.class))
+        && query
+            .min_packets_per_second
+            .is_none_or(|min| edge.packets_per_second >= min)
+        && query
+            .min_loss_rate
+            .is_none_or(|min| edge.loss_rate_window >= min)
+}
+
+/// Nodes within `hops` edges of any seed, treating edges as undirected.
+fn neighborhood(edges: &[EdgeSnapshot], seeds: &[NodeId], hops: u8) -> HashSet<NodeId> {
+    let mut adjacency: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    for edge in edges {
+        adjacency
+            .entry(edge.src_node_id)
+            .or_default()
+            .push(edge.dst_node_id);
+        adjacency
+            .entry(edge.dst_node_id)
+            .or_default()
+            .push(edge.src_node_id);
+    }
+
+    let mut reached: HashSet<NodeId> = seeds.iter().copied().collect();
+    let mut frontier: Vec<NodeId> = seeds.to_vec();
+    for _ in 0..hops {
+        let mut next = Vec::new();
+        for node_id in frontier {
+            for neighbor in adjacency.get(&node_id).into_iter().flatten() {
+                if reached.insert(*neighbor) {
+                    next.push(*neighbor);
+                }
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+        frontier = next;
+    }
+    reached
+}
@@ -0,0 +1,294 @@
+use common::analytics::{
+    EdgeSnapshot, NodeLiveness, NodeSnapshot, TopologySnapshot, TopologySubscription,
+};
+use common::events::EventBatch;
+use common::{Codec, EdgeId, NodeId};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// UDP has no connection to notice a subscriber going away, so UDP
+/// subscriptions are leases the client renews by subscribing again.
+pub const SUBSCRIPTION_LEASE: Duration = Duration::from_secs(60);
+/// Most UDP subscribers of one kind; further subscriptions are refused.
+pub const MAX_UDP_SUBSCRIBERS: usize = 256;
+
+/// A client receiving periodic topology pushes.
+#[derive(Debug, Clone, Copy)]
+pub struct Subscriber {
+    pub codec: Codec,
+    pub subscription: TopologySubscription,
+    /// Set once the subscriber holds a full baseline to apply deltas to.
+    synced: bool,
+}
+
+impl Subscriber {
+    pub fn new(codec: Codec, subscription: TopologySubscription) -> Self {
+        Self {
+            codec,
+            subscription,
+            synced: false,
+        }
+    }
+
+    /// Applies a repeated `Subscribe`, keeping the baseline it already has.
+    pub fn renew(&mut self, codec: Codec, subscription: TopologySubscription) {
+        self.codec = codec;
+        self.subscription = subscription;
+    }
+}
+
+/// A client receiving topology events as they happen.
+#[derive(Debug, Clone, Copy)]
+pub struct EventSubscriber {
+    pub codec: Codec,
+    /// Sequence number of the last event sent.
+    pub cursor: u64,
+}
+
+impl EventSubscriber {
+    pub fn new(codec: Codec, cursor: u64) -> Self {
+        Self { codec, cursor }
+    }
+
+    /// Events after the cursor, in batches fetched from `events_since`,
+    /// advancing the cursor past each one. Empty once caught up with
+    /// `last_seq`.
+    pub fn drain(
+        &mut self,
+        last_seq: u64,
+        events_since: impl Fn(u64) -> EventBatch,
+    ) -> Vec<EventBatch> {
+        let mut batches = Vec::new();
+        while self.cursor < last_seq {
+            let batch = events_since(self.cursor);
+            let Some(last) = batch.events.last() else {
+                self.cursor = last_seq;
+                break;
+            };
+            self.cursor = last.seq;
+            batches.push(batch);
+        }
+        batches
+    }
+}
+
+/// UDP subscribers keyed by address, each holding a lease that expires
+/// `SUBSCRIPTION_LEASE` after its latest subscribe.
+pub struct LeasedSubscribers<T> {
+    entries: HashMap<SocketAddr, (T, Instant)>,
+}
+
+impl<T> Default for LeasedSubscribers<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<T> LeasedSubscribers<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts or renews the lease of `addr`. A known subscriber is passed to
+    /// `renew`, a new one built with `subscriber`. Returns false, leaving
+    /// everything untouched, when a new subscriber would exceed
+    /// `MAX_UDP_SUBSCRIBERS`.
+    pub fn subscribe(
+        &mut self,
+        addr: SocketAddr,
+        now: Instant,
+        subscriber: impl FnOnce() -> T,
+        renew: impl FnOnce(&mut T),
+    ) -> bool {
+        let expires_at = now + SUBSCRIPTION_LEASE;
+        if let Some((existing, lease)) = self.entries.get_mut(&addr) {
+            renew(existing);
+            *lease = expires_at;
+            return true;
+        }
+        if self.entries.len() >= MAX_UDP_SUBSCRIBERS {
+            return false;
+        }
+        self.entries.insert(addr, (subscriber(), expires_at));
+        true
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) -> Option<T> {
+        self.entries.remove(addr).map(|(subscriber, _)| subscriber)
+    }
+
+    /// Drops subscribers whose lease ran out; returns their addresses.
+    pub fn expire(&mut self, now: Instant) -> Vec<SocketAddr> {
+        let expired: Vec<SocketAddr> = self
+            .entries
+            .iter()
+            .filter(|(_, (_, expires_at))| *expires_at <= now)
+            .map(|(addr, _)| *addr)
+            .collect();
+        for addr in &expired {
+            self.entries.remove(addr);
+        }
+        expired
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (SocketAddr, &mut T)> {
+        self.entries
+            .iter_mut()
+            .map(|(addr, (subscriber, _))| (*addr, subscriber))
+    }
+}
+
+/// Snapshot pair produced once per push tick and shared by every subscriber,
+/// so removed ids reach all of them rather than whoever asked first.
+pub struct TopologyPush {
+    pub full: TopologySnapshot,
+    pub delta: TopologySnapshot,
+}
+
+impl TopologyPush {
+    /// Snapshot to send to `subscriber`. Delta subscribers get a full
+    /// snapshot on their first push, since they joined after the baseline.
+    pub fn for_subscriber(&self, subscriber: &mut Subscriber) -> &TopologySnapshot {
+        let synced = std::mem::replace(&mut subscriber.synced, true);
+        if subscriber.subscription.delta && synced {
+            &self.delta
+        } else {
+            &self.full
+        }
+    }
+}
+
+/// The parts of a node whose change puts it in a delta push.
+type NodeState = (u64, bool, NodeLiveness, f64, u64);
+/// The parts of an edge whose change puts it in a delta push.
+type EdgeState = (u64, bool, f64, f64, f64);
+
+fn node_state(node: &NodeSnapshot) -> NodeState {
+    (
+        node.last_seen_us,
+        node.active,
+        node.liveness,
+        node.total_pps,
+        node.loss.missing_sequences,
+    )
+}
+
+fn edge_state(edge: &EdgeSnapshot) -> EdgeState {
+    (
+        edge.packets,
+        edge.active,
+        edge.packets_per_second,
+        edge.loss_rate_window,
+        edge.reverse_loss_rate,
+    )
+}
+
+/// Remembers what the previous push contained so delta subscribers only
+/// receive nodes and edges that changed.
+#[derive(Default)]
+pub struct DeltaTracker {
+    nodes: HashMap<NodeId, NodeState>,
+    edges: HashMap<EdgeId, EdgeState>,
+    change_seq: u64,
+}
+
+impl DeltaTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `change_seq` of the previous push, the cursor for the next one.
+    pub fn change_seq(&self) -> u64 {
+        self.change_seq
+    }
+
+    /// Builds the push for `snapshot` and makes it the new baseline. A node
+    /// changed if it is new, was seen again or changed activity, liveness,
+    /// packet rate or loss; an edge if it is new, carried packets or changed
+    /// activity, packet rate or loss.
+    pub fn push(&mut self, snapshot: TopologySnapshot) -> TopologyPush {
+        let mut delta = snapshot.clone();
+        delta
+            .nodes
+            .retain(|node| self.nodes.get(&node.node_id) != Some(&node_state(node)));
+        delta
+            .edges
+            .retain(|edge| self.edges.get(&edge.edge_id) != Some(&edge_state(edge)));
+
+        self.nodes = snapshot
+            .nodes
+            .iter()
+            .map(|node| (node.node_id, node_state(node)))
+            .collect();
+        self.edges = snapshot
+            .edges
+            .iter()
+            .map(|edge| (edge.edge_id, edge_state(edge)))
+            .collect();
+        self.change_seq = snapshot.change_seq;
+
+        TopologyPush {
+            full: snapshot,
+            delta,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::AnalyticsManager;
+    use common::TrafficClass;
+
+    #[test]
+    fn leases_renew_expire_and_cap_new_subscribers() {
+        let now = Instant::now();
+        let mut subscribers = LeasedSubscribers::new();
+        let addr = |port: u16| SocketAddr::from(([127, 0, 0, 1], port));
+        for port in 0..MAX_UDP_SUBSCRIBERS as u16 {
+            assert!(subscribers.subscribe(addr(port), now, || port, |_| {}));
+        }
+        assert!(!subscribers.subscribe(addr(9999), now, || 0, |_| {}));
+
+        // Renewing keeps the subscriber and pushes its lease out.
+        let later = now + SUBSCRIPTION_LEASE / 2;
+        assert!(subscribers.subscribe(addr(0), later, || 0, |port| *port += 1000));
+        let expired = subscribers.expire(now + SUBSCRIPTION_LEASE);
+        assert_eq!(expired.len(), MAX_UDP_SUBSCRIBERS - 1);
+        let remaining: Vec<_> = subscribers.iter_mut().map(|(a, port)| (a, *port)).collect();
+        assert_eq!(remaining, vec![(addr(0), 1000)]);
+        assert!(subscribers.subscribe(addr(9999), later, || 0, |_| {}));
+    }
+
+    #[test]
+    fn delta_includes_edges_whose_rate_decays_without_new_packets() {
+        let mut analytics = AnalyticsManager::new(5, 100);
+        let now = Instant::now();
+        let addr = SocketAddr::from(([127, 0, 0, 1], 9000));
+        let (src, dst) = (*b"NODE-DELTA-0000A", *b"NODE-DELTA-0000B");
+        let packet =
+            common::make_data_packet(src, dst, 0, 1, TrafficClass::Api, 100, *b"delta-node------");
+        analytics.on_packet_received(addr, &packet, now);
+
+        let mut tracker = DeltaTracker::new();
+        let first = tracker.push(analytics.peek_topology_snapshot(now));
+        assert_eq!(first.delta.edges.len(), 1);
+        let unchanged = tracker.push(analytics.peek_topology_snapshot(now));
+        assert!(unchanged.delta.edges.is_empty());
+
+        let quiet = tracker.push(analytics.peek_topology_snapshot(now + Duration::from_secs(10)));
+        assert_eq!(quiet.delta.edges.len(), 1);
+        assert_eq!(quiet.delta.edges[0].packets_per_second, 0.0);
+    }
+}
@@ -0,0 +1,8 @@
+const VIEWER_HTML: &str = include_str!("../static/viewer.html");
+
+/// The embedded reference visualizer. The page subscribes to delta pushes on
+/// the WebSocket listener at `ws_port` on the host it was served from.
+pub fn viewer_page(ws_port: Option<u16>) -> String {
+    let ws_port = ws_port.map_or_else(|| "null".to_string(), |port| port.to_string());
+    VIEWER_HTML.replace("__WS_PORT__", &ws_port)
+}
@@ -0,0 +1,201 @@
+use crate::subscription::{EventSubscriber, Subscriber, TopologyPush};
+use common::events::EventBatch;
+use common::{Codec, ErrorCode, WireMessage};
+use std::io::{ErrorKind, Result};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+/// Longest a connecting browser may stall the server loop during the
+/// opening handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Optional WebSocket front door for browser visualizers.
+///
+/// Connections speak the same `WireMessage` protocol as UDP clients: text
+/// frames carry JSON, binary frames carry postcard, and replies use the
+/// frame type of the request. Everything is non-blocking so it can be polled
+/// from the UDP loop.
+pub struct WebSocketServer {
+    listener: TcpListener,
+    connections: Vec<Connection>,
+}
+
+struct Connection {
+    peer: SocketAddr,
+    socket: WebSocket<TcpStream>,
+    subscriber: Option<Subscriber>,
+    event_subscriber: Option<EventSubscriber>,
+}
+
+impl WebSocketServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            connections: Vec::new(),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn connection_count(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Accepts new connections and answers every pending frame. `Subscribe`
+    /// and the two unsubscribes are handled here; other requests go to
+    /// `handle`, whose reply (if any) is sent back on the same connection.
+    /// An `EventsSubscribed` reply starts event pushes from its cursor.
+    pub fn poll(&mut self, mut handle: impl FnMut(WireMessage, SocketAddr) -> Option<WireMessage>) {
+        self.accept_pending();
+
+        self.connections.retain_mut(|connection| {
+            loop {
+                let (codec, bytes) = match connection.socket.read() {
+                    Ok(Message::Text(text)) => (Codec::Json, text.as_bytes().to_vec()),
+                    Ok(Message::Binary(bytes)) => (Codec::Postcard, bytes.to_vec()),
+                    Ok(_) => continue,
+                    Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => {
+                        return flush(&mut connection.socket);
+                    }
+                    Err(_) => return false,
+                };
+                let reply = match codec.decode_message(&bytes) {
+                    Err(err) => {
+                        log::warn!(
+                            "Failed to decode WebSocket frame from {}: {}",
+                            connection.peer,
+                            err
+                        );
+                        Some(WireMessage::Error {
+                            code: ErrorCode::DecodeFailed,
+                            detail: format!("could not decode {}-byte frame", bytes.len()),
+                            related_seq: None,
+                        })
+                    }
+                    Ok(WireMessage::Subscribe(subscription)) => {
+                        connection.subscriber = Some(Subscriber::new(codec, subscription));
+                        None
+                    }
+                    Ok(WireMessage::Unsubscribe) => {
+                        connection.subscriber = None;
+                        None
+                    }
+                    Ok(WireMessage::UnsubscribeEvents) => {
+                        connection.event_subscriber = None;
+                        None
+                    }
+                    Ok(message) => handle(message, connection.peer),
+                };
+                if let Some(WireMessage::EventsSubscribed { after_seq }) = reply {
+                    connection.event_subscriber = Some(EventSubscriber::new(codec, after_seq));
+                }
+                if let Some(reply) = reply
+                    && !send(&mut connection.socket, codec, &reply)
+                {
+                    return false;
+                }
+            }
+        });
+    }
+
+    /// Sends the snapshot matching each subscriber's mode. Returns how many
+    /// connections were pushed to.
+    pub fn push(&mut self, push: &TopologyPush) -> usize {
+        let mut pushed = 0;
+        self.connections.retain_mut(|connection| {
+            let Some(subscriber) = connection.subscriber.as_mut() else {
+                return true;
+            };
+            let codec = subscriber.codec;
+            let snapshot = push.for_subscriber(subscriber).clone();
+            pushed += 1;
+            send(
+                &mut connection.socket,
+                codec,
+                &WireMessage::Topology(snapshot),
+            )
+        });
+        pushed
+    }
+
+    /// Sends each event subscriber the events after its cursor, up to
+    /// `last_seq`.
+    pub fn push_events(&mut self, last_seq: u64, events_since: impl Fn(u64) -> EventBatch) {
+        self.connections.retain_mut(|connection| {
+            let Some(subscriber) = connection.event_subscriber.as_mut() else {
+                return true;
+            };
+            let codec = subscriber.codec;
+            subscriber
+                .drain(last_seq, &events_since)
+                .into_iter()
+                .all(|batch| send(&mut connection.socket, codec, &WireMessage::Events(batch)))
+        });
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.connections
+            .iter()
+            .any(|connection| connection.subscriber.is_some())
+    }
+
+    fn accept_pending(&mut self) {
+        while let Ok((stream, peer)) = self.listener.accept() {
+            match handshake(stream) {
+                Ok(socket) => {
+                    log::info!("WebSocket client connected from {}", peer);
+                    self.connections.push(Connection {
+                        peer,
+                        socket,
+                        subscriber: None,
+                        event_subscriber: None,
+                    });
+                }
+                Err(err) => log::warn!("WebSocket handshake with {} failed: {}", peer, err),
+            }
+        }
+    }
+}
+
+fn handshake(stream: TcpStream) -> Result<WebSocket<TcpStream>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let socket =
+        tungstenite::accept(stream).map_err(|err| std::io::Error::other(err.to_string()))?;
+    socket.get_ref().set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Queues `message` and tries to flush it. Returns `false` once the
+/// connection is unusable.
+fn send(socket: &mut WebSocket<TcpStream>, codec: Codec, message: &WireMessage) -> bool {
+    let Ok(bytes) = codec.encode_message(message) else {
+        return true;
+    };
+    let frame = match codec {
+        Codec::Json => match String::from_utf8(bytes) {
+            Ok(text) => Message::text(text),
+            Err(_) => return true,
+        },
+        Codec::Postcard => Message::binary(bytes),
+    };
+    match socket.write(frame) {
+        Ok(()) => flush(socket),
+        Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => true,
+        Err(_) => false,
+    }
+}
+
+fn flush(socket: &mut WebSocket<TcpStream>) -> bool {
+    match socket.flush() {
+        Ok(()) => true,
+        Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => true,
+        Err(_) => false,
+    }
+}
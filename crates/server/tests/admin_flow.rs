@@ -0,0 +1,158 @@
+use common::admin::{AdminCommand, AdminError, AdminRequest, Checkpoint};
+use common::config::{AdminConfig, ClassTtls};
+use common::{Codec, NodeDomain, NodeId, TrafficClass, WireMessage};
+use server::admin;
+use server::analytics::AnalyticsManager;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Instant;
+
+const SRC_NODE_ID: NodeId = *b"ADMIN-FLOW-SRC-N";
+const DST_NODE_ID: NodeId = *b"ADMIN-FLOW-DST-N";
+const TOKEN: &str = "s3cret";
+
+fn seeded_analytics(now: Instant) -> AnalyticsManager {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let addr = SocketAddr::from_str("127.0.0.1:59094").expect("valid socket");
+    let desc = *b"admin-flow-node-";
+    analytics.on_node_registered(
+        &common::make_register_node_packet(SRC_NODE_ID, desc, NodeDomain::Internal),
+        addr,
+        now,
+    );
+    let packet =
+        common::make_data_packet(SRC_NODE_ID, DST_NODE_ID, 1, 1, TrafficClass::Api, 100, desc);
+    analytics.on_packet_received(addr, &packet, now);
+    analytics
+}
+
+fn request(token: &str, command: AdminCommand) -> AdminRequest {
+    AdminRequest {
+        token: token.to_string(),
+        request_id: 7,
+        command,
+    }
+}
+
+#[test]
+fn admin_commands_require_a_configured_matching_token() {
+    let now = Instant::now();
+    let mut analytics = seeded_analytics(now);
+
+    let disabled = admin::execute(
+        &mut analytics,
+        &AdminConfig::default(),
+        request(TOKEN, AdminCommand::ResetCounters),
+        now,
+    );
+    assert_eq!(disabled.result, Err(AdminError::Disabled));
+
+    let config = AdminConfig {
+        token: Some(TOKEN.to_string()),
+        ..AdminConfig::default()
+    };
+    let wrong = admin::execute(
+        &mut analytics,
+        &config,
+        request("guess", AdminCommand::ResetCounters),
+        now,
+    );
+    assert_eq!(wrong.request_id, 7);
+    assert_eq!(wrong.result, Err(AdminError::Unauthorized));
+    assert_eq!(analytics.global_stats().total_packets, 1);
+}
+
+#[test]
+fn admin_commands_change_live_state_and_report_outcomes() {
+    let now = Instant::now();
+    let mut analytics = seeded_analytics(now);
+    let checkpoint_dir = std::env::temp_dir().join(format!("simd-admin-{}", std::process::id()));
+    std::fs::create_dir_all(&checkpoint_dir).expect("checkpoint dir");
+    let config = AdminConfig {
+        token: Some(TOKEN.to_string()),
+        checkpoint_dir: checkpoint_dir.display().to_string(),
+    };
+    let mut run = |command| admin::execute(&mut analytics, &config, request(TOKEN, command), now);
+
+    assert!(run(AdminCommand::ResetCounters).result.is_ok());
+    let checkpoint_path = run(AdminCommand::Checkpoint).result.expect("checkpoint");
+    let invalid = run(AdminCommand::SetWindow { window_secs: 0 }).result;
+    assert!(matches!(invalid, Err(AdminError::Invalid(_))));
+    let invalid = run(AdminCommand::SetTtls {
+        node_ttl_secs: Some(0),
+        edge_ttl_secs: None,
+        edge_ttl_secs_by_class: ClassTtls::default(),
+    })
+    .result;
+    assert!(
+        matches!(invalid, Err(AdminError::Invalid(reason)) if reason.contains("node_ttl_secs"))
+    );
+    assert!(
+        run(AdminCommand::SetTtls {
+            node_ttl_secs: None,
+            edge_ttl_secs: Some(12),
+            edge_ttl_secs_by_class: ClassTtls {
+                api: Some(4),
+                ..ClassTtls::default()
+            },
+        })
+        .result
+        .is_ok()
+    );
+    assert!(
+        run(AdminCommand::SetWindow { window_secs: 2 })
+            .result
+            .is_ok()
+    );
+    assert_eq!(
+        run(AdminCommand::EvictNode(*b"NOT-A-KNOWN-NODE")).result,
+        Err(AdminError::NotFound)
+    );
+    assert!(run(AdminCommand::EvictNode(SRC_NODE_ID)).result.is_ok());
+    assert_eq!(
+        run(AdminCommand::NewEpoch).result.as_deref(),
+        Ok("epoch 1 started")
+    );
+
+    let checkpoint: Checkpoint =
+        serde_json::from_slice(&std::fs::read(&checkpoint_path).expect("checkpoint file"))
+            .expect("checkpoint json");
+    std::fs::remove_dir_all(&checkpoint_dir).ok();
+    assert_eq!(checkpoint.epoch, 0);
+    assert_eq!(checkpoint.topology.edges.len(), 1);
+    assert_eq!(
+        checkpoint.topology.global_stats.total_packets, 0,
+        "taken after the reset"
+    );
+
+    let settings = analytics.config();
+    assert_eq!(settings.edge_ttl_secs, 12);
+    assert_eq!(settings.edge_ttl_secs_by_class.api, Some(4));
+    assert_eq!(settings.window_secs, 2);
+
+    let snapshot = analytics.export_topology_snapshot(now);
+    assert!(snapshot.nodes.is_empty());
+    assert!(snapshot.edges.is_empty());
+    assert!(snapshot.removed_nodes.contains(&SRC_NODE_ID));
+    assert!(snapshot.removed_nodes.contains(&DST_NODE_ID));
+    assert_eq!(snapshot.removed_edges.len(), 1);
+    assert_eq!(snapshot.global_stats.epoch, 1);
+}
+
+#[test]
+fn admin_requests_round_trip_as_json_with_uuid_ids() {
+    let message = WireMessage::Admin(request(TOKEN, AdminCommand::EvictNode(SRC_NODE_ID)));
+    let bytes = Codec::Json.encode_message(&message).expect("encode");
+    let text = String::from_utf8(bytes.clone()).expect("utf8");
+    let uuid = uuid::Uuid::from_bytes(SRC_NODE_ID).to_string();
+    assert!(
+        text.contains(&format!("{{\"EvictNode\":\"{uuid}\"}}")),
+        "{text}"
+    );
+    match Codec::detect(&bytes).decode_message(&bytes) {
+        Ok(WireMessage::Admin(decoded)) => {
+            assert_eq!(decoded.command, AdminCommand::EvictNode(SRC_NODE_ID))
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+}
@@ -0,0 +1,192 @@
+use common::{NodeDomain, NodeId, TrafficClass};
+use server::analytics::AnalyticsManager;
+use server::api;
+use server::http::{HttpRequest, HttpResponse, HttpServer};
+use server::metrics::{self, ServerMetrics};
+use server::viewer::viewer_page;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Issues `GET path` from a helper thread while the test thread polls the
+/// server, and returns the raw response.
+fn get(
+    server: &mut HttpServer,
+    path: &str,
+    mut handle: impl FnMut(&HttpRequest) -> HttpResponse,
+) -> String {
+    let addr: SocketAddr = server.local_addr().expect("local addr");
+    let request = format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\n\r\n");
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).expect("connect");
+        stream.write_all(request.as_bytes()).expect("send request");
+        let mut response = String::new();
+        stream.read_to_string(&mut response).expect("read response");
+        response
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !client.is_finished() {
+        assert!(Instant::now() < deadline, "http request timed out");
+        server.poll(&mut handle);
+        thread::sleep(Duration::from_millis(5));
+    }
+    client.join().expect("client thread")
+}
+
+const SRC_NODE_ID: NodeId = *b"HTTP-API-SRC-NOD";
+const DST_NODE_ID: NodeId = *b"HTTP-API-DST-NOD";
+
+fn routes(request: &HttpRequest) -> HttpResponse {
+    match request.path.as_str() {
+        "/" => HttpResponse::ok("text/html; charset=utf-8", viewer_page(Some(8081))),
+        _ => HttpResponse::not_found(),
+    }
+}
+
+#[test]
+fn serves_embedded_viewer_and_404s_unknown_paths() {
+    let mut server = HttpServer::bind("127.0.0.1:0").expect("bind http");
+
+    let page = get(&mut server, "/?refresh=1", routes);
+    assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(page.contains("Content-Type: text/html"));
+    assert!(page.contains("const WS_PORT = 8081;"));
+    assert!(page.contains("Subscribe: { delta: true }"));
+
+    let missing = get(&mut server, "/nope", routes);
+    assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
+
+fn seeded_analytics(now: Instant) -> AnalyticsManager {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let addr = SocketAddr::from_str("127.0.0.1:59092").expect("valid socket");
+    let desc = *b"http-api-node---";
+    analytics.on_node_registered(
+        &common::make_register_node_packet(SRC_NODE_ID, desc, NodeDomain::Internal),
+        addr,
+        now,
+    );
+    for seq in 1..=3 {
+        let packet = common::make_data_packet(
+            SRC_NODE_ID,
+            DST_NODE_ID,
+            seq,
+            seq,
+            TrafficClass::Api,
+            100,
+            desc,
+        );
+        analytics.on_packet_received(addr, &packet, now);
+    }
+    analytics
+}
+
+fn body(response: &str) -> serde_json::Value {
+    let (_, body) = response.split_once("\r\n\r\n").expect("response body");
+    serde_json::from_str(body).expect("json body")
+}
+
+#[test]
+fn api_serves_topology_nodes_edges_stats_and_health_without_side_effects() {
+    let now = Instant::now();
+    let mut analytics = seeded_analytics(now);
+    let seq_before = analytics.export_topology_snapshot(now).snapshot_seq;
+
+    let mut server = HttpServer::bind("127.0.0.1:0").expect("bind http");
+    let mut get_api = |path: &str| {
+        get(&mut server, path, |request| {
+            api::handle(&analytics, request, now).unwrap_or_else(HttpResponse::not_found)
+        })
+    };
+    let src_uuid = uuid::Uuid::from_bytes(SRC_NODE_ID).to_string();
+
+    let topology = get_api("/topology");
+    assert!(topology.contains("Content-Type: application/json"));
+    let topology = body(&topology);
+    assert_eq!(topology["snapshot_seq"], seq_before);
+    assert_eq!(topology["edges"].as_array().expect("edges").len(), 1);
+    let edge_id = topology["edges"][0]["edge_id"]
+        .as_str()
+        .expect("uuid edge id")
+        .to_string();
+
+    let filtered = body(&get_api("/topology?class=background&active_only=true"));
+    assert!(filtered["edges"].as_array().expect("edges").is_empty());
+    let bad = get_api("/topology?class=bulk");
+    assert!(bad.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+    let node = body(&get_api(&format!("/nodes/{src_uuid}")));
+    assert_eq!(node["node_id"], src_uuid.as_str());
+    assert_eq!(node["total_packets"], 3);
+    let edge = body(&get_api(&format!("/edges/{edge_id}")));
+    assert_eq!(edge["packets"], 3);
+    assert_eq!(
+        edge["dst_node_id"],
+        uuid::Uuid::from_bytes(DST_NODE_ID).to_string()
+    );
+    let unknown = get_api("/nodes/00000000-0000-0000-0000-000000000000");
+    assert!(unknown.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    let signed = get_api("/nodes/+0000000-0000-0000-0000-000000000000");
+    assert!(signed.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+    assert_eq!(body(&get_api("/stats"))["total_packets"], 3);
+    let health = body(&get_api("/health"));
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["nodes"], 2);
+    assert_eq!(health["edges"], 1);
+
+    assert_eq!(
+        analytics.export_topology_snapshot(now).snapshot_seq,
+        seq_before + 1,
+        "API reads must not advance the snapshot sequence"
+    );
+}
+
+#[test]
+fn metrics_endpoint_exposes_edge_node_route_and_server_series() {
+    let now = Instant::now();
+    let mut analytics = seeded_analytics(now);
+    let _ = analytics.export_topology_snapshot(now);
+    let server_metrics = ServerMetrics {
+        datagrams_received: 4,
+        bytes_received: 400,
+        decode_failures: 1,
+        ack_send_errors: 0,
+    };
+
+    let mut server = HttpServer::bind("127.0.0.1:0").expect("bind http");
+    let response = get(&mut server, "/metrics", |_| {
+        HttpResponse::ok(
+            metrics::CONTENT_TYPE,
+            metrics::render(&analytics, &server_metrics, now),
+        )
+    });
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+
+    let src = uuid::Uuid::from_bytes(SRC_NODE_ID).to_string();
+    let dst = uuid::Uuid::from_bytes(DST_NODE_ID).to_string();
+    for line in [
+        "# TYPE simd_datagrams_received_total counter".to_string(),
+        "simd_datagrams_received_total 4".to_string(),
+        "simd_decode_failures_total 1".to_string(),
+        "simd_snapshots_total 1".to_string(),
+        "simd_nodes 2".to_string(),
+        "simd_edges 1".to_string(),
+        "simd_route_packets_total{src_domain=\"internal\",dst_domain=\"internal\"} 3".to_string(),
+        format!(
+            "simd_node_packets_total{{node=\"{src}\",name=\"http-api-node---\",domain=\"internal\"}} 3"
+        ),
+        "# TYPE simd_edge_loss_ratio gauge".to_string(),
+        format!("simd_edge_loss_ratio{{src=\"{src}\",dst=\"{dst}\",class=\"api\"}} 0"),
+    ] {
+        assert!(
+            response.lines().any(|candidate| candidate == line),
+            "missing `{line}` in:\n{response}"
+        );
+    }
+    assert!(response.contains("simd_edge_packets_per_second{"));
+    assert!(response.contains("simd_edge_latency_ewma_seconds{"));
+}
@@ -1,4 +1,7 @@
-use common::{NodeDomain, NodeId, TrafficClass, WireMessage};
+use common::{
+    Codec, NodeDomain, NodeId, RegisterAckPacket, RegisterOutcome, TrafficClass,
+    UnregisterAckPacket, UnregisterOutcome, WireMessage,
+};
 use server::analytics::AnalyticsManager;
 use std::net::SocketAddr;
 use std::str::FromStr;
@@ -16,26 +19,83 @@ fn handle_message(
 ) -> Option<WireMessage> {
     match message {
         WireMessage::RegisterNode(packet) => {
-            analytics.on_node_registered(&packet, src, now);
-            None
+            let outcome = analytics.on_node_registered(&packet, src, now);
+            Some(WireMessage::RegisterAck(RegisterAckPacket {
+                node_id: packet.node_id,
+                control_id: packet.control_id,
+                outcome,
+                server_timestamp_us: 0,
+            }))
         }
         WireMessage::UnregisterNode(packet) => {
-            analytics.on_node_unregistered(&packet, now);
+            let outcome = analytics.on_node_unregistered(&packet, now);
+            Some(WireMessage::UnregisterAck(UnregisterAckPacket {
+                node_id: packet.node_id,
+                control_id: packet.control_id,
+                outcome,
+                server_timestamp_us: 0,
+            }))
+        }
+        WireMessage::Data(packet) => analytics
+            .on_packet_received(src, &packet, now)
+            .map(WireMessage::Ack),
+        WireMessage::AckLossReport(report) => {
+            analytics.on_ack_loss_report(&report);
             None
         }
-        WireMessage::Data(packet) => {
-            let ack = analytics.on_packet_received(src, &packet, now);
-            Some(WireMessage::Ack(ack))
+        WireMessage::Heartbeat(packet) => {
+            analytics.on_heartbeat(&packet, src, now);
+            None
         }
+        WireMessage::DataBatch(packets) => Some(WireMessage::AckBatch(
+            analytics.on_batch_received(src, &packets, now).0,
+        )),
         WireMessage::RequestTopology => {
             let topology = analytics.export_topology_snapshot(now);
             Some(WireMessage::Topology(topology))
         }
+        WireMessage::RequestGroupedTopology { max_depth } => {
+            let topology = analytics.peek_grouped_topology_snapshot(now, max_depth);
+            Some(WireMessage::Topology(topology))
+        }
+        WireMessage::RequestFilteredTopology(query) => {
+            let topology = analytics.peek_filtered_topology_snapshot(now, &query);
+            Some(WireMessage::Topology(topology))
+        }
+        WireMessage::RequestPath {
+            src_node_id,
+            dst_node_id,
+        } => Some(WireMessage::Path(
+            analytics.shortest_path(src_node_id, dst_node_id),
+        )),
         WireMessage::RequestAnalytics => {
             let snapshot = analytics.export_snapshot();
             Some(WireMessage::Analytics(snapshot))
         }
-        WireMessage::Ack(_) | WireMessage::Topology(_) | WireMessage::Analytics(_) => None,
+        WireMessage::RequestEvents { after_seq } => {
+            Some(WireMessage::Events(analytics.events_since(after_seq, 64)))
+        }
+        WireMessage::Ack(_)
+        | WireMessage::Topology(_)
+        | WireMessage::Analytics(_)
+        | WireMessage::Path(_)
+        | WireMessage::Subscribe(_)
+        | WireMessage::Unsubscribe
+        | WireMessage::RequestServerInfo
+        | WireMessage::ServerInfo(_)
+        | WireMessage::Admin(_)
+        | WireMessage::AdminReply(_)
+        | WireMessage::Error { .. }
+        | WireMessage::RegisterAck(_)
+        | WireMessage::UnregisterAck(_)
+        | WireMessage::AckBatch(_)
+        | WireMessage::SetAckMode(_)
+        | WireMessage::AckModeSet(_)
+        | WireMessage::SelectiveAck(_)
+        | WireMessage::Events(_)
+        | WireMessage::SubscribeEvents { .. }
+        | WireMessage::EventsSubscribed { .. }
+        | WireMessage::UnsubscribeEvents => None,
     }
 }
 
@@ -156,3 +216,942 @@ fn register_send_remove_request_topology_flow() {
     assert!(topology_after_remove.edges.is_empty());
     assert!(!topology_after_remove.removed_edges.is_empty());
 }
+
+#[test]
+fn removals_reach_every_snapshot_consumer() {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let base = Instant::now();
+    let src = test_addr();
+    let leaving: NodeId = *b"FLOW-CHANGES-001";
+    let staying: NodeId = *b"FLOW-CHANGES-002";
+    for node_id in [leaving, staying] {
+        let register =
+            common::make_register_node_packet(node_id, *b"changes-node----", NodeDomain::Internal);
+        dispatch(
+            &mut analytics,
+            WireMessage::RegisterNode(register),
+            src,
+            base,
+        );
+    }
+    let packet = common::make_data_packet(
+        leaving,
+        staying,
+        1,
+        1,
+        TrafficClass::Api,
+        100,
+        *b"changes-node----",
+    );
+    dispatch(&mut analytics, WireMessage::Data(packet), src, base);
+    let topology = |message: Option<WireMessage>| match message {
+        Some(WireMessage::Topology(snapshot)) => snapshot,
+        other => panic!("expected topology, got {other:?}"),
+    };
+    let cursor = topology(dispatch(
+        &mut analytics,
+        WireMessage::RequestFilteredTopology(Default::default()),
+        src,
+        base,
+    ))
+    .change_seq;
+    let unregister = common::make_unregister_node_packet(leaving);
+    dispatch(
+        &mut analytics,
+        WireMessage::UnregisterNode(unregister),
+        src,
+        base,
+    );
+
+    // Grouped and filtered requests read the removals without taking them
+    // from the plain topology stream.
+    let grouped = topology(dispatch(
+        &mut analytics,
+        WireMessage::RequestGroupedTopology { max_depth: 0 },
+        src,
+        base,
+    ));
+    assert_eq!(grouped.removed_nodes, vec![leaving]);
+    let plain = topology(dispatch(
+        &mut analytics,
+        WireMessage::RequestTopology,
+        src,
+        base,
+    ));
+    assert_eq!(plain.removed_nodes, vec![leaving]);
+    assert_eq!(plain.removed_edges.len(), 1);
+    assert!(
+        topology(dispatch(
+            &mut analytics,
+            WireMessage::RequestTopology,
+            src,
+            base
+        ))
+        .removed_nodes
+        .is_empty()
+    );
+
+    // A consumer with its own cursor still gets them after the export.
+    let query = common::analytics::TopologyQuery {
+        after_change_seq: Some(cursor),
+        ..Default::default()
+    };
+    let own = topology(dispatch(
+        &mut analytics,
+        WireMessage::RequestFilteredTopology(query),
+        src,
+        base,
+    ));
+    assert_eq!(own.removed_nodes, vec![leaving]);
+    assert_eq!(own.removed_edges, plain.removed_edges);
+    assert!(
+        own.liveness_events
+            .iter()
+            .any(|event| event.node_id == leaving)
+    );
+    assert_eq!(own.change_seq, plain.change_seq);
+}
+
+/// Mirrors the server loop: detect the codec of raw bytes, answer in kind.
+fn dispatch_raw(
+    analytics: &mut AnalyticsManager,
+    bytes: &[u8],
+    src: SocketAddr,
+    now: Instant,
+) -> Option<Vec<u8>> {
+    let codec = Codec::detect(bytes);
+    let message = codec.decode_message(bytes).expect("message should decode");
+    let response = handle_message(analytics, message, src, now)?;
+    Some(
+        codec
+            .encode_message(&response)
+            .expect("response should encode"),
+    )
+}
+
+#[test]
+fn json_client_registers_and_reads_topology_with_uuid_ids() {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let now = Instant::now();
+    let src = test_addr();
+    let node_uuid = "6a736f6e-2d6e-6f64-652d-303030303031";
+
+    let register = format!(
+        r#"{{"RegisterNode":{{"node_id":"{node_uuid}","desc":[106,115,111,110,45,110,111,100,101,45,45,45,45,45,45,45],"domain":"Internal","parent_node_id":null,"timestamp_us":0}}}}"#
+    );
+    let ack = dispatch_raw(&mut analytics, register.as_bytes(), src, now).expect("ack expected");
+    let text = String::from_utf8(ack).expect("json reply");
+    assert!(text.starts_with(r#"{"RegisterAck":"#));
+    assert!(text.contains(r#""outcome":"Registered""#));
+
+    let reply =
+        dispatch_raw(&mut analytics, br#""RequestTopology""#, src, now).expect("topology expected");
+    let text = String::from_utf8(reply).expect("json reply");
+    assert!(text.starts_with(r#"{"Topology":"#));
+    assert!(text.contains(&format!(r#""node_id":"{node_uuid}""#)));
+
+    let postcard_reply = dispatch_raw(
+        &mut analytics,
+        &common::encode_message(&WireMessage::RequestTopology).expect("request should encode"),
+        src,
+        now,
+    )
+    .expect("topology expected");
+    match common::decode_message(&postcard_reply).expect("postcard reply") {
+        WireMessage::Topology(snapshot) => {
+            assert_eq!(snapshot.nodes[0].node_id, *b"json-node-000001");
+        }
+        _ => panic!("expected topology snapshot"),
+    }
+}
+
+#[test]
+fn register_and_unregister_report_their_outcome() {
+    let mut analytics = AnalyticsManager::new(5, 1);
+    let now = Instant::now();
+    let src = test_addr();
+    let first: NodeId = *b"ACK-FIRST-NODE01";
+    let second: NodeId = *b"ACK-SECOND-NODE2";
+    let desc = *b"ack-node--------";
+
+    let register = |analytics: &mut AnalyticsManager, node_id| match dispatch(
+        analytics,
+        WireMessage::RegisterNode(common::make_register_node_packet(
+            node_id,
+            desc,
+            NodeDomain::Internal,
+        )),
+        src,
+        now,
+    ) {
+        Some(WireMessage::RegisterAck(ack)) => {
+            assert_eq!(ack.node_id, node_id);
+            ack.outcome
+        }
+        other => panic!("expected register ack, got {other:?}"),
+    };
+    assert_eq!(register(&mut analytics, first), RegisterOutcome::Registered);
+    assert_eq!(register(&mut analytics, first), RegisterOutcome::Refreshed);
+    assert_eq!(
+        register(&mut analytics, second),
+        RegisterOutcome::NodeLimitReached
+    );
+
+    // Data toward an untracked node is dropped and reported by seq.
+    let data =
+        |seq| common::make_data_packet(first, second, seq, seq, TrafficClass::Api, 100, desc);
+    assert!(analytics.on_packet_received(src, &data(7), now).is_none());
+    let (ack, dropped) = analytics.on_batch_received(src, &[data(8), data(9)], now);
+    assert!(ack.original_seqs.is_empty());
+    assert_eq!(dropped, vec![8, 9]);
+
+    // Neither endpoint is tracked when only one of them would fit.
+    let mut full = AnalyticsManager::new(5, 1);
+    assert!(full.on_packet_received(src, &data(10), now).is_none());
+    assert_eq!(full.node_count(), 0);
+    assert_eq!(analytics.edge_count(), 0);
+
+    let unregister = |analytics: &mut AnalyticsManager, node_id| match dispatch(
+        analytics,
+        WireMessage::UnregisterNode(common::make_unregister_node_packet(node_id)),
+        src,
+        now,
+    ) {
+        Some(WireMessage::UnregisterAck(ack)) => ack.outcome,
+        other => panic!("expected unregister ack, got {other:?}"),
+    };
+    assert_eq!(
+        unregister(&mut analytics, first),
+        UnregisterOutcome::Unregistered
+    );
+    assert_eq!(
+        unregister(&mut analytics, first),
+        UnregisterOutcome::UnknownNode
+    );
+}
+
+#[test]
+fn retransmitted_control_messages_are_applied_once() {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let now = Instant::now();
+    let src = test_addr();
+    let node_id: NodeId = *b"RETRY-NODE-00001";
+
+    let register = WireMessage::RegisterNode(common::RegisterNodePacket {
+        control_id: 7,
+        ..common::make_register_node_packet(node_id, *b"retry-node------", NodeDomain::Internal)
+    });
+    let unregister = WireMessage::UnregisterNode(common::UnregisterNodePacket {
+        control_id: 8,
+        ..common::make_unregister_node_packet(node_id)
+    });
+
+    for _ in 0..2 {
+        match dispatch(&mut analytics, register.clone(), src, now) {
+            Some(WireMessage::RegisterAck(ack)) => {
+                assert_eq!(ack.control_id, 7);
+                assert_eq!(ack.outcome, RegisterOutcome::Registered);
+            }
+            other => panic!("expected register ack, got {other:?}"),
+        }
+    }
+    for _ in 0..2 {
+        match dispatch(&mut analytics, unregister.clone(), src, now) {
+            Some(WireMessage::UnregisterAck(ack)) => {
+                assert_eq!(ack.control_id, 8);
+                assert_eq!(ack.outcome, UnregisterOutcome::Unregistered);
+            }
+            other => panic!("expected unregister ack, got {other:?}"),
+        }
+    }
+
+    // A register retransmission arriving late must not resurrect the node.
+    match dispatch(&mut analytics, register, src, now) {
+        Some(WireMessage::RegisterAck(ack)) => {
+            assert_eq!(ack.outcome, RegisterOutcome::Superseded)
+        }
+        other => panic!("expected register ack, got {other:?}"),
+    }
+    assert_eq!(analytics.node_count(), 0);
+}
+
+#[test]
+fn restarted_client_registers_again_with_a_new_session() {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let now = Instant::now();
+    let src = test_addr();
+    let node_id: NodeId = *b"RESTART-NODE-001";
+    let register = |session, control_id| {
+        WireMessage::RegisterNode(common::RegisterNodePacket {
+            control_id,
+            session,
+            ..common::make_register_node_packet(node_id, *b"restart-node----", NodeDomain::Internal)
+        })
+    };
+    let outcome = |reply| match reply {
+        Some(WireMessage::RegisterAck(ack)) => ack.outcome,
+        other => panic!("expected register ack, got {other:?}"),
+    };
+
+    // The first run registers a few times, then quits without unregistering.
+    for control_id in 1..=3 {
+        dispatch(&mut analytics, register(11, control_id), src, now);
+    }
+    // Its ids restart at 1 after a relaunch; a new session is not stale.
+    assert_eq!(
+        outcome(dispatch(&mut analytics, register(22, 1), src, now)),
+        RegisterOutcome::Refreshed
+    );
+    assert_eq!(
+        outcome(dispatch(&mut analytics, register(22, 1), src, now)),
+        RegisterOutcome::Refreshed
+    );
+    // Late retransmissions within the new session are still deduplicated.
+    dispatch(&mut analytics, register(22, 2), src, now);
+    assert_eq!(
+        outcome(dispatch(&mut analytics, register(22, 1), src, now)),
+        RegisterOutcome::Superseded
+    );
+}
+
+#[test]
+fn data_batch_is_acked_as_one_and_updates_every_edge() {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let now = Instant::now();
+    let src = test_addr();
+    let src_node_id: NodeId = *b"BATCH-SRC-NODE01";
+    let desc = *b"batch-src-------";
+
+    let records: Vec<_> = [
+        (*b"BATCH-DST-NODE01", TrafficClass::Api),
+        (*b"BATCH-DST-NODE01", TrafficClass::Background),
+        (*b"BATCH-DST-NODE02", TrafficClass::Api),
+    ]
+    .into_iter()
+    .zip(10..)
+    .map(|((dst_node_id, class), seq)| {
+        common::make_data_packet(src_node_id, dst_node_id, seq, seq, class, 1200, desc)
+    })
+    .collect();
+
+    match dispatch(&mut analytics, WireMessage::DataBatch(records), src, now) {
+        Some(WireMessage::AckBatch(ack)) => assert_eq!(ack.original_seqs, vec![10, 11, 12]),
+        other => panic!("expected ack batch, got {other:?}"),
+    }
+    assert_eq!(analytics.edge_count(), 3);
+    assert_eq!(analytics.global_stats().total_packets, 3);
+}
+
+#[test]
+fn ack_loss_reports_set_reverse_loss_on_the_reported_edge() {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let now = Instant::now();
+    let src = test_addr();
+    let src_node_id: NodeId = *b"ACKLOSS-SRC-0001";
+    let dst_node_id: NodeId = *b"ACKLOSS-DST-0001";
+    let desc = *b"ackloss-src-----";
+
+    for (seq, class) in [(0, TrafficClass::Api), (1, TrafficClass::Background)] {
+        let packet = common::make_data_packet(src_node_id, dst_node_id, seq, 0, class, 100, desc);
+        dispatch(&mut analytics, WireMessage::Data(packet), src, now);
+    }
+    let report = common::ack::AckLossReport {
+        src_node_id,
+        entries: vec![
+            common::ack::AckLossEntry {
+                dst_node_id,
+                class: TrafficClass::Api,
+                acked: 3,
+                lost: 1,
+            },
+            // No such edge; ignored.
+            common::ack::AckLossEntry {
+                dst_node_id: src_node_id,
+                class: TrafficClass::Api,
+                acked: 0,
+                lost: 5,
+            },
+        ],
+    };
+    assert!(dispatch(&mut analytics, WireMessage::AckLossReport(report), src, now).is_none());
+
+    let snapshot = analytics.export_topology_snapshot(now);
+    let reverse_loss = |class| {
+        snapshot
+            .edges
+            .iter()
+            .find(|edge| edge.class == class)
+            .map(|edge| edge.reverse_loss_rate)
+    };
+    assert_eq!(reverse_loss(TrafficClass::Api), Some(0.25));
+    assert_eq!(reverse_loss(TrafficClass::Background), Some(0.0));
+    assert_eq!(snapshot.edges.len(), 2);
+
+    // A small clean report pulls the rate down without resetting it.
+    let report = common::ack::AckLossReport {
+        src_node_id,
+        entries: vec![common::ack::AckLossEntry {
+            dst_node_id,
+            class: TrafficClass::Api,
+            acked: 1,
+            lost: 0,
+        }],
+    };
+    dispatch(&mut analytics, WireMessage::AckLossReport(report), src, now);
+    let rate = analytics
+        .export_topology_snapshot(now)
+        .edges
+        .iter()
+        .find(|edge| edge.class == TrafficClass::Api)
+        .map(|edge| edge.reverse_loss_rate)
+        .expect("api edge");
+    assert!(rate > 0.15 && rate < 0.25, "{rate}");
+}
+
+#[test]
+fn topology_changes_are_streamed_as_sequenced_events() {
+    use common::analytics::RemovalReason;
+    use common::config::{AnalyticsConfig, EventThresholds};
+    use common::events::{EdgeMetric, TopologyEventKind};
+    use std::sync::mpsc;
+
+    let mut analytics = AnalyticsManager::with_config(AnalyticsConfig {
+        event_thresholds: EventThresholds {
+            latency_ewma_us: 100_000.0,
+            loss_rate: 0.0,
+            loss_burst_packets: 10,
+        },
+        ..AnalyticsConfig::default()
+    });
+    let (sender, receiver) = mpsc::channel();
+    analytics.add_event_listener(move |event| sender.send(event.seq).expect("listener"));
+    let now = Instant::now();
+    let src = test_addr();
+    let src_node_id: NodeId = *b"EVENTS-SRC-NODE1";
+    let dst_node_id: NodeId = *b"EVENTS-DST-NODE1";
+    let desc = *b"events-src------";
+
+    for node_id in [src_node_id, dst_node_id] {
+        let packet = common::make_register_node_packet(node_id, desc, NodeDomain::Internal);
+        dispatch(&mut analytics, WireMessage::RegisterNode(packet), src, now);
+    }
+    // Half a second old, so the first latency sample is over the threshold;
+    // the second skips 19 sequence numbers.
+    for class_seq in [0, 20] {
+        let mut packet = common::make_data_packet(
+            src_node_id,
+            dst_node_id,
+            class_seq,
+            class_seq,
+            TrafficClass::Api,
+            100,
+            desc,
+        );
+        packet.timestamp_us -= 500_000;
+        dispatch(&mut analytics, WireMessage::Data(packet), src, now);
+    }
+    let relabel =
+        common::make_register_node_packet(src_node_id, *b"events-renamed--", NodeDomain::Internal);
+    dispatch(&mut analytics, WireMessage::RegisterNode(relabel), src, now);
+    let unregister = common::make_unregister_node_packet(dst_node_id);
+    dispatch(
+        &mut analytics,
+        WireMessage::UnregisterNode(unregister),
+        src,
+        now,
+    );
+
+    let batch = match dispatch(
+        &mut analytics,
+        WireMessage::RequestEvents { after_seq: 0 },
+        src,
+        now,
+    ) {
+        Some(WireMessage::Events(batch)) => batch,
+        other => panic!("expected events, got {other:?}"),
+    };
+    assert_eq!(batch.missed, 0);
+    let seqs: Vec<u64> = batch.events.iter().map(|event| event.seq).collect();
+    assert_eq!(seqs, (1..=8).collect::<Vec<_>>());
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), seqs);
+
+    let kinds: Vec<&TopologyEventKind> = batch.events.iter().map(|event| &event.kind).collect();
+    assert!(
+        matches!(kinds[0], TopologyEventKind::NodeAdded { node_id, .. } if *node_id == src_node_id)
+    );
+    assert!(
+        matches!(kinds[1], TopologyEventKind::NodeAdded { node_id, .. } if *node_id == dst_node_id)
+    );
+    assert!(matches!(
+        kinds[2],
+        TopologyEventKind::EdgeAdded {
+            class: TrafficClass::Api,
+            ..
+        }
+    ));
+    assert!(matches!(
+        kinds[3],
+        TopologyEventKind::EdgeMetricThresholdCrossed {
+            metric: EdgeMetric::LatencyEwmaUs,
+            above: true,
+            ..
+        }
+    ));
+    assert!(matches!(
+        kinds[4],
+        TopologyEventKind::LossBurstDetected { missing: 19, .. }
+    ));
+    assert!(matches!(
+        kinds[5],
+        TopologyEventKind::NodeUpdated { desc, .. } if desc == b"events-renamed--"
+    ));
+    assert!(matches!(
+        kinds[6],
+        TopologyEventKind::EdgeRemoved {
+            reason: RemovalReason::EndpointRemoved,
+            ..
+        }
+    ));
+    assert!(matches!(
+        kinds[7],
+        TopologyEventKind::NodeRemoved {
+            reason: RemovalReason::Unregistered,
+            ..
+        }
+    ));
+
+    match dispatch(
+        &mut analytics,
+        WireMessage::RequestEvents { after_seq: 6 },
+        src,
+        now,
+    ) {
+        Some(WireMessage::Events(batch)) => {
+            assert_eq!(batch.events.len(), 2);
+            assert_eq!(batch.events[0].seq, 7);
+        }
+        other => panic!("expected events, got {other:?}"),
+    }
+}
+
+#[test]
+fn observers_see_lifecycle_packets_and_cleanup_sweeps() {
+    use common::config::ObserversConfig;
+    use server::observer::{
+        self, CleanupSweep, EdgeCsvExporter, EdgeMetrics, PacketObserver, TopologyObserver,
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl PacketObserver for Recorder {
+        fn on_packet(&mut self, packet: &common::DataPacket, edge: &EdgeMetrics, _now: Instant) {
+            self.0.lock().unwrap().push(format!(
+                "packet {} edge_packets={} latency={}",
+                packet.global_seq,
+                edge.packets,
+                edge.last_latency_us.is_some()
+            ));
+        }
+    }
+
+    impl TopologyObserver for Recorder {
+        fn on_node_registered(
+            &mut self,
+            _packet: &common::RegisterNodePacket,
+            outcome: RegisterOutcome,
+            _now: Instant,
+        ) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("registered {outcome:?}"));
+        }
+
+        fn on_node_unregistered(
+            &mut self,
+            _packet: &common::UnregisterNodePacket,
+            outcome: UnregisterOutcome,
+            _now: Instant,
+        ) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("unregistered {outcome:?}"));
+        }
+
+        fn on_cleanup(&mut self, sweep: &CleanupSweep<'_>, _now: Instant) {
+            self.0.lock().unwrap().push(format!(
+                "cleanup nodes={} edges={} remaining={}",
+                sweep.removed_nodes.len(),
+                sweep.removed_edges.len(),
+                sweep.edges.len()
+            ));
+        }
+    }
+
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let recorder = Recorder::default();
+    analytics.add_packet_observer(recorder.clone());
+    analytics.add_topology_observer(recorder.clone());
+    let csv_path = std::env::temp_dir().join(format!("simd-edges-{}.csv", std::process::id()));
+    let _ = std::fs::remove_file(&csv_path);
+    observer::install(
+        &mut analytics,
+        &ObserversConfig {
+            edge_csv: Some(csv_path.to_string_lossy().into_owned()),
+            ..ObserversConfig::default()
+        },
+    )
+    .expect("install built-in observers");
+
+    let now = Instant::now();
+    let src = test_addr();
+    let src_node_id: NodeId = *b"OBSERVE-SRC-NODE";
+    let dst_node_id: NodeId = *b"OBSERVE-DST-NODE";
+    let desc = *b"observe-src-----";
+    let register = common::make_register_node_packet(src_node_id, desc, NodeDomain::Internal);
+    dispatch(
+        &mut analytics,
+        WireMessage::RegisterNode(register),
+        src,
+        now,
+    );
+    for seq in [1, 2] {
+        let packet = common::make_data_packet(
+            src_node_id,
+            dst_node_id,
+            seq,
+            seq,
+            TrafficClass::Api,
+            100,
+            desc,
+        );
+        dispatch(&mut analytics, WireMessage::Data(packet), src, now);
+    }
+    analytics.cleanup_expired(now);
+    let unregister = common::make_unregister_node_packet(src_node_id);
+    dispatch(
+        &mut analytics,
+        WireMessage::UnregisterNode(unregister),
+        src,
+        now,
+    );
+    analytics.cleanup_expired(now);
+
+    assert_eq!(
+        *recorder.0.lock().unwrap(),
+        [
+            "registered Registered",
+            "packet 1 edge_packets=1 latency=true",
+            "packet 2 edge_packets=2 latency=true",
+            "cleanup nodes=0 edges=0 remaining=1",
+            "unregistered Unregistered",
+            "cleanup nodes=0 edges=0 remaining=0",
+        ]
+    );
+
+    let csv = std::fs::read_to_string(&csv_path).expect("edge csv");
+    let _ = std::fs::remove_file(&csv_path);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], EdgeCsvExporter::HEADER);
+    assert!(lines[1].contains(",api,2,200,"), "{}", lines[1]);
+}
+
+#[test]
+fn alert_rules_go_pending_firing_and_resolved_with_hysteresis() {
+    use common::alerts::{AlertMetric, AlertRule, AlertScope, AlertState, AlertTarget};
+    use common::config::{AnalyticsConfig, ObserversConfig};
+    use common::events::TopologyEventKind;
+    use server::observer;
+
+    let rule = |name: &str, class| AlertRule {
+        name: name.to_string(),
+        metric: AlertMetric::LatencyEwmaUs,
+        op: Default::default(),
+        threshold: 100_000.0,
+        clear_threshold: Some(50_000.0),
+        for_secs: 2,
+        scope: AlertScope {
+            class: Some(class),
+            ..AlertScope::default()
+        },
+    };
+    let mut analytics = AnalyticsManager::with_config(AnalyticsConfig {
+        alert_rules: vec![
+            rule("slow-api", TrafficClass::Api),
+            rule("slow-background", TrafficClass::Background),
+        ],
+        ..AnalyticsConfig::default()
+    });
+    let log_path = std::env::temp_dir().join(format!("simd-alerts-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&log_path);
+    observer::install(
+        &mut analytics,
+        &ObserversConfig {
+            alert_log: Some(log_path.to_string_lossy().into_owned()),
+            ..ObserversConfig::default()
+        },
+    )
+    .expect("install alert log");
+
+    let base = Instant::now();
+    let src = test_addr();
+    let src_node_id: NodeId = *b"ALERT-SRC-NODE01";
+    let dst_node_id: NodeId = *b"ALERT-DST-NODE01";
+    let desc = *b"alert-src-------";
+    let mut seq = 0;
+    let mut send = |analytics: &mut AnalyticsManager, age_us: u64| {
+        seq += 1;
+        let mut packet = common::make_data_packet(
+            src_node_id,
+            dst_node_id,
+            seq,
+            seq,
+            TrafficClass::Api,
+            100,
+            desc,
+        );
+        packet.timestamp_us -= age_us;
+        dispatch(analytics, WireMessage::Data(packet), src, base);
+    };
+    let alert_events = |analytics: &AnalyticsManager| -> Vec<AlertState> {
+        analytics
+            .events_since(0, 64)
+            .events
+            .into_iter()
+            .filter_map(|event| match event.kind {
+                TopologyEventKind::AlertChanged { alert } => Some(alert.state),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Half a second of latency: pending first, firing once held for 2s.
+    send(&mut analytics, 500_000);
+    analytics.evaluate_alerts(base);
+    let pending = analytics.peek_topology_snapshot(base).alerts;
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].state, AlertState::Pending);
+    assert_eq!(alert_events(&analytics), [AlertState::Pending]);
+    analytics.evaluate_alerts(base + Duration::from_secs(2));
+    let firing = analytics.peek_topology_snapshot(base).alerts;
+    assert_eq!(firing.len(), 1);
+    assert_eq!(firing[0].rule, "slow-api");
+    assert_eq!(firing[0].state, AlertState::Firing);
+    let edge_id = analytics.peek_topology_snapshot(base).edges[0].edge_id;
+    assert_eq!(firing[0].target, AlertTarget::Edge(edge_id));
+    assert_eq!(
+        alert_events(&analytics),
+        [AlertState::Pending, AlertState::Firing]
+    );
+
+    // Fresh packets pull the EWMA below the threshold but not yet below the
+    // clear threshold, so the alert keeps firing...
+    for _ in 0..8 {
+        send(&mut analytics, 0);
+    }
+    analytics.evaluate_alerts(base + Duration::from_secs(3));
+    assert_eq!(analytics.peek_topology_snapshot(base).alerts.len(), 1);
+
+    // ...until it drops under it.
+    for _ in 0..8 {
+        send(&mut analytics, 0);
+    }
+    analytics.evaluate_alerts(base + Duration::from_secs(4));
+    assert!(analytics.peek_topology_snapshot(base).alerts.is_empty());
+    assert_eq!(
+        alert_events(&analytics),
+        [
+            AlertState::Pending,
+            AlertState::Firing,
+            AlertState::Resolved
+        ]
+    );
+
+    let log = std::fs::read_to_string(&log_path).expect("alert log");
+    let _ = std::fs::remove_file(&log_path);
+    let states: Vec<String> = log
+        .lines()
+        .map(|line| {
+            let alert: serde_json::Value = serde_json::from_str(line).expect("json line");
+            alert["state"].as_str().expect("state").to_string()
+        })
+        .collect();
+    assert_eq!(states, ["Pending", "Firing", "Resolved"]);
+}
+
+#[test]
+fn loss_alerts_use_their_own_window_between_evaluations() {
+    use common::alerts::{AlertMetric, AlertRule, AlertScope, AlertState};
+    use common::config::AnalyticsConfig;
+
+    let mut analytics = AnalyticsManager::with_config(AnalyticsConfig {
+        alert_rules: vec![AlertRule {
+            name: "lossy".to_string(),
+            metric: AlertMetric::LossRateWindow,
+            op: Default::default(),
+            threshold: 0.2,
+            clear_threshold: None,
+            for_secs: 0,
+            scope: AlertScope::default(),
+        }],
+        ..AnalyticsConfig::default()
+    });
+    let base = Instant::now();
+    let src = test_addr();
+    let src_node_id: NodeId = *b"LOSSALRT-SRC-001";
+    let dst_node_id: NodeId = *b"LOSSALRT-DST-001";
+    let desc = *b"lossalrt-src----";
+    let send = |analytics: &mut AnalyticsManager, class_seqs: &[u32]| {
+        for &class_seq in class_seqs {
+            let packet = common::make_data_packet(
+                src_node_id,
+                dst_node_id,
+                class_seq,
+                class_seq,
+                TrafficClass::Api,
+                100,
+                desc,
+            );
+            dispatch(analytics, WireMessage::Data(packet), src, base);
+        }
+    };
+
+    // Every other sequence number skipped, then a clean second; no export
+    // runs in between, yet the second evaluation sees only the clean one.
+    send(&mut analytics, &[0, 2, 4, 6, 8, 10]);
+    analytics.evaluate_alerts(base);
+    let firing = analytics.peek_topology_snapshot(base).alerts;
+    assert_eq!(firing.len(), 1);
+    assert_eq!(firing[0].state, AlertState::Firing);
+
+    send(&mut analytics, &[11, 12, 13, 14, 15, 16]);
+    analytics.evaluate_alerts(base + Duration::from_secs(1));
+    assert!(analytics.peek_topology_snapshot(base).alerts.is_empty());
+}
+
+#[test]
+fn anomaly_loss_baseline_ignores_snapshot_exports() {
+    use common::config::{AnalyticsConfig, AnomalyConfig};
+    use common::events::{EdgeMetric, TopologyEventKind};
+
+    let mut analytics = AnalyticsManager::with_config(AnalyticsConfig {
+        anomaly: AnomalyConfig {
+            score_threshold: 3.0,
+            baseline_alpha: 0.1,
+            warmup_samples: 3,
+        },
+        ..AnalyticsConfig::default()
+    });
+    let base = Instant::now();
+    let src = test_addr();
+    let src_node_id: NodeId = *b"ANOMALY-SRC-0001";
+    let dst_node_id: NodeId = *b"ANOMALY-DST-0001";
+    let desc = *b"anomaly-src-----";
+    let mut class_seq = 0;
+    let mut send_second = |analytics: &mut AnalyticsManager, second: u64, skipped: u32| {
+        class_seq += skipped;
+        for _ in 0..10 {
+            class_seq += 1;
+            let packet = common::make_data_packet(
+                src_node_id,
+                dst_node_id,
+                class_seq,
+                class_seq,
+                TrafficClass::Api,
+                100,
+                desc,
+            );
+            let now = base + Duration::from_secs(second);
+            dispatch(analytics, WireMessage::Data(packet), src, now);
+        }
+    };
+
+    for second in 0..6 {
+        send_second(&mut analytics, second, 0);
+        analytics.evaluate_anomalies(base + Duration::from_secs(second));
+    }
+    // Ten records lost, then an export resets the snapshot loss window
+    // before the anomaly evaluation runs.
+    send_second(&mut analytics, 6, 10);
+    analytics.export_topology_snapshot(base + Duration::from_secs(6));
+    analytics.evaluate_anomalies(base + Duration::from_secs(6));
+
+    let loss_flagged = analytics
+        .events_since(0, 64)
+        .events
+        .into_iter()
+        .any(|event| {
+            matches!(
+                event.kind,
+                TopologyEventKind::EdgeAnomalyChanged {
+                    metric: EdgeMetric::LossRate,
+                    anomalous: true,
+                    ..
+                }
+            )
+        });
+    assert!(loss_flagged);
+}
+
+#[test]
+fn loss_threshold_crossings_are_checked_without_exports() {
+    use common::config::{AnalyticsConfig, EventThresholds};
+    use common::events::{EdgeMetric, TopologyEventKind};
+
+    let mut analytics = AnalyticsManager::with_config(AnalyticsConfig {
+        event_thresholds: EventThresholds {
+            loss_rate: 0.2,
+            ..EventThresholds::default()
+        },
+        ..AnalyticsConfig::default()
+    });
+    let now = Instant::now();
+    let src = test_addr();
+    let src_node_id: NodeId = *b"LOSSTHR-SRC-0001";
+    let dst_node_id: NodeId = *b"LOSSTHR-DST-0001";
+    let desc = *b"lossthr-src-----";
+    let send = |analytics: &mut AnalyticsManager, class_seqs: &[u32]| {
+        for &class_seq in class_seqs {
+            let packet = common::make_data_packet(
+                src_node_id,
+                dst_node_id,
+                class_seq,
+                class_seq,
+                TrafficClass::Api,
+                100,
+                desc,
+            );
+            dispatch(analytics, WireMessage::Data(packet), src, now);
+        }
+    };
+    let loss_crossings = |analytics: &AnalyticsManager| -> Vec<bool> {
+        analytics
+            .events_since(0, 64)
+            .events
+            .into_iter()
+            .filter_map(|event| match event.kind {
+                TopologyEventKind::EdgeMetricThresholdCrossed {
+                    metric: EdgeMetric::LossRate,
+                    above,
+                    ..
+                } => Some(above),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Every other sequence number skipped.
+    send(&mut analytics, &[0, 2, 4, 6, 8, 10]);
+    analytics.evaluate_loss_thresholds();
+    assert_eq!(loss_crossings(&analytics), [true]);
+
+    // An export between checks does not reset the threshold window.
+    send(&mut analytics, &[11, 12, 13, 14, 15, 16]);
+    analytics.export_topology_snapshot(now);
+    analytics.evaluate_loss_thresholds();
+    assert_eq!(loss_crossings(&analytics), [true, false]);
+}
@@ -0,0 +1,116 @@
+use common::analytics::{TopologySnapshot, TopologySubscription};
+use common::{Codec, NodeDomain, NodeId, TrafficClass, WireMessage};
+use server::analytics::AnalyticsManager;
+use server::subscription::DeltaTracker;
+use server::websocket::WebSocketServer;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tungstenite::Message;
+
+const SRC_NODE_ID: NodeId = *b"WS-FLOW-SRC-NODE";
+const DST_NODE_ID: NodeId = *b"WS-FLOW-DST-NODE";
+
+fn seeded_analytics(now: Instant) -> AnalyticsManager {
+    let mut analytics = AnalyticsManager::new(5, 100);
+    let addr = SocketAddr::from_str("127.0.0.1:59091").expect("valid socket");
+    let desc = *b"ws-flow-node----";
+    analytics.on_node_registered(
+        &common::make_register_node_packet(SRC_NODE_ID, desc, NodeDomain::Internal),
+        addr,
+        now,
+    );
+    let packet =
+        common::make_data_packet(SRC_NODE_ID, DST_NODE_ID, 1, 1, TrafficClass::Api, 100, desc);
+    analytics.on_packet_received(addr, &packet, now);
+    analytics
+}
+
+#[test]
+fn websocket_client_requests_and_subscribes_to_delta_pushes() {
+    let now = Instant::now();
+    let mut analytics = seeded_analytics(now);
+    let mut server = WebSocketServer::bind("127.0.0.1:0").expect("bind websocket");
+    let url = format!("ws://{}", server.local_addr().expect("local addr"));
+
+    let (events, received) = mpsc::channel();
+    let client = thread::spawn(move || {
+        let (mut socket, _) = tungstenite::connect(url).expect("connect");
+
+        socket
+            .send(Message::text("\"RequestTopology\""))
+            .expect("send request");
+        let reply = match socket.read().expect("read reply") {
+            Message::Text(text) => text.to_string(),
+            other => panic!("expected text reply, got {other:?}"),
+        };
+
+        let subscribe = Codec::Postcard
+            .encode_message(&WireMessage::Subscribe(TopologySubscription {
+                delta: true,
+            }))
+            .expect("encode subscribe");
+        socket
+            .send(Message::binary(subscribe))
+            .expect("send subscribe");
+
+        let pushes: Vec<TopologySnapshot> = (0..3)
+            .map(|_| match socket.read().expect("read push") {
+                Message::Binary(bytes) => match Codec::Postcard.decode_message(&bytes) {
+                    Ok(WireMessage::Topology(snapshot)) => snapshot,
+                    _ => panic!("expected topology push"),
+                },
+                other => panic!("expected binary push, got {other:?}"),
+            })
+            .collect();
+        events.send((reply, pushes)).expect("report");
+    });
+
+    let mut tracker = DeltaTracker::new();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut pushed = false;
+    let outcome = loop {
+        assert!(Instant::now() < deadline, "websocket flow timed out");
+        server.poll(|message, src| match message {
+            WireMessage::RequestTopology => Some(WireMessage::Topology(
+                analytics.export_topology_snapshot(now),
+            )),
+            other => panic!("unexpected request {other:?} from {src}"),
+        });
+        if !pushed && server.has_subscribers() {
+            // Prime the tracker before the subscription so the first push
+            // to this subscriber is only full because it is its first.
+            let _ = tracker.push(analytics.export_topology_snapshot(now));
+            for _ in 0..3 {
+                let push = tracker.push(analytics.export_topology_snapshot(now));
+                assert_eq!(server.push(&push), 1);
+            }
+            pushed = true;
+        }
+        if let Ok(outcome) = received.recv_timeout(Duration::from_millis(10)) {
+            break outcome;
+        }
+    };
+    client.join().expect("client thread");
+
+    let (reply, pushes) = outcome;
+    let src_uuid = uuid::Uuid::from_bytes(SRC_NODE_ID).to_string();
+    assert!(reply.starts_with("{\"Topology\":"));
+    assert!(reply.contains(&format!("\"node_id\":\"{src_uuid}\"")));
+
+    assert_eq!(server.connection_count(), 1);
+    assert!(
+        pushes[0]
+            .nodes
+            .iter()
+            .any(|node| node.node_id == SRC_NODE_ID)
+    );
+    assert_eq!(pushes[0].edges.len(), 1);
+    for push in &pushes[1..] {
+        assert!(push.nodes.is_empty(), "nothing changed between pushes");
+        assert!(push.edges.is_empty());
+    }
+    assert_eq!(pushes[2].snapshot_seq, pushes[0].snapshot_seq + 2);
+}